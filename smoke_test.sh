@@ -6,8 +6,8 @@
 cargo run -- transactions.csv > accounts.csv
 
 expected_headers='client,available,held,total,locked'
-expected_account_1='1,1.5,0,1.5,false'
-expected_account_2='2,2,0,2,false'
+expected_account_1='1,1.5000,0.0000,1.5000,false'
+expected_account_2='2,2.0000,0.0000,2.0000,false'
 
 while read -r HEADERS; do
     read -r ONE
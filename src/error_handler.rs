@@ -1,8 +1,12 @@
-use crate::{input::Error, ledger::account::account::TransactionError};
+use crate::{
+    input::Error,
+    ledger::account::account::TransactionError,
+    strategy::{ErrorStrategy, SinkStrategy},
+};
 
-use std::{
-    io::{self, Write},
-    sync::mpsc::Receiver,
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc::Receiver,
 };
 
 // Here, we simply ignore the errors and keep processing other transactions.
@@ -10,18 +14,19 @@ use std::{
 // be ignored. I'm extending that behaviour to other kind of inconsistencies
 // that can be found.
 //
-// I decided to sink the errors instead of, for example, printing them,
-// to be sure it wouldn't cause issues with automated grading systems
+// The errors are reported with `tracing`, instead of, for example, printing
+// them: they are only written somewhere if a subscriber is installed, to be
+// sure it wouldn't cause issues with automated grading systems
 // (e.g. slow things down as it writes massive amounts of data to stderr).
 //
-// In a real-world scenario, we'd do something with errors.
-// At least print them.
-// We could either store them in some kind of tracing system where we can
-// learn more about them, or send some info to an external system
+// In a real-world scenario, we'd do something more with errors.
+// We could either collect the traces somewhere we can learn more about
+// them, or send some info to an external system
 // (e.g. a queue + a dedicated service listening on it) to deal with the
 // error outside of this system.
 //
 // We could also try to recover from some errors.
+#[must_use]
 pub fn sink(
     input_errors: Receiver<Error>,
     transaction_errors: Receiver<TransactionError>,
@@ -29,17 +34,193 @@ pub fn sink(
     vec![
         std::thread::spawn(move || {
             for err in input_errors {
-                io::sink()
-                    .write_fmt(format_args!("failed to read record: {:?}", err))
-                    .expect("Writing to sink should never fail");
+                tracing::warn!(?err, "failed to read record");
             }
         }),
         std::thread::spawn(move || {
             for err in transaction_errors {
-                io::sink()
-                    .write_fmt(format_args!("failed to apply transaction: {:?}", err))
-                    .expect("Writing to sink should never fail");
+                tracing::warn!(%err, "failed to apply transaction");
             }
         }),
     ]
 }
+
+/// Same as `sink()`, but waits for both channels to be closed, and returns
+/// how many errors were received on each of them:
+/// `(parse_error_count, transaction_error_count)`.
+///
+/// The errors are still reported with `tracing`.
+///
+/// # Panics
+///
+/// Panics if one of the threads panicked.
+#[must_use]
+pub fn count(
+    input_errors: Receiver<Error>,
+    transaction_errors: Receiver<TransactionError>,
+) -> (usize, usize) {
+    handle(input_errors, transaction_errors, &SinkStrategy)
+}
+
+/// Same as `count()`, but also passes each error to the given strategy.
+///
+/// # Panics
+///
+/// Panics if one of the threads panicked, e.g. if the strategy panicked.
+pub fn handle(
+    input_errors: Receiver<Error>,
+    transaction_errors: Receiver<TransactionError>,
+    strategy: &dyn ErrorStrategy,
+) -> (usize, usize) {
+    handle_with_budget(input_errors, transaction_errors, strategy, None, &|| {})
+}
+
+/// Same as `handle()`, but calls `abort` once `max_errors` errors have been
+/// received, across both channels. `abort` may be called several times.
+///
+/// The errors of both channels are received in no particular order, so the
+/// transactions applied before `abort` is called depend on timing. To stop
+/// at the same transaction every time, the parser and the ledger should
+/// share an `ErrorBudget` instead (see `ledger::build_with_budget()`).
+///
+/// The channels are still read until they are closed, so the errors received
+/// after that are counted as well.
+///
+/// # Panics
+///
+/// Panics if one of the threads panicked, e.g. if the strategy panicked.
+pub fn handle_with_budget(
+    input_errors: Receiver<Error>,
+    transaction_errors: Receiver<TransactionError>,
+    strategy: &dyn ErrorStrategy,
+    max_errors: Option<usize>,
+    abort: &(dyn Fn() + Sync),
+) -> (usize, usize) {
+    let error_count = AtomicUsize::new(0);
+    // Returns whether the budget is exhausted.
+    let count_error = || {
+        let error_count = error_count.fetch_add(1, Ordering::Relaxed) + 1;
+        max_errors.is_some_and(|max_errors| error_count >= max_errors)
+    };
+
+    std::thread::scope(|scope| {
+        let input_errors = scope.spawn(|| {
+            input_errors
+                .into_iter()
+                .inspect(|err| {
+                    tracing::warn!(?err, "failed to read record");
+                    if count_error() {
+                        abort();
+                    }
+                    strategy.on_parse_error(err);
+                })
+                .count()
+        });
+        let transaction_errors = scope.spawn(|| {
+            transaction_errors
+                .into_iter()
+                .inspect(|err| {
+                    tracing::warn!(%err, "failed to apply transaction");
+                    // Before calling the strategy, in case it panics.
+                    if count_error() {
+                        abort();
+                    }
+                    strategy.on_transaction_error(err);
+                })
+                .count()
+        });
+
+        // Both threads are joined before returning, so the counts are final.
+        // Panics are propagated as they are, e.g. for `AbortStrategy`.
+        (
+            input_errors
+                .join()
+                .unwrap_or_else(|err| std::panic::resume_unwind(err)),
+            transaction_errors
+                .join()
+                .unwrap_or_else(|err| std::panic::resume_unwind(err)),
+        )
+    })
+}
+
+#[test]
+fn test_count() {
+    use crate::ledger::{ClientId, TransactionId};
+    use std::sync::mpsc;
+
+    let (input_errors_tx, input_errors) = mpsc::channel();
+    let (transaction_errors_tx, transaction_errors) = mpsc::channel();
+    for line in 1..=3 {
+        input_errors_tx
+            .send(Error::Format(format!("invalid record on line {line}")))
+            .unwrap();
+    }
+    for tx_id in 1..=2 {
+        transaction_errors_tx
+            .send(TransactionError::UnknownTransaction {
+                tx_id: TransactionId(tx_id),
+                client_id: ClientId(1),
+                amount: None,
+            })
+            .unwrap();
+    }
+    drop(input_errors_tx);
+    drop(transaction_errors_tx);
+
+    assert_eq!((3, 2), count(input_errors, transaction_errors));
+}
+
+#[test]
+// Both invalid records and failed transactions should abort the processing,
+// once the budget is exhausted.
+fn test_handle_with_budget() {
+    use crate::ledger::{ClientId, TransactionId};
+    use std::sync::{atomic::AtomicBool, mpsc};
+
+    for (input_error_count, transaction_error_count, max_errors, want_aborted) in [
+        (0, 2, None, false),
+        (0, 2, Some(2), true),
+        (0, 2, Some(3), false),
+        (3, 0, Some(2), true),
+        (1, 1, Some(2), true),
+        (1, 1, Some(3), false),
+    ] {
+        let (input_errors_tx, input_errors) = mpsc::channel();
+        let (transaction_errors_tx, transaction_errors) = mpsc::channel();
+        for line in 1..=input_error_count {
+            input_errors_tx
+                .send(Error::Format(format!("invalid record on line {line}")))
+                .unwrap();
+        }
+        for tx_id in 1..=transaction_error_count {
+            transaction_errors_tx
+                .send(TransactionError::UnknownTransaction {
+                    tx_id: TransactionId(tx_id),
+                    client_id: ClientId(1),
+                    amount: None,
+                })
+                .unwrap();
+        }
+        drop(input_errors_tx);
+        drop(transaction_errors_tx);
+
+        let aborted = AtomicBool::new(false);
+        let counts = handle_with_budget(
+            input_errors,
+            transaction_errors,
+            &SinkStrategy,
+            max_errors,
+            &|| aborted.store(true, Ordering::Relaxed),
+        );
+
+        // Errors are still counted after the abort.
+        assert_eq!(
+            (
+                input_error_count,
+                usize::try_from(transaction_error_count).unwrap()
+            ),
+            counts
+        );
+        assert_eq!(want_aborted, aborted.into_inner(), "{max_errors:?}");
+    }
+}
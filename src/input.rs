@@ -1,27 +1,193 @@
-use crate::ledger::transaction::{self, Transaction};
+#[cfg(feature = "async")]
+mod input_async;
+#[cfg(feature = "async")]
+pub use input_async::parse_async;
 
-use rust_decimal::Decimal;
+use crate::ledger::{
+    budget::ErrorBudget,
+    transaction::{self, Transaction},
+    ClientId, LedgerConfig, TransactionId,
+};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::Deserialize;
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::{
+    io::BufRead,
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender},
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum Error {
     Csv(String),    // CSV is malformed
     Format(String), // Data format is incorrect
+    Json(String),   // JSON is malformed
+    MissingHeader,  // Header row is missing or doesn't contain the expected columns
+    // Record doesn't have as many fields as the header
+    UnexpectedColumnCount {
+        expected: usize,
+        got: usize,
+        line: u64,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Csv(msg) | Self::Format(msg) | Self::Json(msg) => f.write_str(msg),
+            Self::MissingHeader => f.write_str("missing or malformed header row"),
+            Self::UnexpectedColumnCount {
+                expected,
+                got,
+                line,
+            } => write!(f, "expected {expected} fields, found {got} on line {line}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[test]
+fn test_error_display() {
+    // Errors can be propagated with `?` in functions returning a boxed error.
+    fn propagate() -> Result<(), Box<dyn std::error::Error>> {
+        Err(Error::Csv("bad csv".into()))?;
+        Ok(())
+    }
+
+    assert_eq!("bad csv", format!("{}", Error::Csv("bad csv".into())));
+    assert_eq!(
+        "bad format",
+        format!("{}", Error::Format("bad format".into()))
+    );
+    assert_eq!("bad csv", propagate().unwrap_err().to_string());
 }
 
 impl From<csv::Error> for Error {
     fn from(err: csv::Error) -> Self {
+        // A wrong number of fields is a data issue rather than a CSV issue, and
+        // callers may want to handle it on its own.
+        if let csv::ErrorKind::UnequalLengths {
+            pos,
+            expected_len,
+            len,
+        } = err.kind()
+        {
+            return Self::UnexpectedColumnCount {
+                expected: usize::try_from(*expected_len).unwrap_or(usize::MAX),
+                got: usize::try_from(*len).unwrap_or(usize::MAX),
+                line: pos.as_ref().map_or(0, csv::Position::line),
+            };
+        }
+
         Self::Csv(err.to_string())
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err.to_string())
+    }
+}
+
 impl From<<TransactionRecord as TryInto<Transaction>>::Error> for Error {
     fn from(err: <TransactionRecord as TryInto<Transaction>>::Error) -> Self {
         Self::Format(err.to_string())
     }
 }
 
+/// How the delimited input should be read by `parse()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseConfig {
+    /// Single byte separating the fields, e.g. `b','` for CSV or `b'\t'` for TSV.
+    pub delimiter: u8,
+
+    /// Whether leading and trailing whitespace should be removed from the
+    /// headers and fields.
+    pub trim: bool,
+
+    /// How the parsed transactions should be created, e.g. their decimal
+    /// precision.
+    pub ledger_config: LedgerConfig,
+
+    /// Whether the rest of the input should be ignored after the first invalid
+    /// record, e.g. to abort the processing on the first error.
+    pub stop_on_error: bool,
+}
+
+impl Default for ParseConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            trim: true,
+            ledger_config: LedgerConfig::default(),
+            stop_on_error: false,
+        }
+    }
+}
+
+impl ParseConfig {
+    /// Build a config, starting from the default values.
+    #[must_use]
+    pub fn builder() -> ParseConfigBuilder {
+        ParseConfigBuilder {
+            config: Self::default(),
+        }
+    }
+}
+
+/// Sets some of the `ParseConfig` values, keeping the defaults for the others.
+pub struct ParseConfigBuilder {
+    config: ParseConfig,
+}
+
+impl ParseConfigBuilder {
+    #[must_use]
+    pub const fn delimiter(mut self, delimiter: u8) -> Self {
+        self.config.delimiter = delimiter;
+        self
+    }
+
+    #[must_use]
+    pub const fn trim(mut self, trim: bool) -> Self {
+        self.config.trim = trim;
+        self
+    }
+
+    /// Number of decimal places the amounts are rounded to.
+    #[must_use]
+    pub const fn decimal_precision(mut self, decimal_precision: u32) -> Self {
+        self.config.ledger_config.decimal_precision = decimal_precision;
+        self
+    }
+
+    /// How the amounts are rounded to the decimal precision.
+    #[must_use]
+    pub const fn rounding_strategy(mut self, rounding_strategy: RoundingStrategy) -> Self {
+        self.config.ledger_config.rounding_strategy = rounding_strategy;
+        self
+    }
+
+    /// Number of errors after which the processing is aborted.
+    #[must_use]
+    pub const fn max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.config.ledger_config.max_errors = max_errors;
+        self
+    }
+
+    #[must_use]
+    pub const fn stop_on_error(mut self, stop_on_error: bool) -> Self {
+        self.config.stop_on_error = stop_on_error;
+        self
+    }
+
+    #[must_use]
+    pub const fn build(self) -> ParseConfig {
+        self.config
+    }
+}
+
 // When parsing, I'm making the assumption that we want to completely abort
 // on errors.
 // When we're reading a CSV file, it makes sense to fix the CSV (or the code),
@@ -29,62 +195,304 @@ impl From<<TransactionRecord as TryInto<Transaction>>::Error> for Error {
 // For a real-world scenario where we're receiving a stream of events instead,
 // we would probably filter out bad rows and send them to an external system
 // for analysis and recovery.
+/// Same as `parse_with_capacity()`, with channels large enough for the
+/// parser to never wait in practice.
+///
+/// # Panics
+///
+/// The parsing thread panics if the errors receiver is dropped before the
+/// whole input has been parsed. If the transactions receiver is dropped
+/// instead, e.g. when the processing is aborted, the parsing simply stops.
 pub fn parse(
-    input_stream: (impl std::io::Read + Send + 'static),
+    input_stream: impl std::io::Read + Send + 'static,
+    config: ParseConfig,
 ) -> (Receiver<Transaction>, Receiver<Error>) {
-    let (transaction_tx, transaction_rx): (Sender<Transaction>, Receiver<Transaction>) =
-        mpsc::channel();
-    let (error_tx, error_rx): (Sender<Error>, Receiver<Error>) = mpsc::channel();
+    parse_with_budget(
+        input_stream,
+        config,
+        ErrorBudget::new(config.ledger_config.max_errors),
+    )
+}
 
-    let buffered = std::io::BufReader::new(input_stream);
-    let mut reader = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .from_reader(buffered);
+/// Same as `parse()`, but each invalid record is recorded in `budget`.
+///
+/// The ledger sharing it then counts them towards `max_errors` as well (see
+/// `ledger::build_with_budget()`). It should be created with
+/// `config.ledger_config.max_errors`.
+///
+/// # Panics
+///
+/// The parsing thread panics if the errors receiver is dropped before the
+/// whole input has been parsed. If the transactions receiver is dropped
+/// instead, e.g. when the processing is aborted, the parsing simply stops.
+pub fn parse_with_budget(
+    input_stream: impl std::io::Read + Send + 'static,
+    config: ParseConfig,
+    budget: ErrorBudget,
+) -> (Receiver<Transaction>, Receiver<Error>) {
+    spawn_parser(
+        input_stream,
+        config,
+        budget,
+        DEFAULT_CHANNEL_CAPACITY,
+        DEFAULT_CHANNEL_CAPACITY,
+    )
+}
+
+pub(crate) const DEFAULT_CHANNEL_CAPACITY: usize = 100_000;
+
+/// Same as `parse()`, reading the file through a read-only memory mapping
+/// instead of buffered reads, which can be faster for very large files.
+///
+/// The mapping is moved to the parsing thread, and only unmapped once the
+/// whole file has been parsed (or the parsing stopped), so it is always kept
+/// alive for the duration of the parsing.
+///
+/// The file must not be modified or truncated while it is being parsed, e.g.
+/// by another process: the mapped memory would change under the parser, and
+/// reading past the new end of the file crashes the whole process (`SIGBUS`).
+///
+/// # Errors
+///
+/// Returns an error if the file can't be opened or mapped.
+///
+/// # Panics
+///
+/// The parsing thread panics if the errors receiver is dropped before the
+/// whole input has been parsed. If the transactions receiver is dropped
+/// instead, e.g. when the processing is aborted, the parsing simply stops.
+pub fn parse_mmap(
+    path: &Path,
+    config: ParseConfig,
+) -> std::io::Result<(Receiver<Transaction>, Receiver<Error>)> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the mapping is read-only, and the caller guarantees that the
+    // file isn't modified while it's mapped (see above).
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+
+    Ok(parse(std::io::Cursor::new(mmap), config))
+}
+
+/// Parse the input stream, sending the transactions and the errors to bounded
+/// channels.
+///
+/// Once a channel is full, the parser waits for it to be read, rather than
+/// racing ahead of the ledger and buffering the whole input in memory.
+///
+/// # Panics
+///
+/// The parsing thread panics if the errors receiver is dropped before the
+/// whole input has been parsed. If the transactions receiver is dropped
+/// instead, e.g. when the processing is aborted, the parsing simply stops.
+pub fn parse_with_capacity(
+    input_stream: impl std::io::Read + Send + 'static,
+    config: ParseConfig,
+    transactions_capacity: usize,
+    errors_capacity: usize,
+) -> (Receiver<Transaction>, Receiver<Error>) {
+    spawn_parser(
+        input_stream,
+        config,
+        ErrorBudget::new(config.ledger_config.max_errors),
+        transactions_capacity,
+        errors_capacity,
+    )
+}
+
+fn spawn_parser(
+    input_stream: impl std::io::Read + Send + 'static,
+    config: ParseConfig,
+    budget: ErrorBudget,
+    transactions_capacity: usize,
+    errors_capacity: usize,
+) -> (Receiver<Transaction>, Receiver<Error>) {
+    let (transaction_tx, transaction_rx): (SyncSender<Transaction>, Receiver<Transaction>) =
+        mpsc::sync_channel(transactions_capacity);
+    let (error_tx, error_rx): (SyncSender<Error>, Receiver<Error>) =
+        mpsc::sync_channel(errors_capacity);
+
+    let mut reader = csv_reader(input_stream, config);
 
     // Moving to a new thread so we can start processing the transactions immediately.
     std::thread::spawn(move || {
+        if let Err(err) = check_headers(&mut reader) {
+            error_tx.send(err).unwrap(); // Would only fail if the rx is disconnected, which should not happen here.
+            return;
+        }
+
+        let mut transactions_read = 0;
         for record in reader.deserialize::<TransactionRecord>() {
-            match convert(record) {
-                Ok(transaction) => transaction_tx.send(transaction).unwrap(), // Would only fail if the rx is disconnected, which should not happen here.
-                Err(err) => error_tx.send(err).unwrap(), // Would only fail if the rx is disconnected, which should not happen here.
-            };
+            match convert(record, config.ledger_config) {
+                Ok(transaction) => {
+                    if transaction_tx.send(transaction).is_err() {
+                        return; // The processing was aborted.
+                    }
+                    transactions_read += 1;
+                }
+                Err(err) => {
+                    error_tx.send(err).unwrap(); // Would only fail if the rx is disconnected, which should not happen here.
+
+                    // Once the error budget is exhausted, the next records
+                    // would be ignored anyway.
+                    if config.stop_on_error || budget.record_parse_error(transactions_read) {
+                        return;
+                    }
+                }
+            }
         }
     });
 
     (transaction_rx, error_rx)
 }
 
+/// Read the whole CSV input, and return all the records that could not be
+/// parsed into a transaction (e.g. wrong field counts, unknown types, missing
+/// amounts), without stopping at the first one.
+///
+/// Nothing is applied to a ledger: this only checks the format of the input,
+/// e.g. before actually processing it. A valid input returns no errors.
+pub fn validate_format(input_stream: impl std::io::Read) -> Vec<Error> {
+    let config = ParseConfig::default();
+    let mut reader = csv_reader(input_stream, config);
+    if let Err(err) = check_headers(&mut reader) {
+        return vec![err];
+    }
+
+    reader
+        .deserialize::<TransactionRecord>()
+        .filter_map(|record| convert(record, config.ledger_config).err())
+        .collect()
+}
+
+/// Parse a single CSV line (without the trailing newline) into a transaction,
+/// e.g. a record received on its own from a message queue.
+///
+/// The fields are matched with the given headers, e.g.
+/// `csv::StringRecord::from(vec!["type", "client", "tx", "amount"])`. The
+/// default `ParseConfig` is used: comma-separated, and trimmed fields.
+///
+/// # Errors
+///
+/// Returns an error if the line is not a valid record for these headers, or
+/// can't be converted into a transaction.
+pub fn parse_record(line: &str, headers: &csv::StringRecord) -> Result<Transaction, Error> {
+    let config = ParseConfig::default();
+    let record = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(config.delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(line.as_bytes())
+        .records()
+        .next()
+        .ok_or_else(|| Error::Format("empty record".to_string()))??;
+
+    // There is only one line.
+    if record.len() != headers.len() {
+        return Err(Error::UnexpectedColumnCount {
+            expected: headers.len(),
+            got: record.len(),
+            line: 1,
+        });
+    }
+
+    convert(record.deserialize(Some(headers)), config.ledger_config)
+}
+
+fn csv_reader<R: std::io::Read>(
+    input_stream: R,
+    config: ParseConfig,
+) -> csv::Reader<std::io::BufReader<R>> {
+    csv::ReaderBuilder::new()
+        .delimiter(config.delimiter)
+        .trim(if config.trim {
+            csv::Trim::All
+        } else {
+            csv::Trim::None
+        })
+        .from_reader(std::io::BufReader::new(input_stream))
+}
+
+// Columns that must be present in the header row. `amount` is optional, as
+// disputes, resolves and chargebacks don't need it.
+const REQUIRED_HEADERS: [&str; 3] = ["type", "client", "tx"];
+
+// Without a header row, the first record would silently be used as the header
+// and every following record would be misread, so we stop right away instead.
+fn check_headers(reader: &mut csv::Reader<impl std::io::Read>) -> Result<(), Error> {
+    check_header_record(reader.headers()?)
+}
+
+fn check_header_record(headers: &csv::StringRecord) -> Result<(), Error> {
+    if REQUIRED_HEADERS
+        .iter()
+        .all(|required| headers.iter().any(|header| header == *required))
+    {
+        Ok(())
+    } else {
+        Err(Error::MissingHeader)
+    }
+}
+
 // Convert from a csv deserialise result into a transaction result.
-fn convert(record: Result<TransactionRecord, csv::Error>) -> Result<Transaction, Error> {
-    Ok(record?.try_into()?)
+fn convert(
+    record: Result<TransactionRecord, csv::Error>,
+    config: LedgerConfig,
+) -> Result<Transaction, Error> {
+    Ok(record?.into_transaction(config)?)
 }
 
 #[test]
 // Parsing well-formed data should return a vector of Transaction.
 fn test_parse_ok() {
-    let data = r#"type,client,tx,amount
+    let data = r"type,client,tx,amount
 deposit,1,1,1.0
 withdrawal,1,4,1.5
 dispute,1,1,
 resolve,1,1,
-chargeback,1,1,"#;
+chargeback,1,1,";
     let reader = std::io::Cursor::new(data);
-    let (transactions, errors) = parse(reader);
+    let (transactions, errors) = parse(reader, ParseConfig::default());
 
     assert_eq!(5, transactions.iter().count());
     assert_eq!(0, errors.iter().count());
 }
 
+#[test]
+fn test_parse_mmap() {
+    use std::io::Write;
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(
+        b"type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,4,1.5
+bad record
+dispute,1,1,",
+    )
+    .unwrap();
+
+    let (transactions, errors) = parse_mmap(file.path(), ParseConfig::default()).unwrap();
+
+    assert_eq!(3, transactions.iter().count());
+    assert_eq!(1, errors.iter().count());
+}
+
+#[test]
+fn test_parse_mmap_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(parse_mmap(&dir.path().join("missing.csv"), ParseConfig::default()).is_err());
+}
+
 #[test]
 fn test_parse_ok_with_whitespace() {
-    let data = r#"type,     client,     tx,amount
+    let data = r"type,     client,     tx,amount
 deposit, 1, 1, 1.0
 withdrawal , 1 , 4 , 1.5
 dispute ,   1   ,   1   ,
     resolve ,1,1,
-        chargeback                  ,1,1,"#;
+        chargeback                  ,1,1,";
     let reader = std::io::Cursor::new(data);
-    let (transactions, errors) = parse(reader);
+    let (transactions, errors) = parse(reader, ParseConfig::default());
 
     assert_eq!(5, transactions.iter().count());
     assert_eq!(0, errors.iter().count());
@@ -93,35 +501,30 @@ dispute ,   1   ,   1   ,
 #[test]
 // Parsing incorrectly formatted data should return an Err.
 fn test_parse_invalid_format() {
-    for (data, err_contains) in vec![
+    for (data, err_contains) in [
         (
-            r#"type,client,tx,amount
-some_unknown_tx_type,1,1,1.0"#,
+            r"type,client,tx,amount
+some_unknown_tx_type,1,1,1.0",
             "unknown variant `some_unknown_tx_type`",
         ),
         (
-            r#"type,client,tx,amount
-dispute,,1,"#, // missing client
-            "cannot parse integer from empty string",
+            r"type,client,tx,amount
+xDeposit,1,1,1.0",
+            "unknown variant `xDeposit`",
         ),
         (
-            r#"type,client,tx,amount
-dispute,1,,"#, // missing tx
+            r"type,client,tx,amount
+dispute,,1,", // missing client
             "cannot parse integer from empty string",
         ),
         (
-            r#"type,client,tx,amount
-dispute,1,1"#,
-            "found record with 3 fields, but the previous record has 4 fields",
-        ),
-        (
-            r#"type,client,tx,amount
-dispute,1,1,,,,"#,
-            "found record with 7 fields, but the previous record has 4 fields",
+            r"type,client,tx,amount
+dispute,1,,", // missing tx
+            "cannot parse integer from empty string",
         ),
     ] {
         let reader = std::io::Cursor::new(data);
-        let (transactions, errors) = parse(reader);
+        let (transactions, errors) = parse(reader, ParseConfig::default());
 
         assert_eq!(0, transactions.iter().count());
 
@@ -129,30 +532,345 @@ dispute,1,1,,,,"#,
         assert_eq!(1, errs.len());
 
         match &errs[0] {
-            Error::Csv(msg) => assert!(msg.contains(err_contains), "{:?}", msg),
+            Error::Csv(msg) => assert!(msg.contains(err_contains), "{msg:?}"),
             _ => panic!("unexpected error"),
         }
     }
 }
 
+#[test]
+// The optional memo column should be kept in the transactions.
+fn test_parse_memo() {
+    let data = r"type,client,tx,amount,memo
+deposit,1,1,1.0,salary
+deposit,1,2,1.0,
+dispute,1,1,,customer called";
+    let (transactions, errors) = parse(std::io::Cursor::new(data), ParseConfig::default());
+
+    let transactions: Vec<_> = transactions.iter().collect();
+    assert_eq!(
+        vec![Some("salary"), None, Some("customer called")],
+        transactions
+            .iter()
+            .map(Transaction::memo)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(0, errors.iter().count());
+}
+
+#[test]
+// The optional timestamp column should be kept with the transaction.
+fn test_parse_timestamp() {
+    let data = r"type,client,tx,amount,timestamp
+deposit,1,1,1.0,2024-01-31T12:00:00Z
+deposit,1,2,1.0,
+dispute,1,1,,2024-02-01T08:30:00+01:00";
+    let (transactions, errors) = parse(std::io::Cursor::new(data), ParseConfig::default());
+
+    let transactions: Vec<_> = transactions.iter().collect();
+    assert_eq!(
+        vec![
+            Some("2024-01-31T12:00:00Z".parse().unwrap()),
+            None,
+            Some("2024-02-01T07:30:00Z".parse().unwrap()),
+        ],
+        transactions
+            .iter()
+            .map(Transaction::timestamp)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(0, errors.iter().count());
+
+    let (transactions, errors) = parse(
+        std::io::Cursor::new("type,client,tx,amount,timestamp\ndeposit,1,1,1.0,yesterday"),
+        ParseConfig::default(),
+    );
+    assert_eq!(0, transactions.iter().count());
+    assert_eq!(1, errors.iter().count());
+}
+
+#[test]
+// Columns the engine doesn't use should be ignored, wherever they are.
+fn test_parse_extra_columns() {
+    let data = r"type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,2,0.5
+dispute,1,1,
+resolve,1,1,
+chargeback,1,1,";
+    let with_notes = r"notes,type,client,tx,amount,merchant
+first,deposit,1,1,1.0,acme
+,withdrawal,1,2,0.5,
+disputed by phone,dispute,1,1,,
+,resolve,1,1,,
+,chargeback,1,1,,acme";
+
+    let (want, _) = parse(std::io::Cursor::new(data), ParseConfig::default());
+    let (transactions, errors) = parse(std::io::Cursor::new(with_notes), ParseConfig::default());
+    let want: Vec<_> = want.iter().collect();
+    assert_eq!(5, want.len());
+    assert_eq!(want, transactions.iter().collect::<Vec<_>>());
+    assert_eq!(0, errors.iter().count());
+}
+
+#[test]
+// Transaction types should be case-insensitive.
+fn test_parse_mixed_case_types() {
+    let data = r"type,client,tx,amount
+Deposit,1,1,1.0
+DEPOSIT,1,2,1.0
+dEpOsIt,1,3,1.0
+Withdrawal,1,4,0.5
+DISPUTE,1,1,
+Resolve,1,1,
+chargeBack,1,2,";
+    let (transactions, errors) = parse(std::io::Cursor::new(data), ParseConfig::default());
+
+    let lowercase = data.to_lowercase();
+    let (want, _) = parse(std::io::Cursor::new(lowercase), ParseConfig::default());
+    let want: Vec<_> = want.iter().collect();
+    assert_eq!(7, want.len());
+    assert_eq!(want, transactions.iter().collect::<Vec<_>>());
+    assert_eq!(0, errors.iter().count());
+}
+
+#[test]
+// TSV should be parsed like CSV, including whitespace trimming.
+fn test_parse_tsv() {
+    let data = "type\tclient\ttx\tamount
+deposit\t1\t1\t1.0
+  withdrawal \t  1\t 4 \t 1.5
+dispute\t1\t1\t
+\"resolve\"\t\" \t1\"\t1\t
+chargeback\t1\t1\t";
+    let reader = std::io::Cursor::new(data);
+    let (transactions, errors) = parse(reader, ParseConfig::builder().delimiter(b'\t').build());
+
+    assert_eq!(5, transactions.iter().count());
+    assert_eq!(0, errors.iter().count());
+}
+
+#[test]
+// Commas aren't delimiters in TSV, so a comma-separated file can't be parsed.
+fn test_parse_tsv_comma_separated() {
+    let data = "type\tclient\ttx\tamount
+deposit,1,1,1.0";
+    let reader = std::io::Cursor::new(data);
+    let (transactions, errors) = parse(reader, ParseConfig::builder().delimiter(b'\t').build());
+
+    assert_eq!(0, transactions.iter().count());
+    assert_eq!(
+        vec![Error::UnexpectedColumnCount {
+            expected: 4,
+            got: 1,
+            line: 2,
+        }],
+        errors.iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+// Any single-byte delimiter can be used.
+fn test_parse_custom_delimiters() {
+    for delimiter in [b';', b'|', b'\t'] {
+        let data = "type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,4,1.5
+dispute,1,1,
+resolve,1,1,
+chargeback,1,1,"
+            .replace(',', &char::from(delimiter).to_string());
+        let reader = std::io::Cursor::new(data);
+        let (transactions, errors) =
+            parse(reader, ParseConfig::builder().delimiter(delimiter).build());
+
+        assert_eq!(5, transactions.iter().count());
+        assert_eq!(0, errors.iter().count());
+    }
+}
+
+#[test]
+// Without trimming, whitespace is kept and the amount can't be parsed.
+fn test_parse_without_trim() {
+    let data = "type,client,tx,amount
+deposit,1,1, 1.0";
+    let reader = std::io::Cursor::new(data);
+    let (transactions, errors) = parse(reader, ParseConfig::builder().trim(false).build());
+
+    assert_eq!(0, transactions.iter().count());
+    assert_eq!(1, errors.iter().count());
+}
+
+#[test]
+fn test_parse_config_builder() {
+    assert_eq!(ParseConfig::default(), ParseConfig::builder().build());
+    assert_eq!(
+        ParseConfig {
+            delimiter: b';',
+            trim: true,
+            ledger_config: LedgerConfig::default(),
+            stop_on_error: false,
+        },
+        ParseConfig::builder().delimiter(b';').build()
+    );
+    assert_eq!(
+        RoundingStrategy::MidpointAwayFromZero,
+        ParseConfig::builder()
+            .rounding_strategy(RoundingStrategy::MidpointAwayFromZero)
+            .build()
+            .ledger_config
+            .rounding_strategy
+    );
+}
+
+#[test]
+// Amounts should be rounded to the configured decimal precision.
+fn test_parse_decimal_precision() {
+    use rust_decimal_macros::dec;
+
+    let data = "type,client,tx,amount
+deposit,1,1,1.23456";
+    for (decimal_precision, want_amount) in [(4, dec!(1.2346)), (2, dec!(1.23)), (0, dec!(1))] {
+        let reader = std::io::Cursor::new(data);
+        let (transactions, errors) = parse(
+            reader,
+            ParseConfig::builder()
+                .decimal_precision(decimal_precision)
+                .build(),
+        );
+
+        assert_eq!(
+            vec![Some(want_amount)],
+            transactions
+                .iter()
+                .map(|tx| tx.amount())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0, errors.iter().count());
+    }
+}
+
+#[test]
+// Records with more or fewer fields than the header should be reported as such,
+// and not as a generic CSV error.
+fn test_parse_unexpected_column_count() {
+    for (data, got) in [
+        ("type,client,tx,amount\ndispute,1,1", 3),
+        ("type,client,tx,amount\ndispute,1,1,,", 5),
+        ("type,client,tx,amount\ndispute,1,1,,,,", 7),
+    ] {
+        let reader = std::io::Cursor::new(data);
+        let (transactions, errors) = parse(reader, ParseConfig::default());
+
+        assert_eq!(0, transactions.iter().count());
+        assert_eq!(
+            vec![Error::UnexpectedColumnCount {
+                expected: 4,
+                got,
+                line: 2,
+            }],
+            errors.iter().collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+// The records after the first invalid one should not be read.
+fn test_parse_stop_on_error() {
+    let data = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2
+deposit,1,3,1.0
+deposit,1,4";
+    let (transactions, errors) = parse(
+        std::io::Cursor::new(data),
+        ParseConfig::builder().stop_on_error(true).build(),
+    );
+
+    assert_eq!(1, transactions.iter().count());
+    assert_eq!(1, errors.iter().count());
+}
+
+#[test]
+// Some CSV exporters (especially on Windows) start the file with a UTF-8 BOM.
+// csv-core already strips it before reading the header, this makes sure it
+// keeps doing so.
+fn test_parse_utf8_bom() {
+    let data = "type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,4,1.5
+dispute,1,1,";
+    let (want, want_errors) = parse(std::io::Cursor::new(data), ParseConfig::default());
+    let (got, got_errors) = parse(
+        std::io::Cursor::new(format!("\u{FEFF}{data}")),
+        ParseConfig::default(),
+    );
+
+    assert_eq!(
+        want.iter().collect::<Vec<_>>(),
+        got.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(0, want_errors.iter().count());
+    assert_eq!(0, got_errors.iter().count());
+}
+
+#[test]
+// Without a header row, parsing should stop immediately.
+fn test_parse_missing_header() {
+    for data in [
+        "1,1,deposit,1.0\n2,2,deposit,2.0\n",
+        "deposit,1,1,1.0\n",
+        "",
+    ] {
+        let reader = std::io::Cursor::new(data);
+        let (transactions, errors) = parse(reader, ParseConfig::default());
+
+        assert_eq!(0, transactions.iter().count());
+        assert_eq!(
+            vec![Error::MissingHeader],
+            errors.iter().collect::<Vec<_>>()
+        );
+    }
+}
+
 #[test]
 // Parsing deposits or withdrawals without an amount should fail to convert
 // into a Transaction.
 fn test_parse_invalid_data() {
-    for (data, want_err) in vec![
+    for (data, want_err) in [
         (
-            r#"type,client,tx,amount
-deposit,1,1,"#,
+            r"type,client,tx,amount
+deposit,1,1,",
             Error::Format("missing amount for deposit".to_string()),
         ),
         (
-            r#"type,client,tx,amount
-withdrawal,1,1,"#,
+            r"type,client,tx,amount
+withdrawal,1,1,",
             Error::Format("missing amount for withdrawal".to_string()),
         ),
+        (
+            r"type,client,tx,amount
+deposit,1,1,-1.0",
+            Error::Format("amount must be positive".to_string()),
+        ),
+        (
+            r"type,client,tx,amount
+withdrawal,1,1,-0.0001",
+            Error::Format("amount must be positive".to_string()),
+        ),
+        (
+            r"type,client,tx,amount
+deposit,1,1,0.0000",
+            Error::Format("amount must not be zero".to_string()),
+        ),
+        (
+            r"type,client,tx,amount
+withdrawal,1,1,0",
+            Error::Format("amount must not be zero".to_string()),
+        ),
     ] {
         let reader = std::io::Cursor::new(data);
-        let (transactions, errors) = parse(reader);
+        let (transactions, errors) = parse(reader, ParseConfig::default());
 
         assert_eq!(0, transactions.iter().count());
 
@@ -161,7 +879,295 @@ withdrawal,1,1,"#,
     }
 }
 
+#[test]
+// All the invalid records should be reported, not only the first one.
+fn test_validate_format() {
+    let data = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2
+refund,1,3,1.0
+withdrawal,1,4,
+deposit,a,5,1.0
+withdrawal,1,6,1.0
+deposit,1,7,1.0,
+dispute,1,1,
+";
+
+    let errors = validate_format(data.as_bytes());
+    assert_eq!(5, errors.len(), "{errors:?}");
+    assert_eq!(
+        Error::UnexpectedColumnCount {
+            expected: 4,
+            got: 3,
+            line: 3,
+        },
+        errors[0]
+    );
+
+    assert!(validate_format(&b"type,client,tx,amount\ndeposit,1,1,1.0\n"[..]).is_empty());
+    assert_eq!(
+        vec![Error::MissingHeader],
+        validate_format(&b"deposit,1,1,1.0"[..])
+    );
+}
+
+#[test]
+fn test_parse_record() {
+    use crate::ledger::transaction::Type;
+    use rust_decimal_macros::dec;
+
+    let headers = csv::StringRecord::from(vec!["type", "client", "tx", "amount"]);
+    for (line, tx_type) in [
+        ("deposit,1,2,1.5", Type::Deposit(dec!(1.5))),
+        ("withdrawal, 1, 2, 1.5", Type::Withdrawal(dec!(1.5))),
+        ("dispute,1,2,", Type::Dispute),
+        ("resolve,1,2,", Type::Resolve),
+        ("chargeback,1,2,", Type::Chargeback),
+    ] {
+        assert_eq!(
+            Ok(Transaction::new(tx_type, ClientId(1), TransactionId(2)).unwrap()),
+            parse_record(line, &headers),
+            "{line}"
+        );
+    }
+
+    for (line, want_err) in [
+        (
+            "dispute,1,2",
+            Error::UnexpectedColumnCount {
+                expected: 4,
+                got: 3,
+                line: 1,
+            },
+        ),
+        ("", Error::Format("empty record".to_string())),
+    ] {
+        assert_eq!(Err(want_err), parse_record(line, &headers), "{line}");
+    }
+    assert!(parse_record("refund,1,2,1.0", &headers).is_err());
+}
+
+/// Parse newline-delimited JSON (NDJSON), as an alternative to CSV.
+///
+/// Each line is a JSON object with the keys `type`, `client`, `tx` and
+/// optionally `amount`, e.g. `{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}`.
+/// Blank lines are ignored.
+///
+/// The channels behave exactly like the ones returned by `parse()`, so both
+/// functions are interchangeable: they are bounded as well, so the parser
+/// waits for them to be read instead of buffering the whole input.
+///
+/// The config is used like in `parse()`, except for the delimiter and the
+/// trimming, which don't apply to JSON.
+///
+/// # Panics
+///
+/// The parsing thread panics if the errors receiver is dropped before the
+/// whole input has been parsed. If the transactions receiver is dropped
+/// instead, e.g. when the processing is aborted, the parsing simply stops.
+pub fn parse_ndjson(
+    input_stream: impl std::io::Read + Send + 'static,
+    config: ParseConfig,
+) -> (Receiver<Transaction>, Receiver<Error>) {
+    parse_ndjson_with_budget(
+        input_stream,
+        config,
+        ErrorBudget::new(config.ledger_config.max_errors),
+    )
+}
+
+/// Same as `parse_ndjson()`, but each invalid record is recorded in `budget`,
+/// like `parse_with_budget()`.
+///
+/// # Panics
+///
+/// The parsing thread panics if the errors receiver is dropped before the
+/// whole input has been parsed. If the transactions receiver is dropped
+/// instead, e.g. when the processing is aborted, the parsing simply stops.
+pub fn parse_ndjson_with_budget(
+    input_stream: impl std::io::Read + Send + 'static,
+    config: ParseConfig,
+    budget: ErrorBudget,
+) -> (Receiver<Transaction>, Receiver<Error>) {
+    let (transaction_tx, transaction_rx): (SyncSender<Transaction>, Receiver<Transaction>) =
+        mpsc::sync_channel(DEFAULT_CHANNEL_CAPACITY);
+    let (error_tx, error_rx): (SyncSender<Error>, Receiver<Error>) =
+        mpsc::sync_channel(DEFAULT_CHANNEL_CAPACITY);
+
+    let buffered = std::io::BufReader::new(input_stream);
+
+    // Moving to a new thread so we can start processing the transactions immediately.
+    std::thread::spawn(move || {
+        let mut transactions_read = 0;
+        for (index, line) in buffered.lines().enumerate() {
+            let mut line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    // We can't read the rest of the stream.
+                    error_tx.send(serde_json::Error::io(err).into()).unwrap(); // Would only fail if the rx is disconnected, which should not happen here.
+                    return;
+                }
+            };
+
+            // Some exporters (especially on Windows) start the file with a
+            // UTF-8 BOM, which isn't valid JSON.
+            if index == 0 && line.starts_with('\u{FEFF}') {
+                line.remove(0);
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match convert_json(&line, config.ledger_config) {
+                Ok(transaction) => {
+                    if transaction_tx.send(transaction).is_err() {
+                        return; // The processing was aborted.
+                    }
+                    transactions_read += 1;
+                }
+                Err(err) => {
+                    error_tx.send(err).unwrap(); // Would only fail if the rx is disconnected, which should not happen here.
+
+                    // Same as `parse()`.
+                    if config.stop_on_error || budget.record_parse_error(transactions_read) {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    (transaction_rx, error_rx)
+}
+
+// Convert a single JSON line into a transaction.
+fn convert_json(line: &str, config: LedgerConfig) -> Result<Transaction, Error> {
+    Ok(serde_json::from_str::<TransactionRecord>(line)?.into_transaction(config)?)
+}
+
+#[test]
+// All transaction types should be parsed from NDJSON, whether amounts are
+// strings or numbers.
+fn test_parse_ndjson_ok() {
+    use rust_decimal_macros::dec;
+
+    let data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+{"type": "withdrawal", "client": 1, "tx": 4, "amount": 1.5}
+
+{"type": "dispute", "client": 1, "tx": 1}
+{"type": "resolve", "client": 1, "tx": 1, "amount": null}
+{"type": "chargeback", "client": 1, "tx": 1}
+"#;
+    let reader = std::io::Cursor::new(data);
+    let (transactions, errors) = parse_ndjson(reader, ParseConfig::default());
+
+    assert_eq!(
+        vec![
+            Transaction::new(
+                transaction::Type::Deposit(dec!(1.0)),
+                ClientId(1),
+                TransactionId(1)
+            )
+            .unwrap(),
+            Transaction::new(
+                transaction::Type::Withdrawal(dec!(1.5)),
+                ClientId(1),
+                TransactionId(4)
+            )
+            .unwrap(),
+            Transaction::new(transaction::Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+            Transaction::new(transaction::Type::Resolve, ClientId(1), TransactionId(1)).unwrap(),
+            Transaction::new(transaction::Type::Chargeback, ClientId(1), TransactionId(1)).unwrap(),
+        ],
+        transactions.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(0, errors.iter().count());
+}
+
+#[test]
+fn test_parse_ndjson_utf8_bom() {
+    let data = "\u{FEFF}{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": \"1.0\"}";
+    let (transactions, errors) = parse_ndjson(std::io::Cursor::new(data), ParseConfig::default());
+
+    assert_eq!(1, transactions.iter().count());
+    assert_eq!(0, errors.iter().count());
+}
+
+#[test]
+// Invalid lines should be reported, without preventing the next lines from
+// being parsed.
+fn test_parse_ndjson_invalid_lines() {
+    let data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+not json at all
+{"type": "deposit", "client": 1
+{"type": "some_unknown_tx_type", "client": 1, "tx": 2}
+{"type": "deposit", "client": 1, "tx": 3}
+{"type": "deposit", "client": 1, "tx": 4, "amount": "2.0"}
+"#;
+    let reader = std::io::Cursor::new(data);
+    let (transactions, errors) = parse_ndjson(reader, ParseConfig::default());
+
+    assert_eq!(2, transactions.iter().count());
+
+    let errs: Vec<Error> = errors.iter().collect();
+    assert_eq!(4, errs.len());
+    assert!(matches!(errs[0], Error::Json(_)));
+    assert!(matches!(errs[1], Error::Json(_)));
+    match &errs[2] {
+        Error::Json(msg) => assert!(msg.contains("unknown variant `some_unknown_tx_type`")),
+        err => panic!("unexpected error: {err:?}"),
+    }
+    assert_eq!(
+        Error::Format("missing amount for deposit".to_string()),
+        errs[3]
+    );
+}
+
+#[test]
+// Like `parse()`, the lines after the first invalid one should not be read
+// with `stop_on_error`, nor the ones after the error budget is exhausted.
+fn test_parse_ndjson_stop_on_error() {
+    let data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+not json at all
+{"type": "deposit", "client": 1, "tx": 2, "amount": "1.0"}
+{"type": "deposit", "client": 1, "tx": 3}
+{"type": "deposit", "client": 1, "tx": 4, "amount": "1.0"}
+"#;
+    for (config, want_transactions, want_errors) in [
+        (ParseConfig::default(), 3, 2),
+        (ParseConfig::builder().stop_on_error(true).build(), 1, 1),
+        (ParseConfig::builder().max_errors(Some(2)).build(), 2, 2),
+    ] {
+        let (transactions, errors) = parse_ndjson(std::io::Cursor::new(data), config);
+
+        assert_eq!(want_transactions, transactions.iter().count(), "{config:?}");
+        assert_eq!(want_errors, errors.iter().count(), "{config:?}");
+    }
+}
+
+#[test]
+fn test_parse_ndjson_decimal_precision() {
+    use rust_decimal_macros::dec;
+
+    let data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.23456"}"#;
+    let (transactions, errors) = parse_ndjson(
+        std::io::Cursor::new(data),
+        ParseConfig::builder().decimal_precision(2).build(),
+    );
+
+    assert_eq!(
+        vec![Some(dec!(1.23))],
+        transactions
+            .iter()
+            .map(|tx| tx.amount())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(0, errors.iter().count());
+}
+
 // I have a TransactionRecord type because I can't directly deserialise into my "domain" type, i.e. Transaction.
+// Unknown fields (e.g. extra columns) are ignored, so it must not deny them.
 // See https://github.com/BurntSushi/rust-csv/issues/211.
 //
 // This gives me way more flexibility in crafting a clean Transaction type,
@@ -174,16 +1180,22 @@ pub struct TransactionRecord {
     tx_type: TransactionRecordType,
 
     #[serde(rename = "client")]
-    client_id: u16,
+    client_id: ClientId,
 
     #[serde(rename = "tx")]
-    transaction_id: u32,
+    transaction_id: TransactionId,
 
     amount: Option<Decimal>,
+
+    // Optional column, passed through to the transaction.
+    memo: Option<String>,
+
+    // Optional column, e.g. `2024-01-31T12:00:00Z`.
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug)]
 pub enum TransactionRecordType {
     Withdrawal,
     Deposit,
@@ -192,19 +1204,41 @@ pub enum TransactionRecordType {
     Chargeback,
 }
 
+// Types are case-insensitive, e.g. `Deposit` or `DEPOSIT` are accepted as
+// well, as some exporters capitalise them.
+impl<'de> Deserialize<'de> for TransactionRecordType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        const VARIANTS: &[&str] = &["withdrawal", "deposit", "dispute", "resolve", "chargeback"];
+
+        let tx_type = String::deserialize(deserializer)?;
+        match tx_type.to_ascii_lowercase().as_str() {
+            "withdrawal" => Ok(Self::Withdrawal),
+            "deposit" => Ok(Self::Deposit),
+            "dispute" => Ok(Self::Dispute),
+            "resolve" => Ok(Self::Resolve),
+            "chargeback" => Ok(Self::Chargeback),
+            _ => Err(serde::de::Error::unknown_variant(&tx_type, VARIANTS)),
+        }
+    }
+}
+
 impl TryFrom<TransactionRecord> for Transaction {
     type Error = &'static str;
     fn try_from(record: TransactionRecord) -> Result<Self, Self::Error> {
-        let client_id = record.client_id;
-        let tx_id = record.transaction_id;
-        let tx_type = match record.tx_type {
-            TransactionRecordType::Withdrawal => {
-                transaction::Type::Withdrawal(match record.amount {
-                    Some(amount) => amount,
-                    None => return Err("missing amount for withdrawal"),
-                })
-            }
-            TransactionRecordType::Deposit => transaction::Type::Deposit(match record.amount {
+        record.into_transaction(LedgerConfig::default())
+    }
+}
+
+impl TransactionRecord {
+    fn into_transaction(self, config: LedgerConfig) -> Result<Transaction, &'static str> {
+        let client_id = self.client_id;
+        let tx_id = self.transaction_id;
+        let tx_type = match self.tx_type {
+            TransactionRecordType::Withdrawal => transaction::Type::Withdrawal(match self.amount {
+                Some(amount) => amount,
+                None => return Err("missing amount for withdrawal"),
+            }),
+            TransactionRecordType::Deposit => transaction::Type::Deposit(match self.amount {
                 Some(amount) => amount,
                 None => return Err("missing amount for deposit"),
             }),
@@ -213,7 +1247,9 @@ impl TryFrom<TransactionRecord> for Transaction {
             TransactionRecordType::Chargeback => transaction::Type::Chargeback,
         };
 
-        Ok(Self::new(tx_type, client_id, tx_id))
+        Ok(Transaction::with_config(tx_type, client_id, tx_id, config)?
+            .with_memo(self.memo)
+            .with_timestamp(self.timestamp))
     }
 }
 
@@ -224,47 +1260,72 @@ fn test_transaction_record_into_transaction_well_formed() {
         (
             TransactionRecord {
                 tx_type: TransactionRecordType::Withdrawal,
-                client_id: 1,
-                transaction_id: 5,
+                client_id: ClientId(1),
+                transaction_id: TransactionId(5),
                 amount: Some(Decimal::new(12, 1)),
+                memo: None,
+                timestamp: None,
             },
-            Transaction::new(transaction::Type::Withdrawal(Decimal::new(12, 1)), 1, 5),
+            Transaction::new(
+                transaction::Type::Withdrawal(Decimal::new(12, 1)),
+                ClientId(1),
+                TransactionId(5),
+            )
+            .unwrap(),
         ),
         (
             TransactionRecord {
                 tx_type: TransactionRecordType::Deposit,
-                client_id: 2,
-                transaction_id: 4,
+                client_id: ClientId(2),
+                transaction_id: TransactionId(4),
                 amount: Some(Decimal::new(21, 1)),
+                memo: None,
+                timestamp: None,
             },
-            Transaction::new(transaction::Type::Deposit(Decimal::new(21, 1)), 2, 4),
+            Transaction::new(
+                transaction::Type::Deposit(Decimal::new(21, 1)),
+                ClientId(2),
+                TransactionId(4),
+            )
+            .unwrap(),
         ),
         (
             TransactionRecord {
                 tx_type: TransactionRecordType::Dispute,
-                client_id: 33,
-                transaction_id: 333,
+                client_id: ClientId(33),
+                transaction_id: TransactionId(333),
                 amount: None,
+                memo: None,
+                timestamp: None,
             },
-            Transaction::new(transaction::Type::Dispute, 33, 333),
+            Transaction::new(transaction::Type::Dispute, ClientId(33), TransactionId(333)).unwrap(),
         ),
         (
             TransactionRecord {
                 tx_type: TransactionRecordType::Resolve,
-                client_id: 44,
-                transaction_id: 444,
+                client_id: ClientId(44),
+                transaction_id: TransactionId(444),
                 amount: None,
+                memo: None,
+                timestamp: None,
             },
-            Transaction::new(transaction::Type::Resolve, 44, 444),
+            Transaction::new(transaction::Type::Resolve, ClientId(44), TransactionId(444)).unwrap(),
         ),
         (
             TransactionRecord {
                 tx_type: TransactionRecordType::Chargeback,
-                client_id: 55,
-                transaction_id: 555,
+                client_id: ClientId(55),
+                transaction_id: TransactionId(555),
                 amount: None,
+                memo: None,
+                timestamp: None,
             },
-            Transaction::new(transaction::Type::Chargeback, 55, 555),
+            Transaction::new(
+                transaction::Type::Chargeback,
+                ClientId(55),
+                TransactionId(555),
+            )
+            .unwrap(),
         ),
     ];
 
@@ -278,11 +1339,77 @@ fn test_transaction_record_into_transaction_well_formed() {
 fn test_transaction_record_into_transaction_invalid_data() {
     let record = TransactionRecord {
         tx_type: TransactionRecordType::Withdrawal,
-        client_id: 1,
-        transaction_id: 2,
+        client_id: ClientId(1),
+        transaction_id: TransactionId(2),
         amount: None,
+        memo: None,
+        timestamp: None,
     };
 
     let got = Transaction::try_from(record);
     assert_eq!(Err("missing amount for withdrawal"), got);
 }
+
+#[test]
+// Client IDs used to be u16: make sure larger IDs are not truncated.
+fn test_parse_large_client_id() {
+    use rust_decimal_macros::dec;
+
+    let data = "type,client,tx,amount
+deposit,65536,1,1.0
+deposit,4294967295,2,1.0
+deposit,4294967296,3,1.0
+";
+    let (transactions, errors) = parse(data.as_bytes(), ParseConfig::default());
+
+    assert_eq!(
+        vec![
+            Transaction::new(
+                transaction::Type::Deposit(dec!(1)),
+                ClientId(65536),
+                TransactionId(1)
+            )
+            .unwrap(),
+            Transaction::new(
+                transaction::Type::Deposit(dec!(1)),
+                ClientId(u32::MAX),
+                TransactionId(2)
+            )
+            .unwrap(),
+        ],
+        transactions.iter().collect::<Vec<_>>()
+    );
+    // Too large for a client ID.
+    assert_eq!(1, errors.iter().count());
+}
+
+#[test]
+// Transaction IDs used to be u32: make sure larger IDs are not truncated.
+fn test_parse_large_transaction_id() {
+    use rust_decimal_macros::dec;
+
+    let data = "type,client,tx,amount
+deposit,1,4294967296,1.0
+deposit,1,18446744073709551615,1.0
+";
+    let (transactions, errors) = parse(data.as_bytes(), ParseConfig::default());
+
+    assert_eq!(
+        vec![
+            Transaction::new(
+                transaction::Type::Deposit(dec!(1)),
+                ClientId(1),
+                TransactionId(4_294_967_296)
+            )
+            .unwrap(),
+            Transaction::new(
+                transaction::Type::Deposit(dec!(1)),
+                ClientId(1),
+                TransactionId(u64::MAX)
+            )
+            .unwrap(),
+        ],
+        transactions.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(0, errors.iter().count());
+}
@@ -0,0 +1,195 @@
+use super::{check_header_record, convert, Error, ParseConfig, TransactionRecord};
+use crate::ledger::transaction::Transaction;
+
+use futures::stream::{self, Stream};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader, Lines};
+
+/// Same as `parse()`, but reads the input stream asynchronously, and lazily
+/// returns the transactions and errors as a stream, instead of parsing them in
+/// a separate thread.
+///
+/// Each line is parsed on its own, so quoted fields can't span several lines.
+pub fn parse_async(
+    input_stream: impl AsyncRead + Unpin,
+    config: ParseConfig,
+) -> impl Stream<Item = Result<Transaction, Error>> {
+    let state = State {
+        lines: BufReader::new(input_stream).lines(),
+        headers: None,
+        line: 0,
+        done: false,
+        config,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        let item = state.next().await?;
+        if item.is_err() && state.config.stop_on_error {
+            state.done = true;
+        }
+        Some((item, state))
+    })
+}
+
+struct State<R> {
+    lines: Lines<BufReader<R>>,
+    headers: Option<csv::StringRecord>,
+    line: u64,
+    // Set when the rest of the input can't be read.
+    done: bool,
+    config: ParseConfig,
+}
+
+impl<R: AsyncRead + Unpin> State<R> {
+    // Returns None once the input has been fully read.
+    async fn next(&mut self) -> Option<Result<Transaction, Error>> {
+        while !self.done {
+            let mut line = match self.lines.next_line().await {
+                Ok(line) => line?,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(Error::Csv(err.to_string())));
+                }
+            };
+            self.line += 1;
+
+            // Some exporters (especially on Windows) start the file with a
+            // UTF-8 BOM.
+            if self.line == 1 && line.starts_with('\u{FEFF}') {
+                line.remove(0);
+            }
+
+            let record = match self.read_record(&line) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            };
+
+            let Some(headers) = &self.headers else {
+                // Without a header row, every following record would be misread.
+                if let Err(err) = check_header_record(&record) {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                self.headers = Some(record);
+                continue;
+            };
+
+            if record.len() != headers.len() {
+                return Some(Err(Error::UnexpectedColumnCount {
+                    expected: headers.len(),
+                    got: record.len(),
+                    line: self.line,
+                }));
+            }
+
+            let record = record.deserialize::<TransactionRecord>(Some(headers));
+            return Some(convert(record, self.config.ledger_config));
+        }
+
+        None
+    }
+
+    // Blank lines are skipped, like `parse()` does.
+    fn read_record(&self, line: &str) -> Result<Option<csv::StringRecord>, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(self.config.delimiter)
+            .trim(if self.config.trim {
+                csv::Trim::All
+            } else {
+                csv::Trim::None
+            })
+            .from_reader(line.as_bytes());
+
+        let mut record = csv::StringRecord::new();
+        if reader.read_record(&mut record)? {
+            Ok(Some(record))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod parse_async_tests {
+    use super::parse_async;
+    use crate::{
+        input::{Error, ParseConfig},
+        ledger::{
+            transaction::{Transaction, Type},
+            ClientId, TransactionId,
+        },
+    };
+
+    use futures::{executor::block_on, StreamExt};
+    use rust_decimal_macros::dec;
+
+    fn parse_all(data: &str, config: ParseConfig) -> Vec<Result<Transaction, Error>> {
+        block_on(parse_async(data.as_bytes(), config).collect())
+    }
+
+    #[test]
+    // The async parser should return the same transactions and errors as the
+    // synchronous one.
+    fn test_parse_async() {
+        let data = "type, client, tx, amount
+deposit, 1, 1, 1.0
+
+withdrawal, 1, 2, 0.5
+dispute, 1, 1,
+dispute, 1, 1
+deposit, 1, 3, -1
+";
+
+        assert_eq!(
+            vec![
+                Ok(
+                    Transaction::new(Type::Deposit(dec!(1)), ClientId(1), TransactionId(1))
+                        .unwrap()
+                ),
+                Ok(
+                    Transaction::new(Type::Withdrawal(dec!(0.5)), ClientId(1), TransactionId(2))
+                        .unwrap()
+                ),
+                Ok(Transaction::new(Type::Dispute, ClientId(1), TransactionId(1)).unwrap()),
+                Err(Error::UnexpectedColumnCount {
+                    expected: 4,
+                    got: 3,
+                    line: 6,
+                }),
+                Err(Error::Format("amount must be positive".to_string())),
+            ],
+            parse_all(data, ParseConfig::default())
+        );
+
+        let (transactions, errors) =
+            crate::input::parse(std::io::Cursor::new(data), ParseConfig::default());
+        assert_eq!(3, transactions.iter().count());
+        assert_eq!(2, errors.iter().count());
+    }
+
+    #[test]
+    fn test_parse_async_tsv() {
+        assert_eq!(
+            vec![Ok(Transaction::new(
+                Type::Deposit(dec!(2)),
+                ClientId(1),
+                TransactionId(1)
+            )
+            .unwrap())],
+            parse_all(
+                "type\tclient\ttx\tamount\ndeposit\t1\t1\t2.0\n",
+                ParseConfig::builder().delimiter(b'\t').build()
+            )
+        );
+    }
+
+    #[test]
+    // Without a header, nothing else is parsed.
+    fn test_parse_async_missing_header() {
+        assert_eq!(
+            vec![Err(Error::MissingHeader)],
+            parse_all("deposit,1,1,1.0\ndeposit,1,2,1.0\n", ParseConfig::default())
+        );
+    }
+}
@@ -1,39 +1,181 @@
 use crate::ledger::{
     transaction::{self, Transaction},
-    Amount, TransactionId,
+    Amount, ClientId, LedgerConfig, TransactionId,
 };
 
 use super::balance::Balance;
+use chrono::{DateTime, Utc};
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-/// Note: I chose to keep errors simple here.
-/// In a real-world scenario, we would most likely need some debugging info
-/// (e.g. `tx_id`, `client_id`, `amount`, `tx_type` and some info about the current state)
-#[derive(Debug, PartialEq)]
+/// Errors that can happen when applying a transaction to an account.
+///
+/// Errors related to a specific transaction carry the `tx_id`, `client_id`
+/// and (when known) the `amount` of that transaction, so failures can be
+/// traced without re-running the whole file.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TransactionError {
     /// Account is already frozen, so no more transactions can be applied.
-    FrozenAccount,
+    FrozenAccount {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
 
     /// Funds in the account are unsufficient for a withdrawal.
-    NotEnoughFunds,
+    NotEnoughFunds {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
 
     /// A transaction with the same ID already exists.
-    DuplicateTransaction,
+    DuplicateTransaction {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
 
     /// The dispute, resolve or chargeback references a transaction that doesn't exist.
-    UnknownTransaction,
+    UnknownTransaction {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
 
     /// A dispute or resolve is applied on a transaction, but the current transaction
     /// state doesn't allow it.
-    InvalidTransaction,
+    InvalidTransaction {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
+
+    /// A dispute, resolve or chargeback references a transaction that belongs
+    /// to another client.
+    ClientMismatch {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
 
     /// Adding more money to the balance would overflow.
     Overflow,
+
+    /// A deposit or withdrawal has a negative amount.
+    NegativeAmount,
+
+    /// A deposit or withdrawal has an amount of zero.
+    ZeroAmount,
+
+    /// A deposit or withdrawal has a transaction ID lower than or equal to the
+    /// highest one the client already used. Only checked when
+    /// `LedgerConfig::enforce_monotonic_tx_id` is enabled.
+    OutOfOrderTransaction {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
+
+    /// A deposit exceeds one of the limits set in the `LedgerConfig`: either
+    /// its amount is too large, or the account balance would become too large.
+    AmountTooLarge {
+        tx_id: TransactionId,
+        client_id: ClientId,
+        amount: Option<Amount>,
+    },
+}
+
+impl TransactionError {
+    /// The transaction this error relates to, if any: `(tx_id, client_id, amount)`.
+    #[must_use]
+    pub const fn context(&self) -> Option<(TransactionId, ClientId, Option<Amount>)> {
+        match *self {
+            Self::FrozenAccount {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::NotEnoughFunds {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::DuplicateTransaction {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::UnknownTransaction {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::InvalidTransaction {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::ClientMismatch {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::OutOfOrderTransaction {
+                tx_id,
+                client_id,
+                amount,
+            }
+            | Self::AmountTooLarge {
+                tx_id,
+                client_id,
+                amount,
+            } => Some((tx_id, client_id, amount)),
+            Self::Overflow | Self::NegativeAmount | Self::ZeroAmount => None,
+        }
+    }
 }
 
+impl std::fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            Self::FrozenAccount { .. } => "account is frozen and cannot accept transactions",
+            Self::NotEnoughFunds { .. } => "insufficient available funds for this withdrawal",
+            Self::DuplicateTransaction { .. } => "a transaction with the same id already exists",
+            Self::UnknownTransaction { .. } => "the referenced transaction does not exist",
+            Self::InvalidTransaction { .. } => {
+                "the referenced transaction is not in a state that allows this operation"
+            }
+            Self::ClientMismatch { .. } => "the referenced transaction belongs to another client",
+            Self::Overflow => "the operation would overflow the balance",
+            Self::NegativeAmount => "the amount must not be negative",
+            Self::ZeroAmount => "the amount must not be zero",
+            Self::OutOfOrderTransaction { .. } => {
+                "the transaction id is not greater than the previous one"
+            }
+            Self::AmountTooLarge { .. } => "the amount exceeds the allowed limit",
+        };
+        f.write_str(msg)?;
+
+        match self.context() {
+            Some((tx_id, client_id, Some(amount))) => {
+                write!(f, " (client: {client_id}, tx: {tx_id}, amount: {amount})")
+            }
+            Some((tx_id, client_id, None)) => write!(f, " (client: {client_id}, tx: {tx_id})"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
 /// The current state of a transaction, used to know whether we apply a new
 /// status on it or not.
+///
+/// It is serialized with the labels of `as_str()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TransactionState {
     /// An amount has been withdrawn.
     Withdrawn,
@@ -45,9 +187,46 @@ pub enum TransactionState {
     Disputed,
 
     /// The deposit was charged back.
+    // Accounts saved before the labels matched `as_str()` used "chargedback".
+    #[serde(alias = "chargedback")]
     ChargedBack,
 }
 
+impl TransactionState {
+    /// Human-readable label, e.g. `charged_back`.
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Withdrawn => "withdrawn",
+            Self::Deposited => "deposited",
+            Self::Disputed => "disputed",
+            Self::ChargedBack => "charged_back",
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TransactionState {
+    type Err = &'static str;
+
+    /// Parse a label returned by `as_str()`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "withdrawn" => Ok(Self::Withdrawn),
+            "deposited" => Ok(Self::Deposited),
+            "disputed" => Ok(Self::Disputed),
+            // Same alias as for serde.
+            "charged_back" | "chargedback" => Ok(Self::ChargedBack),
+            _ => Err("unknown transaction state"),
+        }
+    }
+}
+
 /// Account is a state-machine, to which you can apply transactions.
 ///
 /// In other words, every time you apply a transaction to the Account, it will
@@ -56,61 +235,481 @@ pub enum TransactionState {
 ///
 /// In the assignment PDF, an account is interchangeably called account, account
 /// balance, client account, asset account.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Account {
     pub frozen: bool,
     pub available: Balance,
     pub held: Balance,
 
-    // tx_states holds the last known state of each transaction.
-    pub(super) tx_states: HashMap<TransactionId, (TransactionState, Amount)>,
+    // tx_states holds the last known state of each transaction, its amount,
+    // and the client that created it.
+    #[serde(with = "tx_states_serde")]
+    pub(super) tx_states: HashMap<TransactionId, (TransactionState, Amount, ClientId)>,
+
+    // tx_metadata holds the metadata of the deposits and withdrawals that have
+    // any, e.g. a memo, along with the memo of the last dispute, resolve or
+    // chargeback that referenced them with one.
+    #[serde(default)]
+    pub(super) tx_metadata: HashMap<TransactionId, TransactionMetadata>,
+
+    // last_tx_id is the highest ID of the deposits and withdrawals applied so
+    // far, to detect transactions arriving out of order.
+    #[serde(default)]
+    pub(super) last_tx_id: Option<TransactionId>,
+
+    // tx_fees holds the fee taken on each withdrawal, when there was one.
+    #[serde(default, with = "amount_map_serde")]
+    pub(super) tx_fees: HashMap<TransactionId, Amount>,
+
+    // tx_chargebacks holds the amount charged back from each deposit so far,
+    // when some was. tx_states keeps the original amount of the deposit.
+    #[serde(default, with = "amount_map_serde")]
+    pub(super) tx_chargebacks: HashMap<TransactionId, Amount>,
+}
+
+/// Free-form information attached to a transaction, that doesn't change how
+/// it is applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionMetadata {
+    pub memo: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+
+    // Metadata saved before disputes had memos doesn't have this field.
+    #[serde(default)]
+    pub dispute_memo: Option<String>,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Decimals are deserialized with `deserialize_any` by default, which isn't
+// supported by formats that are not self-describing, such as bincode. The
+// amounts are (de)serialized as strings instead.
+mod tx_states_serde {
+    use super::{Amount, ClientId, HashMap, TransactionId, TransactionState};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct StrAmount(#[serde(with = "rust_decimal::serde::str")] pub(super) Amount);
+
+    type TxStates = HashMap<TransactionId, (TransactionState, Amount, ClientId)>;
+
+    pub fn serialize<S: Serializer>(
+        tx_states: &TxStates,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            tx_states
+                .iter()
+                .map(|(tx_id, (tx_state, amount, client_id))| {
+                    (tx_id, (tx_state, StrAmount(*amount), client_id))
+                }),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TxStates, D::Error> {
+        let tx_states =
+            HashMap::<TransactionId, (TransactionState, StrAmount, ClientId)>::deserialize(
+                deserializer,
+            )?;
+
+        Ok(tx_states
+            .into_iter()
+            .map(|(tx_id, (tx_state, StrAmount(amount), client_id))| {
+                (tx_id, (tx_state, amount, client_id))
+            })
+            .collect())
+    }
+}
+
+// Amounts per transaction, e.g. the fees or the chargebacks, stored like the
+// amounts in tx_states.
+mod amount_map_serde {
+    use super::{tx_states_serde::StrAmount, Amount, HashMap, TransactionId};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        amounts: &HashMap<TransactionId, Amount>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            amounts
+                .iter()
+                .map(|(tx_id, amount)| (tx_id, StrAmount(*amount))),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<TransactionId, Amount>, D::Error> {
+        let amounts = HashMap::<TransactionId, StrAmount>::deserialize(deserializer)?;
+        Ok(amounts
+            .into_iter()
+            .map(|(tx_id, StrAmount(amount))| (tx_id, amount))
+            .collect())
+    }
 }
 
 impl Account {
+    #[must_use]
     pub fn new() -> Self {
         Self {
             frozen: false,
             available: Balance::new(dec!(0), dec!(0)),
             held: Balance::new(dec!(0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         }
     }
 
     /// Calculate the total amount stored in the balance.
+    #[must_use]
     pub fn total_amount(&self) -> Amount {
         self.available.amount() + self.held.amount()
     }
 
+    /// Unfreeze the account, so it accepts transactions again.
+    ///
+    /// This is an admin-only operation, e.g. to reinstate an account after an
+    /// investigation following a chargeback: it must never be triggered by a
+    /// client transaction. Unfreezing an account that isn't frozen does
+    /// nothing.
+    pub const fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// Whether a withdrawal of the given amount would currently succeed.
+    /// It performs the same checks as `apply()` without changing the account,
+    /// except for duplicate transaction IDs which cannot be known in advance.
+    #[must_use]
+    pub fn can_withdraw(&self, amount: Amount) -> bool {
+        !self.frozen && amount > dec!(0) && self.available.is_sufficient_for(amount)
+    }
+
+    /// Whether the given transaction can currently be disputed, i.e. it is a
+    /// deposit that is neither disputed nor charged back already.
+    #[must_use]
+    pub fn can_dispute(&self, tx_id: TransactionId) -> bool {
+        !self.frozen
+            && matches!(
+                self.tx_states.get(&tx_id),
+                Some((TransactionState::Deposited, _, _))
+            )
+    }
+
+    /// Number of deposits and withdrawals applied to the account.
+    /// Disputes, resolves and chargebacks only change the state of an existing
+    /// transaction, so they are not counted.
+    #[must_use]
+    pub fn transaction_count(&self) -> usize {
+        self.tx_states.len()
+    }
+
+    /// Number of transactions currently disputed. A resolved dispute goes
+    /// back to being a deposit, so it is not counted.
+    #[must_use]
+    pub fn dispute_count(&self) -> usize {
+        self.count_in_state(&TransactionState::Disputed)
+    }
+
+    /// Number of transactions that were charged back.
+    #[must_use]
+    pub fn chargeback_count(&self) -> usize {
+        self.count_in_state(&TransactionState::ChargedBack)
+    }
+
+    /// IDs of the transactions currently disputed, in no particular order.
+    pub fn pending_disputes(&self) -> impl Iterator<Item = TransactionId> + '_ {
+        self.tx_states
+            .iter()
+            .filter(|(_, (tx_state, _, _))| *tx_state == TransactionState::Disputed)
+            .map(|(tx_id, _)| *tx_id)
+    }
+
+    /// All the deposits and withdrawals applied to the account, whatever their
+    /// current state, with their amount. In no particular order.
+    pub fn history(&self) -> impl Iterator<Item = (TransactionId, &TransactionState, Amount)> + '_ {
+        self.tx_states
+            .iter()
+            .map(|(tx_id, (tx_state, amount, _))| (*tx_id, tx_state, *amount))
+    }
+
+    /// Total amount of the transactions currently disputed, without the parts
+    /// that were already charged back.
+    #[must_use]
+    pub fn disputed_amount(&self) -> Amount {
+        self.tx_states
+            .iter()
+            .filter(|(_, (tx_state, _, _))| *tx_state == TransactionState::Disputed)
+            .map(|(tx_id, (_, amount, _))| *amount - self.chargedback_so_far(*tx_id))
+            .sum()
+    }
+
+    /// Total amount that was charged back, including partial chargebacks.
+    #[must_use]
+    pub fn chargedback_amount(&self) -> Amount {
+        self.tx_states
+            .iter()
+            .map(|(tx_id, (tx_state, amount, _))| {
+                if *tx_state == TransactionState::ChargedBack {
+                    *amount
+                } else {
+                    self.chargedback_so_far(*tx_id)
+                }
+            })
+            .sum()
+    }
+
+    fn chargedback_so_far(&self, tx_id: TransactionId) -> Amount {
+        self.tx_chargebacks
+            .get(&tx_id)
+            .copied()
+            .unwrap_or(Amount::ZERO)
+    }
+
+    fn count_in_state(&self, state: &TransactionState) -> usize {
+        self.tx_states
+            .values()
+            .filter(|(tx_state, _, _)| tx_state == state)
+            .count()
+    }
+
     // Note:
     // I'm making the assumption that clients cannot dispute withdrawals.
     // I'm basing that on the fact that the PDF says that disputes
     // "decrease the available funds", i.e. cancels a deposit, but
     // never the opposite. This also seems to generally make sense.
+    /// Apply a single transaction to the account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be applied, e.g. when the
+    /// account is frozen or doesn't have enough funds.
     pub fn apply(&mut self, tx: &Transaction) -> Result<(), TransactionError> {
+        self.apply_with_config(tx, &LedgerConfig::default())
+    }
+
+    /// Same as `apply()`, with the checks enabled in `config`.
+    ///
+    /// Each call gets its own debug span, so does `apply()` since it delegates here.
+    /// When the transaction fails, the error is recorded in the span as a debug
+    /// event: it is still up to the caller to report it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be applied, e.g. when the
+    /// account is frozen or doesn't have enough funds.
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(tx_id = %tx.tx_id, client_id = %tx.client_id),
+        err(level = "debug")
+    )]
+    pub fn apply_with_config(
+        &mut self,
+        tx: &Transaction,
+        config: &LedgerConfig,
+    ) -> Result<(), TransactionError> {
         // When an account is frozen, no transaction whatsoever should be applied to it.
         if self.frozen {
-            return Err(TransactionError::FrozenAccount);
+            return Err(TransactionError::FrozenAccount {
+                tx_id: tx.tx_id,
+                client_id: tx.client_id,
+                amount: tx.amount(),
+            });
         }
 
-        match tx.tx_type {
-            transaction::Type::Withdrawal(amount) => self.apply_withdrawal(tx.tx_id, amount),
-            transaction::Type::Deposit(amount) => self.apply_deposit(tx.tx_id, amount),
-            transaction::Type::Dispute => self.apply_dispute(tx.tx_id),
-            transaction::Type::Resolve => self.apply_resolve(tx.tx_id),
-            transaction::Type::Chargeback => self.apply_chargeback(tx.tx_id),
+        let is_stored = matches!(
+            tx.tx_type,
+            transaction::Type::Withdrawal(_) | transaction::Type::Deposit(_)
+        );
+        if is_stored {
+            self.check_tx_order(tx.client_id, tx.tx_id, tx.amount(), config)?;
         }
+
+        let result = match tx.tx_type {
+            transaction::Type::Withdrawal(amount) => {
+                self.apply_withdrawal(tx.client_id, tx.tx_id, amount, config)
+            }
+            transaction::Type::Deposit(amount) => {
+                self.apply_deposit(tx.client_id, tx.tx_id, amount, config)
+            }
+            transaction::Type::Dispute => self.apply_dispute(tx.client_id, tx.tx_id, config),
+            transaction::Type::Resolve => self.apply_resolve(tx.client_id, tx.tx_id),
+            transaction::Type::Chargeback => self.apply_chargeback(tx.client_id, tx.tx_id, None),
+            transaction::Type::PartialChargeback(amount) => {
+                self.apply_chargeback(tx.client_id, tx.tx_id, Some(amount))
+            }
+            // A transfer involves two accounts, so it can only be applied by
+            // the ledger.
+            transaction::Type::Transfer { .. } => Err(TransactionError::InvalidTransaction {
+                tx_id: tx.tx_id,
+                client_id: tx.client_id,
+                amount: tx.amount(),
+            }),
+        };
+
+        result?;
+
+        // Only deposits and withdrawals are stored: the other transactions
+        // reference them, so their memo is kept with the referenced one.
+        if !is_stored {
+            if let Some(memo) = tx.memo() {
+                self.tx_metadata.entry(tx.tx_id).or_default().dispute_memo = Some(memo.to_string());
+            }
+            return Ok(());
+        }
+
+        self.last_tx_id = self.last_tx_id.max(Some(tx.tx_id));
+        if tx.memo().is_some() || tx.timestamp().is_some() {
+            self.tx_metadata.insert(
+                tx.tx_id,
+                TransactionMetadata {
+                    memo: tx.memo().map(str::to_string),
+                    timestamp: tx.timestamp(),
+                    dispute_memo: None,
+                },
+            );
+        }
+
+        Ok(())
     }
 
-    /// Get the current stored state of a transaction, as well as the transaction amount.
-    pub(super) fn get_tx_state(
+    // With `enforce_monotonic_tx_id`, a new deposit or withdrawal must have a
+    // higher ID than the previous ones.
+    pub(super) fn check_tx_order(
         &self,
+        client_id: ClientId,
         tx_id: TransactionId,
-    ) -> Result<(&TransactionState, Amount), TransactionError> {
-        let (tx_state, amount) = self
-            .tx_states
+        amount: Option<Amount>,
+        config: &LedgerConfig,
+    ) -> Result<(), TransactionError> {
+        if config.enforce_monotonic_tx_id && self.last_tx_id.is_some_and(|last| tx_id <= last) {
+            return Err(TransactionError::OutOfOrderTransaction {
+                tx_id,
+                client_id,
+                amount,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The original amount of a deposit or withdrawal, whatever its current
+    /// state, even after a partial chargeback.
+    #[must_use]
+    pub fn get_tx_amount(&self, tx_id: TransactionId) -> Option<Amount> {
+        self.tx_states.get(&tx_id).map(|(_, amount, _)| *amount)
+    }
+
+    /// The amount charged back from a deposit so far, if any was.
+    #[must_use]
+    pub fn get_tx_chargedback_amount(&self, tx_id: TransactionId) -> Option<Amount> {
+        match self.tx_states.get(&tx_id) {
+            Some((TransactionState::ChargedBack, amount, _)) => Some(*amount),
+            _ => self.tx_chargebacks.get(&tx_id).copied(),
+        }
+    }
+
+    /// The fee taken on a withdrawal, if there was one.
+    #[must_use]
+    pub fn get_tx_fee(&self, tx_id: TransactionId) -> Option<Amount> {
+        self.tx_fees.get(&tx_id).copied()
+    }
+
+    /// The memo of a deposit or withdrawal, if it had any.
+    #[must_use]
+    pub fn get_tx_memo(&self, tx_id: TransactionId) -> Option<&str> {
+        self.tx_metadata.get(&tx_id)?.memo.as_deref()
+    }
+
+    /// The memo of the last dispute, resolve or chargeback of a deposit or
+    /// withdrawal that had one.
+    #[must_use]
+    pub fn get_tx_dispute_memo(&self, tx_id: TransactionId) -> Option<&str> {
+        self.tx_metadata.get(&tx_id)?.dispute_memo.as_deref()
+    }
+
+    /// When a deposit or withdrawal happened, if it was known.
+    #[must_use]
+    pub fn get_tx_timestamp(&self, tx_id: TransactionId) -> Option<DateTime<Utc>> {
+        self.tx_metadata.get(&tx_id)?.timestamp
+    }
+
+    /// Apply all the transactions, or none of them: if any transaction fails,
+    /// the account is left unchanged and the first error is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first transaction that couldn't be applied.
+    pub fn apply_batch(&mut self, txs: &[Transaction]) -> Result<(), TransactionError> {
+        // Work on a copy, so we can simply drop it to roll back.
+        let mut account = self.clone();
+        for tx in txs {
+            account.apply(tx)?;
+        }
+
+        *self = account;
+        Ok(())
+    }
+
+    /// The current state of a deposit or withdrawal, and its amount.
+    #[must_use]
+    pub fn get_tx_state(&self, tx_id: TransactionId) -> Option<(&TransactionState, Amount)> {
+        self.tx_states
             .get(&tx_id)
-            .ok_or(TransactionError::UnknownTransaction)?;
+            .map(|(tx_state, amount, _)| (tx_state, *amount))
+    }
 
-        Ok((tx_state, *amount))
+    /// The current state of a deposit or withdrawal.
+    #[must_use]
+    pub fn tx_state(&self, tx_id: TransactionId) -> Option<&TransactionState> {
+        self.get_tx_state(tx_id).map(|(tx_state, _)| tx_state)
+    }
+
+    // Only the state changes: the amount and the client stay the ones of the
+    // original deposit.
+    pub(super) fn set_tx_state(&mut self, tx_id: TransactionId, state: TransactionState) {
+        if let Some((tx_state, _, _)) = self.tx_states.get_mut(&tx_id) {
+            *tx_state = state;
+        }
+    }
+
+    /// Same as `get_tx_state()`, for a dispute, resolve or chargeback: the
+    /// referenced transaction must exist, and have been created by the same
+    /// client.
+    pub(super) fn get_referenced_tx_state(
+        &self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+    ) -> Result<(&TransactionState, Amount), TransactionError> {
+        let (tx_state, amount, tx_client_id) =
+            self.tx_states
+                .get(&tx_id)
+                .ok_or(TransactionError::UnknownTransaction {
+                    tx_id,
+                    client_id,
+                    amount: None,
+                })?;
+
+        // The amount is not reported, as it belongs to another client.
+        if *tx_client_id != client_id {
+            return Err(TransactionError::ClientMismatch {
+                tx_id,
+                client_id,
+                amount: None,
+            });
+        }
+
+        // The parts that were already charged back can't be disputed, resolved
+        // or charged back again.
+        Ok((tx_state, *amount - self.chargedback_so_far(tx_id)))
     }
 }
 
@@ -119,6 +718,7 @@ mod tests {
     use crate::ledger::{
         account::account::{Account, Balance, TransactionError},
         transaction::{self, Transaction},
+        ClientId, LedgerConfig, TransactionId,
     };
 
     #[test]
@@ -131,16 +731,29 @@ mod tests {
             available: Balance::new(dec!(3.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(2.0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
         let transaction = Transaction {
             tx_type: transaction::Type::Deposit(dec!(5000)),
-            client_id: 15,
-            tx_id: 12,
+            client_id: ClientId(15),
+            tx_id: TransactionId(12),
+            memo: None,
+            timestamp: None,
         };
 
         let got = acc.apply(&transaction);
-        assert_eq!(Err(TransactionError::FrozenAccount), got);
+        assert_eq!(
+            Err(TransactionError::FrozenAccount {
+                tx_id: TransactionId(12),
+                client_id: ClientId(15),
+                amount: Some(dec!(5000)),
+            }),
+            got
+        );
     }
 
     #[test]
@@ -153,12 +766,18 @@ mod tests {
             available: Balance::new(dec!(0), dec!(0)),
             held: Balance::new(dec!(0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
         let deposit = Transaction {
             tx_type: transaction::Type::Deposit(dec!(5000)),
-            client_id: 1,
-            tx_id: 1,
+            client_id: ClientId(1),
+            tx_id: TransactionId(1),
+            memo: None,
+            timestamp: None,
         };
         acc.apply(&deposit).expect("should apply a deposit");
         assert_eq!(dec!(5000), acc.available.amount());
@@ -166,8 +785,10 @@ mod tests {
 
         let dispute = Transaction {
             tx_type: transaction::Type::Dispute,
-            client_id: 1,
-            tx_id: 1,
+            client_id: ClientId(1),
+            tx_id: TransactionId(1),
+            memo: None,
+            timestamp: None,
         };
         acc.apply(&dispute).expect("should apply a dispute");
         assert_eq!(dec!(0), acc.available.amount());
@@ -175,8 +796,10 @@ mod tests {
 
         let resolve = Transaction {
             tx_type: transaction::Type::Resolve,
-            client_id: 1,
-            tx_id: 1,
+            client_id: ClientId(1),
+            tx_id: TransactionId(1),
+            memo: None,
+            timestamp: None,
         };
         acc.apply(&resolve).expect("should apply a resolve");
         assert_eq!(dec!(5000), acc.available.amount());
@@ -184,8 +807,10 @@ mod tests {
 
         let withdrawal = Transaction {
             tx_type: transaction::Type::Withdrawal(dec!(1000)),
-            client_id: 1,
-            tx_id: 2,
+            client_id: ClientId(1),
+            tx_id: TransactionId(2),
+            memo: None,
+            timestamp: None,
         };
         acc.apply(&withdrawal).expect("should apply a withdrawal");
         assert_eq!(dec!(4000), acc.available.amount());
@@ -198,8 +823,10 @@ mod tests {
 
         let chargeback = Transaction {
             tx_type: transaction::Type::Chargeback,
-            client_id: 1,
-            tx_id: 1,
+            client_id: ClientId(1),
+            tx_id: TransactionId(1),
+            memo: None,
+            timestamp: None,
         };
         acc.apply(&chargeback).expect("should apply a chargeback");
         assert!(acc.frozen);
@@ -207,6 +834,727 @@ mod tests {
         assert_eq!(dec!(0), acc.held.amount());
     }
 
+    #[test]
+    fn test_transaction_count() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        assert_eq!(0, acc.transaction_count());
+
+        for (tx_type, tx_id, want_count) in [
+            (transaction::Type::Deposit(dec!(10)), 1, 1),
+            (transaction::Type::Deposit(dec!(5)), 2, 2),
+            // Disputes and resolves don't create new transactions.
+            (transaction::Type::Dispute, 1, 2),
+            (transaction::Type::Resolve, 1, 2),
+            (transaction::Type::Withdrawal(dec!(3)), 3, 3),
+        ] {
+            let transaction = Transaction {
+                tx_type,
+                client_id: ClientId(1),
+                tx_id: TransactionId(tx_id),
+                memo: None,
+                timestamp: None,
+            };
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+            assert_eq!(want_count, acc.transaction_count());
+        }
+
+        // Failed transactions are not counted.
+        let duplicate = Transaction {
+            tx_type: transaction::Type::Deposit(dec!(1)),
+            client_id: ClientId(1),
+            tx_id: TransactionId(1),
+            memo: None,
+            timestamp: None,
+        };
+        assert!(acc.apply(&duplicate).is_err());
+        assert_eq!(3, acc.transaction_count());
+    }
+
+    #[test]
+    fn test_dispute_and_chargeback_count() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        for tx_id in 1..=3 {
+            let deposit = Transaction {
+                tx_type: transaction::Type::Deposit(dec!(10)),
+                client_id: ClientId(1),
+                tx_id: TransactionId(tx_id),
+                memo: None,
+                timestamp: None,
+            };
+            acc.apply(&deposit).expect("should apply a deposit");
+        }
+        assert_eq!(0, acc.dispute_count());
+        assert_eq!(0, acc.chargeback_count());
+
+        for (tx_type, tx_id, want_disputes, want_chargebacks) in [
+            (transaction::Type::Dispute, 1, 1, 0),
+            (transaction::Type::Dispute, 2, 2, 0),
+            // A resolved dispute is not pending anymore.
+            (transaction::Type::Resolve, 1, 1, 0),
+            (transaction::Type::Chargeback, 2, 0, 1),
+        ] {
+            let transaction = Transaction {
+                tx_type,
+                client_id: ClientId(1),
+                tx_id: TransactionId(tx_id),
+                memo: None,
+                timestamp: None,
+            };
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+            assert_eq!(want_disputes, acc.dispute_count());
+            assert_eq!(want_chargebacks, acc.chargeback_count());
+        }
+
+        // Deposit 1 was resolved, and deposit 3 was never disputed.
+        assert_eq!(dec!(0), acc.disputed_amount());
+        assert_eq!(dec!(10), acc.chargedback_amount());
+    }
+
+    #[test]
+    // Two deposits disputed at the same time are held separately: resolving
+    // or charging back one of them only moves its own amount.
+    fn test_simultaneous_disputes() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        for (tx_type, tx_id, want_available, want_held, want_frozen) in [
+            (
+                transaction::Type::Deposit(dec!(10)),
+                1,
+                dec!(10),
+                dec!(0),
+                false,
+            ),
+            (
+                transaction::Type::Deposit(dec!(4)),
+                2,
+                dec!(14),
+                dec!(0),
+                false,
+            ),
+            (
+                transaction::Type::Deposit(dec!(1)),
+                3,
+                dec!(15),
+                dec!(0),
+                false,
+            ),
+            (transaction::Type::Dispute, 1, dec!(5), dec!(10), false),
+            (transaction::Type::Dispute, 2, dec!(1), dec!(14), false),
+            (transaction::Type::Resolve, 1, dec!(11), dec!(4), false),
+            (transaction::Type::Chargeback, 2, dec!(11), dec!(0), true),
+        ] {
+            let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(tx_id)).unwrap();
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+
+            assert_eq!(want_available, acc.available.amount(), "tx {tx_id}");
+            assert_eq!(want_held, acc.held.amount(), "tx {tx_id}");
+            assert_eq!(want_frozen, acc.frozen, "tx {tx_id}");
+        }
+
+        assert_eq!(0, acc.dispute_count());
+        assert_eq!(1, acc.chargeback_count());
+        assert_eq!(dec!(4), acc.chargedback_amount());
+    }
+
+    #[test]
+    fn test_history() {
+        use crate::ledger::account::account::TransactionState;
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        for transaction in [
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .deposit(dec!(10))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(2))
+                .deposit(dec!(5))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(2))
+                .dispute()
+                .unwrap(),
+        ] {
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+        }
+
+        let mut history: Vec<_> = acc.history().collect();
+        history.sort_unstable_by_key(|(tx_id, _, _)| *tx_id);
+        assert_eq!(
+            vec![
+                (TransactionId(1), &TransactionState::Deposited, dec!(10)),
+                (TransactionId(2), &TransactionState::Disputed, dec!(5)),
+            ],
+            history
+        );
+        assert_eq!(0, Account::new().history().count());
+    }
+
+    #[test]
+    fn test_get_tx_amount() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        for transaction in [
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .deposit(dec!(12.3456))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(2))
+                .withdrawal(dec!(2))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .dispute()
+                .unwrap(),
+        ] {
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+        }
+
+        assert_eq!(Some(dec!(12.3456)), acc.get_tx_amount(TransactionId(1)));
+        assert_eq!(Some(dec!(2)), acc.get_tx_amount(TransactionId(2)));
+        assert_eq!(None, acc.get_tx_amount(TransactionId(3)));
+    }
+
+    #[test]
+    fn test_get_tx_state() {
+        use crate::ledger::account::account::TransactionState;
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        for transaction in [
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .deposit(dec!(10))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .dispute()
+                .unwrap(),
+        ] {
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+        }
+
+        assert_eq!(
+            Some((&TransactionState::Disputed, dec!(10))),
+            acc.get_tx_state(TransactionId(1))
+        );
+        assert_eq!(
+            Some(&TransactionState::Disputed),
+            acc.tx_state(TransactionId(1))
+        );
+        assert_eq!(None, acc.get_tx_state(TransactionId(2)));
+        assert_eq!(None, acc.tx_state(TransactionId(2)));
+    }
+
+    #[test]
+    fn test_pending_disputes() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        assert_eq!(None, acc.pending_disputes().next());
+
+        for (tx_type, tx_id) in [
+            (transaction::Type::Deposit(dec!(10)), 1),
+            (transaction::Type::Deposit(dec!(5)), 2),
+            (transaction::Type::Deposit(dec!(2)), 3),
+            (transaction::Type::Dispute, 1),
+            (transaction::Type::Dispute, 2),
+            (transaction::Type::Resolve, 1),
+        ] {
+            let transaction = Transaction {
+                tx_type,
+                client_id: ClientId(1),
+                tx_id: TransactionId(tx_id),
+                memo: None,
+                timestamp: None,
+            };
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+        }
+
+        assert_eq!(
+            vec![TransactionId(2)],
+            acc.pending_disputes().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_can_withdraw() {
+        use rust_decimal_macros::dec;
+        use std::collections::HashMap;
+
+        for (frozen, available, amount, want) in [
+            (false, dec!(10), dec!(5), true),
+            // Exact balance.
+            (false, dec!(10), dec!(10), true),
+            (false, dec!(10), dec!(10.0001), false),
+            (true, dec!(10), dec!(5), false),
+            (false, dec!(10), dec!(0), false),
+            (false, dec!(10), dec!(-5), false),
+            (false, dec!(-10), dec!(5), false),
+        ] {
+            let acc = Account {
+                frozen,
+                available: Balance::new(available, dec!(0)),
+                held: Balance::new(dec!(0), dec!(0)),
+                tx_states: HashMap::new(),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            };
+            assert_eq!(
+                want,
+                acc.can_withdraw(amount),
+                "{frozen} {available} {amount}"
+            );
+
+            // Make sure it is consistent with actually applying the withdrawal.
+            let mut acc = acc;
+            let withdrawal = Transaction {
+                tx_type: transaction::Type::Withdrawal(amount),
+                client_id: ClientId(1),
+                tx_id: TransactionId(1),
+                memo: None,
+                timestamp: None,
+            };
+            assert_eq!(want, acc.apply(&withdrawal).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_can_dispute() {
+        use crate::ledger::account::account::TransactionState;
+        use rust_decimal_macros::dec;
+        use std::collections::HashMap;
+
+        for (frozen, tx_state, want) in [
+            (false, Some(TransactionState::Deposited), true),
+            (false, Some(TransactionState::Withdrawn), false),
+            (false, Some(TransactionState::Disputed), false),
+            (false, Some(TransactionState::ChargedBack), false),
+            (false, None, false),
+            (true, Some(TransactionState::Deposited), false),
+        ] {
+            let acc = Account {
+                frozen,
+                available: Balance::new(dec!(10), dec!(0)),
+                held: Balance::new(dec!(0), dec!(0)),
+                tx_states: tx_state
+                    .map(|tx_state| {
+                        HashMap::from([(TransactionId(1), (tx_state, dec!(10), ClientId(1)))])
+                    })
+                    .unwrap_or_default(),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            };
+            assert_eq!(
+                want,
+                acc.can_dispute(TransactionId(1)),
+                "{frozen} {:?}",
+                acc.tx_states
+            );
+        }
+    }
+
+    #[test]
+    fn test_unfreeze() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        acc.apply_deposit(
+            ClientId(1),
+            TransactionId(1),
+            dec!(10),
+            &LedgerConfig::default(),
+        )
+        .expect("should apply a deposit");
+        acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default())
+            .expect("should apply a dispute");
+        acc.apply_chargeback(ClientId(1), TransactionId(1), None)
+            .expect("should apply a chargeback");
+        assert!(acc.frozen);
+
+        acc.unfreeze();
+        assert!(!acc.frozen);
+        acc.apply_deposit(
+            ClientId(1),
+            TransactionId(2),
+            dec!(5),
+            &LedgerConfig::default(),
+        )
+        .expect("should apply a deposit once unfrozen");
+        assert_eq!(dec!(5), acc.available.amount());
+
+        // Unfreezing an account that isn't frozen is a no-op.
+        acc.unfreeze();
+        assert!(!acc.frozen);
+        assert_eq!(dec!(5), acc.available.amount());
+    }
+
+    #[test]
+    fn test_apply_batch() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        let batch = [
+            Transaction::new(
+                transaction::Type::Deposit(dec!(10)),
+                ClientId(1),
+                TransactionId(1),
+            )
+            .unwrap(),
+            Transaction::new(
+                transaction::Type::Withdrawal(dec!(4)),
+                ClientId(1),
+                TransactionId(2),
+            )
+            .unwrap(),
+            Transaction::new(transaction::Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+        ];
+        acc.apply_batch(&batch).expect("should apply the batch");
+        assert_eq!(dec!(-4), acc.available.amount());
+        assert_eq!(dec!(10), acc.held.amount());
+        assert_eq!(2, acc.transaction_count());
+        assert_eq!(1, acc.dispute_count());
+    }
+
+    #[test]
+    // A failing transaction in the middle of a batch should roll back the
+    // transactions applied before it.
+    fn test_apply_batch_rollback() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        acc.apply(
+            &Transaction::new(
+                transaction::Type::Deposit(dec!(10)),
+                ClientId(1),
+                TransactionId(1),
+            )
+            .unwrap(),
+        )
+        .expect("should apply a deposit");
+        let before = acc.snapshot();
+
+        let batch = [
+            Transaction::new(
+                transaction::Type::Deposit(dec!(5)),
+                ClientId(1),
+                TransactionId(2),
+            )
+            .unwrap(),
+            Transaction::new(
+                transaction::Type::Withdrawal(dec!(100)),
+                ClientId(1),
+                TransactionId(3),
+            )
+            .unwrap(),
+            Transaction::new(transaction::Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+        ];
+        assert_eq!(
+            Err(TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(3),
+                client_id: ClientId(1),
+                amount: Some(dec!(100)),
+            }),
+            acc.apply_batch(&batch)
+        );
+        assert_eq!(before, acc.snapshot());
+    }
+
+    #[test]
+    fn test_clone() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        acc.apply(
+            &Transaction::new(
+                transaction::Type::Deposit(dec!(10)),
+                ClientId(1),
+                TransactionId(1),
+            )
+            .unwrap(),
+        )
+        .expect("should apply a deposit");
+
+        let clone = acc.clone();
+        acc.apply(
+            &Transaction::new(
+                transaction::Type::Deposit(dec!(5)),
+                ClientId(1),
+                TransactionId(2),
+            )
+            .unwrap(),
+        )
+        .expect("should apply a deposit");
+        acc.apply(
+            &Transaction::new(transaction::Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+        )
+        .expect("should apply a dispute");
+
+        assert_eq!(dec!(10), clone.available.amount());
+        assert_eq!(dec!(0), clone.held.amount());
+        assert_eq!(1, clone.transaction_count());
+        assert_eq!(0, clone.dispute_count());
+        assert_ne!(acc.snapshot(), clone.snapshot());
+    }
+
+    #[test]
+    // The memo of a deposit should still be known after it was disputed, along
+    // with the memo of the dispute, and survive a snapshot.
+    fn test_tx_memo() {
+        use rust_decimal_macros::dec;
+
+        let mut acc = Account::new();
+        for transaction in [
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .memo("salary")
+                .deposit(dec!(10))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(2))
+                .deposit(dec!(5))
+                .unwrap(),
+            Transaction::builder(ClientId(1), TransactionId(1))
+                .memo("customer called")
+                .dispute()
+                .unwrap(),
+        ] {
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+        }
+
+        assert_eq!(Some("salary"), acc.get_tx_memo(TransactionId(1)));
+        assert_eq!(None, acc.get_tx_memo(TransactionId(2)));
+        assert_eq!(None, acc.get_tx_memo(TransactionId(3)));
+        assert_eq!(
+            Some("customer called"),
+            acc.get_tx_dispute_memo(TransactionId(1))
+        );
+        assert_eq!(None, acc.get_tx_dispute_memo(TransactionId(2)));
+
+        // A resolve without a memo keeps the one of the dispute.
+        acc.apply(
+            &Transaction::builder(ClientId(1), TransactionId(1))
+                .resolve()
+                .unwrap(),
+        )
+        .expect("should apply a resolve");
+        assert_eq!(
+            Some("customer called"),
+            acc.get_tx_dispute_memo(TransactionId(1))
+        );
+
+        let restored = Account::from_snapshot(acc.snapshot()).unwrap();
+        assert_eq!(Some("salary"), restored.get_tx_memo(TransactionId(1)));
+        assert_eq!(
+            Some("customer called"),
+            restored.get_tx_dispute_memo(TransactionId(1))
+        );
+    }
+
+    #[test]
+    // The timestamp of a deposit should be kept through dispute/resolve
+    // cycles, and when the account is saved.
+    fn test_tx_timestamp() {
+        use chrono::{DateTime, Utc};
+        use rust_decimal_macros::dec;
+
+        let timestamp: DateTime<Utc> = "2024-01-31T12:00:00Z".parse().unwrap();
+        let mut acc = Account::new();
+        acc.apply(
+            &Transaction::new(
+                transaction::Type::Deposit(dec!(10)),
+                ClientId(1),
+                TransactionId(1),
+            )
+            .unwrap()
+            .with_timestamp(Some(timestamp)),
+        )
+        .expect("should apply a deposit");
+
+        for _ in 0..2 {
+            for tx_type in [transaction::Type::Dispute, transaction::Type::Resolve] {
+                let later = "2024-02-01T12:00:00Z".parse().unwrap();
+                let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(1))
+                    .unwrap()
+                    .with_timestamp(Some(later));
+                acc.apply(&transaction)
+                    .expect("should apply the transaction");
+            }
+        }
+
+        assert_eq!(Some(timestamp), acc.get_tx_timestamp(TransactionId(1)));
+        assert_eq!(None, acc.get_tx_memo(TransactionId(1)));
+        assert_eq!(None, acc.get_tx_timestamp(TransactionId(2)));
+
+        let saved = bincode::serialize(&acc).unwrap();
+        let restored: Account = bincode::deserialize(&saved).unwrap();
+        assert_eq!(Some(timestamp), restored.get_tx_timestamp(TransactionId(1)));
+    }
+
+    #[test]
+    fn test_monotonic_tx_id() {
+        use rust_decimal_macros::dec;
+
+        let config = LedgerConfig {
+            enforce_monotonic_tx_id: true,
+            ..LedgerConfig::default()
+        };
+        let mut acc = Account::new();
+        for (tx_type, tx_id) in [
+            (transaction::Type::Deposit(dec!(10)), 1),
+            (transaction::Type::Deposit(dec!(5)), 3),
+            (transaction::Type::Withdrawal(dec!(2)), 7),
+            // Disputes reference an earlier transaction, so they aren't checked.
+            (transaction::Type::Dispute, 3),
+            (transaction::Type::Resolve, 3),
+        ] {
+            let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(tx_id)).unwrap();
+            acc.apply_with_config(&transaction, &config)
+                .expect("should apply the transaction");
+        }
+
+        for tx_id in [7, 5] {
+            let deposit = Transaction::new(
+                transaction::Type::Deposit(dec!(1)),
+                ClientId(1),
+                TransactionId(tx_id),
+            )
+            .unwrap();
+            assert_eq!(
+                Err(TransactionError::OutOfOrderTransaction {
+                    tx_id: TransactionId(tx_id),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(1)),
+                }),
+                acc.apply_with_config(&deposit, &config)
+            );
+        }
+        assert_eq!(dec!(13), acc.available.amount());
+
+        // The check is disabled by default.
+        let deposit = Transaction::new(
+            transaction::Type::Deposit(dec!(1)),
+            ClientId(1),
+            TransactionId(5),
+        )
+        .unwrap();
+        acc.apply(&deposit).expect("should apply the deposit");
+        assert_eq!(dec!(14), acc.available.amount());
+    }
+
+    #[test]
+    fn test_transaction_error_display() {
+        use rust_decimal_macros::dec;
+
+        for err in [
+            TransactionError::FrozenAccount {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(3)),
+            },
+            TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(3)),
+            },
+            TransactionError::DuplicateTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(3)),
+            },
+            TransactionError::UnknownTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: None,
+            },
+            TransactionError::InvalidTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(3)),
+            },
+            TransactionError::ClientMismatch {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: None,
+            },
+            TransactionError::Overflow,
+            TransactionError::NegativeAmount,
+            TransactionError::ZeroAmount,
+            TransactionError::OutOfOrderTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(3)),
+            },
+            TransactionError::AmountTooLarge {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(3)),
+            },
+        ] {
+            let msg = format!("{err}");
+            assert!(!msg.is_empty());
+            assert!(!msg.starts_with("TransactionError::"), "{msg}");
+        }
+
+        let boxed: Box<dyn std::error::Error> = Box::new(TransactionError::FrozenAccount {
+            tx_id: TransactionId(1),
+            client_id: ClientId(2),
+            amount: Some(dec!(3.5)),
+        });
+        assert_eq!(
+            "account is frozen and cannot accept transactions (client: 2, tx: 1, amount: 3.5)",
+            boxed.to_string()
+        );
+
+        let err = TransactionError::UnknownTransaction {
+            tx_id: TransactionId(1),
+            client_id: ClientId(2),
+            amount: None,
+        };
+        assert_eq!(
+            "the referenced transaction does not exist (client: 2, tx: 1)",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_transaction_state_display() {
+        use crate::ledger::account::account::TransactionState;
+
+        for (state, want) in [
+            (TransactionState::Withdrawn, "withdrawn"),
+            (TransactionState::Deposited, "deposited"),
+            (TransactionState::Disputed, "disputed"),
+            (TransactionState::ChargedBack, "charged_back"),
+        ] {
+            assert_eq!(want, state.as_str());
+            assert_eq!(want, format!("{state}"));
+            // The same labels are used everywhere.
+            assert_eq!(
+                format!("\"{want}\""),
+                serde_json::to_string(&state).unwrap()
+            );
+            assert_eq!(Ok(state), want.parse());
+        }
+
+        // The label used before, still accepted when reading.
+        assert_eq!(
+            TransactionState::ChargedBack,
+            serde_json::from_str::<TransactionState>("\"chargedback\"").unwrap()
+        );
+        assert_eq!(Ok(TransactionState::ChargedBack), "chargedback".parse());
+    }
+
     #[test]
     fn test_total_amount() {
         use rust_decimal_macros::dec;
@@ -217,6 +1565,10 @@ mod tests {
             available: Balance::new(dec!(3.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(2.0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
         assert_eq!(dec!(2.0), acc.total_amount());
     }
@@ -231,6 +1583,10 @@ mod tests {
             available: Balance::new(dec!(3.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(2.0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
         assert_eq!(dec!(3.0), acc.available.amount());
     }
@@ -245,6 +1601,10 @@ mod tests {
             available: Balance::new(dec!(3.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(2.0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
         assert_eq!(dec!(-1.0), acc.held.amount());
     }
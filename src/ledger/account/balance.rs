@@ -1,6 +1,7 @@
 use crate::ledger::Amount;
 
 use super::account::TransactionError;
+use serde::{Deserialize, Serialize};
 
 /// A balance is a sum of credits (adds money to the balance)
 /// and debits (remove money from the balance).
@@ -10,17 +11,55 @@ use super::account::TransactionError;
 /// In a more production-ready implementation, we'd have a
 /// collection of debits and collection of credits instead, i.e.
 /// an immutable collection of everything that happened.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Balance {
+    #[serde(with = "rust_decimal::serde::str")]
     credit: Amount,
+    #[serde(with = "rust_decimal::serde::str")]
     debit: Amount,
 }
 
 impl Balance {
+    #[must_use]
     pub fn amount(&self) -> Amount {
         self.credit - self.debit
     }
 
+    /// Sum of all the credits. The credits and debits can only be changed
+    /// through `add()` and `subtract()`.
+    #[must_use]
+    pub const fn credit(&self) -> Amount {
+        self.credit
+    }
+
+    /// Sum of all the debits.
+    #[must_use]
+    pub const fn debit(&self) -> Amount {
+        self.debit
+    }
+
+    /// Whether the balance covers the given amount, i.e. it would not become
+    /// negative if the amount was subtracted.
+    #[must_use]
+    pub fn is_sufficient_for(&self, amount: Amount) -> bool {
+        self.amount() >= amount
+    }
+
+    #[must_use]
+    pub fn is_zero(&self) -> bool {
+        self.amount().is_zero()
+    }
+
+    #[must_use]
+    pub fn is_negative(&self) -> bool {
+        self.amount().is_sign_negative() && !self.is_zero()
+    }
+
+    /// Add a credit to the balance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TransactionError::Overflow` if the sum of the credits overflows.
     pub fn add(&mut self, amount: Amount) -> Result<(), TransactionError> {
         self.credit = self
             .credit
@@ -30,6 +69,11 @@ impl Balance {
         Ok(())
     }
 
+    /// Add a debit to the balance.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TransactionError::Overflow` if the sum of the debits overflows.
     pub fn subtract(&mut self, amount: Amount) -> Result<(), TransactionError> {
         self.debit = self
             .debit
@@ -39,6 +83,7 @@ impl Balance {
         Ok(())
     }
 
+    #[must_use]
     pub const fn new(credit: Amount, debit: Amount) -> Self {
         Self { credit, debit }
     }
@@ -57,7 +102,7 @@ mod tests {
     fn test_balance_amount() {
         use rust_decimal_macros::dec;
 
-        for (credit, debit, want) in vec![
+        for (credit, debit, want) in [
             (dec!(10), dec!(0), dec!(10)),
             (dec!(0), dec!(10), dec!(-10)),
             (dec!(10), dec!(10), dec!(0)),
@@ -67,6 +112,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_credit_debit() {
+        let mut balance = Balance::new(dec!(10), dec!(3));
+        assert_eq!(dec!(10), balance.credit());
+        assert_eq!(dec!(3), balance.debit());
+
+        balance.add(dec!(2)).expect("should not overflow");
+        balance.subtract(dec!(1)).expect("should not overflow");
+        assert_eq!(dec!(12), balance.credit());
+        assert_eq!(dec!(4), balance.debit());
+    }
+
+    #[test]
+    fn test_is_sufficient_for() {
+        for (balance, amount, want) in [
+            (Balance::new(dec!(10), dec!(0)), dec!(5), true),
+            (Balance::new(dec!(10), dec!(0)), dec!(10), true),
+            (Balance::new(dec!(10), dec!(0)), dec!(10.0001), false),
+            (Balance::new(dec!(0), dec!(0)), dec!(0), true),
+            (Balance::new(dec!(0), dec!(0)), dec!(1), false),
+            (Balance::new(dec!(5), dec!(10)), dec!(1), false),
+            (Balance::new(dec!(5), dec!(10)), dec!(0), false),
+        ] {
+            assert_eq!(want, balance.is_sufficient_for(amount));
+        }
+    }
+
+    #[test]
+    fn test_is_zero_is_negative() {
+        for (credit, debit, want_zero, want_negative) in [
+            (dec!(0), dec!(0), true, false),
+            (dec!(10), dec!(10), true, false),
+            (dec!(10), dec!(3), false, false),
+            (dec!(3), dec!(10), false, true),
+            (dec!(0), dec!(0.0001), false, true),
+        ] {
+            let balance = Balance::new(credit, debit);
+            assert_eq!(want_zero, balance.is_zero());
+            assert_eq!(want_negative, balance.is_negative());
+        }
+    }
+
     #[test]
     fn test_add() {
         let mut balance = Balance::new(dec!(10), dec!(0));
@@ -1,31 +1,57 @@
-use crate::ledger::TransactionId;
+use crate::ledger::{Amount, ClientId, TransactionId};
 
 use super::account::{Account, TransactionError, TransactionState};
 
 impl Account {
+    // `partial_amount` is the part of the disputed amount to charge back, or
+    // `None` to charge back all of it.
+    #[tracing::instrument(level = "debug", skip(self))]
     pub(super) fn apply_chargeback(
         &mut self,
+        client_id: ClientId,
         tx_id: TransactionId,
+        partial_amount: Option<Amount>,
     ) -> Result<(), TransactionError> {
-        let (tx_state, amount) = self.get_tx_state(tx_id)?;
-
-        match tx_state {
-            TransactionState::Disputed => {
-                self.held.subtract(amount)?;
-                self.tx_states
-                    .insert(tx_id, (TransactionState::ChargedBack, amount));
-
-                self.frozen = true;
-                Ok(())
-            }
-            _ => Err(TransactionError::InvalidTransaction),
+        let (tx_state, amount) = self.get_referenced_tx_state(client_id, tx_id)?;
+
+        if *tx_state != TransactionState::Disputed {
+            return Err(TransactionError::InvalidTransaction {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
+        }
+
+        let chargedback = partial_amount.unwrap_or(amount);
+        // We can't charge back more than what is disputed.
+        if chargedback > amount {
+            return Err(TransactionError::InvalidTransaction {
+                tx_id,
+                client_id,
+                amount: Some(chargedback),
+            });
+        }
+
+        self.held.subtract(chargedback)?;
+        *self.tx_chargebacks.entry(tx_id).or_default() += chargedback;
+
+        // Charging back everything that is left is a full chargeback. The
+        // original amount of the deposit is kept either way.
+        if chargedback == amount {
+            self.set_tx_state(tx_id, TransactionState::ChargedBack);
+            self.frozen = true;
         }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod chargeback_tests {
-    use crate::ledger::account::{account::TransactionError, balance::Balance};
+    use crate::ledger::{
+        account::{account::TransactionError, balance::Balance},
+        ClientId, TransactionId,
+    };
 
     use super::{Account, TransactionState};
     use rust_decimal_macros::dec;
@@ -37,14 +63,173 @@ mod chargeback_tests {
             frozen: false,
             available: Balance::new(dec!(10.0), dec!(0)),
             held: Balance::new(dec!(10.0), dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Disputed, dec!(8.0)))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, dec!(8.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_chargeback(1);
+        let got = acc.apply_chargeback(ClientId(1), TransactionId(1), None);
         assert_eq!(Ok(()), got);
         assert_eq!(dec!(10.0), acc.available.amount());
         assert_eq!(dec!(2.0), acc.held.amount());
-        assert_eq!(true, acc.frozen);
+        assert!(acc.frozen);
+    }
+
+    #[test]
+    // Only a full chargeback freezes the account: after a partial one, the
+    // rest of the amount stays disputed.
+    fn test_partial_chargeback() {
+        // The state keeps the original amount of the deposit.
+        for (partial_amount, want_held, want_state, want_frozen) in [
+            (
+                Some(dec!(3.0)),
+                dec!(7.0),
+                (TransactionState::Disputed, dec!(8.0)),
+                false,
+            ),
+            (
+                Some(dec!(8.0)),
+                dec!(2.0),
+                (TransactionState::ChargedBack, dec!(8.0)),
+                true,
+            ),
+            (
+                None,
+                dec!(2.0),
+                (TransactionState::ChargedBack, dec!(8.0)),
+                true,
+            ),
+        ] {
+            let mut acc = Account {
+                frozen: false,
+                available: Balance::new(dec!(10.0), dec!(0)),
+                held: Balance::new(dec!(10.0), dec!(0)),
+                tx_states: HashMap::from([(
+                    TransactionId(1),
+                    (TransactionState::Disputed, dec!(8.0), ClientId(1)),
+                )]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            };
+
+            let got = acc.apply_chargeback(ClientId(1), TransactionId(1), partial_amount);
+            assert_eq!(Ok(()), got);
+            assert_eq!(dec!(10.0), acc.available.amount());
+            assert_eq!(want_held, acc.held.amount());
+            assert_eq!(
+                Some(&(want_state.0, want_state.1, ClientId(1))),
+                acc.tx_states.get(&TransactionId(1))
+            );
+            assert_eq!(want_frozen, acc.frozen);
+            assert_eq!(dec!(10.0) - want_held, acc.chargedback_amount());
+            assert_eq!(
+                Some(dec!(10.0) - want_held),
+                acc.get_tx_chargedback_amount(TransactionId(1))
+            );
+        }
+    }
+
+    #[test]
+    fn test_partial_chargeback_too_large() {
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(dec!(10.0), dec!(0)),
+            held: Balance::new(dec!(10.0), dec!(0)),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, dec!(8.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let got = acc.apply_chargeback(ClientId(1), TransactionId(1), Some(dec!(8.5)));
+        assert_eq!(
+            Err(TransactionError::InvalidTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: Some(dec!(8.5)),
+            }),
+            got
+        );
+        assert_eq!(dec!(10.0), acc.held.amount());
+        assert!(!acc.frozen);
+    }
+
+    #[test]
+    // Successive partial chargebacks, until nothing is left.
+    fn test_partial_chargebacks() {
+        use crate::ledger::transaction::{Transaction, Type};
+
+        let mut acc = Account::new();
+        for tx_type in [
+            Type::Deposit(dec!(10)),
+            Type::Dispute,
+            Type::PartialChargeback(dec!(4)),
+            Type::PartialChargeback(dec!(4)),
+        ] {
+            let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(1)).unwrap();
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+            assert!(!acc.frozen);
+        }
+        assert_eq!(dec!(0), acc.available.amount());
+        assert_eq!(dec!(2), acc.held.amount());
+        assert_eq!(dec!(2), acc.disputed_amount());
+        assert_eq!(dec!(8), acc.chargedback_amount());
+        assert_eq!(Some(dec!(10)), acc.get_tx_amount(TransactionId(1)));
+
+        let chargeback = Transaction::new(
+            Type::PartialChargeback(dec!(2)),
+            ClientId(1),
+            TransactionId(1),
+        )
+        .unwrap();
+        acc.apply(&chargeback).expect("should apply a chargeback");
+        assert!(acc.frozen);
+        assert_eq!(dec!(0), acc.total_amount());
+
+        // The whole deposit was charged back, not only the last part.
+        assert_eq!(dec!(0), acc.disputed_amount());
+        assert_eq!(dec!(10), acc.chargedback_amount());
+        assert_eq!(Some(dec!(10)), acc.get_tx_amount(TransactionId(1)));
+        assert_eq!(
+            Some(dec!(10)),
+            acc.get_tx_chargedback_amount(TransactionId(1))
+        );
+    }
+
+    #[test]
+    // After a partial chargeback, resolving the dispute only releases what is
+    // left, and disputing the deposit again only holds what is left.
+    fn test_partial_chargeback_then_resolve() {
+        use crate::ledger::transaction::{Transaction, Type};
+
+        let mut acc = Account::new();
+        for (tx_type, want_available, want_held) in [
+            (Type::Deposit(dec!(10)), dec!(10), dec!(0)),
+            (Type::Dispute, dec!(0), dec!(10)),
+            (Type::PartialChargeback(dec!(4)), dec!(0), dec!(6)),
+            (Type::Resolve, dec!(6), dec!(0)),
+            (Type::Dispute, dec!(0), dec!(6)),
+        ] {
+            let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(1)).unwrap();
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+            assert_eq!(want_available, acc.available.amount());
+            assert_eq!(want_held, acc.held.amount());
+        }
+        assert_eq!(dec!(6), acc.disputed_amount());
+        assert_eq!(dec!(4), acc.chargedback_amount());
     }
 
     #[test]
@@ -54,18 +239,29 @@ mod chargeback_tests {
             available: Balance::new(dec!(10.0), dec!(0)),
             held: Balance::new(dec!(10.0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_chargeback(1);
-        assert_eq!(Err(TransactionError::UnknownTransaction), got);
+        let got = acc.apply_chargeback(ClientId(1), TransactionId(1), None);
+        assert_eq!(
+            Err(TransactionError::UnknownTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: None,
+            }),
+            got
+        );
         assert_eq!(dec!(10.0), acc.available.amount());
         assert_eq!(dec!(10.0), acc.held.amount());
-        assert_eq!(false, acc.frozen);
+        assert!(!acc.frozen);
     }
 
     #[test]
     fn test_chargeback_invalid_state() {
-        for state in vec![
+        for state in [
             TransactionState::Withdrawn,
             TransactionState::Deposited,
             TransactionState::ChargedBack,
@@ -74,14 +270,55 @@ mod chargeback_tests {
                 frozen: false,
                 available: Balance::new(dec!(0), dec!(0)),
                 held: Balance::new(dec!(88.88), dec!(0)),
-                tx_states: HashMap::from([(1, (state, dec!(10.0)))]),
+                tx_states: HashMap::from([(TransactionId(1), (state, dec!(10.0), ClientId(1)))]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
             };
 
-            let got = acc.apply_chargeback(1);
-            assert_eq!(Err(TransactionError::InvalidTransaction), got);
+            let got = acc.apply_chargeback(ClientId(1), TransactionId(1), None);
+            assert_eq!(
+                Err(TransactionError::InvalidTransaction {
+                    tx_id: TransactionId(1),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(10.0)),
+                }),
+                got
+            );
             assert_eq!(dec!(0), acc.available.amount());
             assert_eq!(dec!(88.88), acc.held.amount());
-            assert_eq!(false, acc.frozen);
+            assert!(!acc.frozen);
         }
     }
+
+    #[test]
+    // A client can't charge back a transaction created by another client.
+    fn test_chargeback_client_mismatch() {
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(dec!(8.0), dec!(0)),
+            held: Balance::new(dec!(5.0), dec!(0)),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let got = acc.apply_chargeback(ClientId(2), TransactionId(1), None);
+        assert_eq!(
+            Err(TransactionError::ClientMismatch {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: None,
+            }),
+            got
+        );
+        assert_eq!(dec!(8.0), acc.available.amount());
+        assert_eq!(dec!(5.0), acc.held.amount());
+    }
 }
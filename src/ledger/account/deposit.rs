@@ -1,23 +1,62 @@
-use crate::ledger::{Amount, TransactionId};
+use crate::ledger::{Amount, ClientId, LedgerConfig, TransactionId};
 
 use super::account::{Account, TransactionError, TransactionState};
+use rust_decimal_macros::dec;
 
 impl Account {
+    #[tracing::instrument(level = "debug", skip(self, config))]
     pub(super) fn apply_deposit(
         &mut self,
+        client_id: ClientId,
         tx_id: TransactionId,
         amount: Amount,
+        config: &LedgerConfig,
     ) -> Result<(), TransactionError> {
+        if amount.is_zero() {
+            return Err(TransactionError::ZeroAmount);
+        }
+
+        // A negative deposit would actually remove money from the account.
+        if amount < dec!(0) {
+            return Err(TransactionError::NegativeAmount);
+        }
+
+        if config.max_deposit_amount.is_some_and(|max| amount > max) {
+            return Err(TransactionError::AmountTooLarge {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
+        }
+
         // We've already seen that transaction, so we probably have a data issue.
         // We can safely return an error.
         if self.tx_states.contains_key(&tx_id) {
-            return Err(TransactionError::DuplicateTransaction);
+            return Err(TransactionError::DuplicateTransaction {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
         }
 
+        // The balance is restored as it was if the deposit is rejected, rather
+        // than cancelled with a debit.
+        let saved_available_balance = self.available.clone();
         self.available.add(amount)?;
+        if config
+            .max_account_balance
+            .is_some_and(|max| self.total_amount() > max)
+        {
+            self.available = saved_available_balance;
+            return Err(TransactionError::AmountTooLarge {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
+        }
 
         self.tx_states
-            .insert(tx_id, (TransactionState::Deposited, amount));
+            .insert(tx_id, (TransactionState::Deposited, amount, client_id));
 
         Ok(())
     }
@@ -25,11 +64,15 @@ impl Account {
 
 #[cfg(test)]
 mod deposit_tests {
-    use crate::ledger::account::{account::TransactionError, balance::Balance};
+    use crate::ledger::{
+        account::{account::TransactionError, balance::Balance},
+        ClientId, LedgerConfig, TransactionId,
+    };
 
     use super::{Account, TransactionState};
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, str::FromStr};
 
     #[test]
     fn test_deposit_ok() {
@@ -38,17 +81,168 @@ mod deposit_tests {
             available: Balance::new(dec!(3.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_deposit(1, dec!(3.0));
+        let got = acc.apply_deposit(
+            ClientId(1),
+            TransactionId(1),
+            dec!(3.0),
+            &LedgerConfig::default(),
+        );
         assert_eq!(Ok(()), got);
         assert_eq!(dec!(6.0), acc.available.amount());
         assert_eq!(dec!(1.0), acc.held.amount());
     }
 
+    #[test]
+    fn test_deposit_non_positive_amount() {
+        for (amount, want_err) in [
+            (dec!(-0.0001), TransactionError::NegativeAmount),
+            (dec!(-1000), TransactionError::NegativeAmount),
+            (dec!(0), TransactionError::ZeroAmount),
+            (dec!(0.0000), TransactionError::ZeroAmount),
+        ] {
+            let mut acc = Account {
+                frozen: false,
+                available: Balance::new(dec!(3.0), dec!(0)),
+                held: Balance::new(dec!(1.0), dec!(0)),
+                tx_states: HashMap::new(),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            };
+
+            let got = acc.apply_deposit(
+                ClientId(1),
+                TransactionId(1),
+                amount,
+                &LedgerConfig::default(),
+            );
+            assert_eq!(Err(want_err), got);
+            assert_eq!(dec!(3.0), acc.available.amount());
+            assert_eq!(dec!(1.0), acc.held.amount());
+            assert!(acc.tx_states.is_empty());
+        }
+    }
+
+    #[test]
+    // The cap is the largest amount allowed.
+    fn test_deposit_max_amount() {
+        let config = LedgerConfig {
+            max_deposit_amount: Some(dec!(10000)),
+            ..LedgerConfig::default()
+        };
+        for (amount, want_ok) in [
+            (dec!(9999.9999), true),
+            (dec!(10000), true),
+            (dec!(10000.0001), false),
+        ] {
+            let mut acc = Account::new();
+            let got = acc.apply_deposit(ClientId(1), TransactionId(1), amount, &config);
+            if want_ok {
+                assert_eq!(Ok(()), got);
+                assert_eq!(amount, acc.available.amount());
+            } else {
+                assert_eq!(
+                    Err(TransactionError::AmountTooLarge {
+                        tx_id: TransactionId(1),
+                        client_id: ClientId(1),
+                        amount: Some(amount),
+                    }),
+                    got
+                );
+                assert!(acc.available.is_zero());
+                assert_eq!(0, acc.transaction_count());
+            }
+        }
+    }
+
+    #[test]
+    // Deposits are accepted until the next one would take the balance over the
+    // cap. Held funds count towards the balance as well.
+    fn test_deposit_max_account_balance() {
+        let config = LedgerConfig {
+            max_account_balance: Some(dec!(100)),
+            ..LedgerConfig::default()
+        };
+        let mut acc = Account::new();
+        acc.held = Balance::new(dec!(10), dec!(0));
+        for (tx_id, amount, want_ok, want_available) in [
+            (1, dec!(50), true, dec!(50)),
+            (2, dec!(30), true, dec!(80)),
+            (3, dec!(10.0001), false, dec!(80)),
+            (4, dec!(10), true, dec!(90)),
+            (5, dec!(0.0001), false, dec!(90)),
+        ] {
+            let got = acc.apply_deposit(ClientId(1), TransactionId(tx_id), amount, &config);
+            if want_ok {
+                assert_eq!(Ok(()), got);
+            } else {
+                assert_eq!(
+                    Err(TransactionError::AmountTooLarge {
+                        tx_id: TransactionId(tx_id),
+                        client_id: ClientId(1),
+                        amount: Some(amount),
+                    }),
+                    got
+                );
+            }
+            assert_eq!(want_available, acc.available.amount());
+        }
+        assert_eq!(dec!(100), acc.total_amount());
+        assert_eq!(3, acc.transaction_count());
+    }
+
+    #[test]
+    // A rejected deposit shouldn't leave any trace in the balance, e.g. a
+    // credit cancelled by a debit.
+    fn test_deposit_max_account_balance_unchanged() {
+        let config = LedgerConfig {
+            max_account_balance: Some(dec!(100)),
+            ..LedgerConfig::default()
+        };
+        let mut acc = Account::new();
+        acc.apply_deposit(ClientId(1), TransactionId(1), dec!(90), &config)
+            .unwrap();
+
+        let got = acc.apply_deposit(ClientId(1), TransactionId(2), dec!(20), &config);
+        assert!(got.is_err());
+        assert_eq!(dec!(90), acc.available.credit());
+        assert_eq!(dec!(0), acc.available.debit());
+    }
+
+    #[test]
+    fn test_deposit_very_large_amount() {
+        let very_big_number = Decimal::from_str("70000000000000000000000000000").unwrap();
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(dec!(0), dec!(0)),
+            held: Balance::new(dec!(0), dec!(0)),
+            tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let got = acc.apply_deposit(
+            ClientId(1),
+            TransactionId(1),
+            very_big_number,
+            &LedgerConfig::default(),
+        );
+        assert_eq!(Ok(()), got);
+        assert_eq!(very_big_number, acc.available.amount());
+    }
+
     #[test]
     fn test_deposit_already_exists() {
-        for state in vec![
+        for state in [
             TransactionState::Withdrawn,
             TransactionState::Deposited,
             TransactionState::Disputed,
@@ -58,11 +252,27 @@ mod deposit_tests {
                 frozen: false,
                 available: Balance::new(dec!(99.99), dec!(0)),
                 held: Balance::new(dec!(88.88), dec!(0)),
-                tx_states: HashMap::from([(1, (state, dec!(123.456)))]),
+                tx_states: HashMap::from([(TransactionId(1), (state, dec!(123.456), ClientId(1)))]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
             };
 
-            let got = acc.apply_deposit(1, dec!(3.0));
-            assert_eq!(Err(TransactionError::DuplicateTransaction), got);
+            let got = acc.apply_deposit(
+                ClientId(1),
+                TransactionId(1),
+                dec!(3.0),
+                &LedgerConfig::default(),
+            );
+            assert_eq!(
+                Err(TransactionError::DuplicateTransaction {
+                    tx_id: TransactionId(1),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(3.0)),
+                }),
+                got
+            );
             assert_eq!(dec!(99.99), acc.available.amount());
             assert_eq!(dec!(88.88), acc.held.amount());
         }
@@ -1,12 +1,26 @@
-use crate::ledger::TransactionId;
+use crate::ledger::{ClientId, LedgerConfig, TransactionId};
 
 use super::account::{Account, TransactionError, TransactionState};
 
 impl Account {
-    pub(super) fn apply_dispute(&mut self, tx_id: TransactionId) -> Result<(), TransactionError> {
-        let (tx_state, amount) = self.get_tx_state(tx_id)?;
+    #[tracing::instrument(level = "debug", skip(self, config))]
+    pub(super) fn apply_dispute(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        config: &LedgerConfig,
+    ) -> Result<(), TransactionError> {
+        let (tx_state, amount) = self.get_referenced_tx_state(client_id, tx_id)?;
         match tx_state {
             TransactionState::Deposited => {
+                if !config.allow_negative_available && !self.available.is_sufficient_for(amount) {
+                    return Err(TransactionError::NotEnoughFunds {
+                        tx_id,
+                        client_id,
+                        amount: Some(amount),
+                    });
+                }
+
                 // We're doing two balance operations: add to held, subtract from
                 // available. If one of them fails, we should roll back both of them.
                 // That's why we're making a clone of the held balance first,
@@ -16,14 +30,17 @@ impl Account {
                 if let Err(e) = self.available.subtract(amount) {
                     self.held = saved_held_balance;
                     return Err(e);
-                };
+                }
 
-                self.tx_states
-                    .insert(tx_id, (TransactionState::Disputed, amount));
+                self.set_tx_state(tx_id, TransactionState::Disputed);
 
                 Ok(())
             }
-            _ => Err(TransactionError::InvalidTransaction),
+            _ => Err(TransactionError::InvalidTransaction {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            }),
         }
     }
 }
@@ -38,6 +55,7 @@ mod dispute_tests {
         account::{Account, TransactionError, TransactionState},
         balance::Balance,
     };
+    use crate::ledger::{ClientId, LedgerConfig, TransactionId};
 
     #[test]
     fn test_dispute_ok() {
@@ -45,10 +63,17 @@ mod dispute_tests {
             frozen: false,
             available: Balance::new(dec!(8.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Deposited, dec!(5.0)))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Deposited, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_dispute(1);
+        let got = acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default());
         assert_eq!(Ok(()), got);
         assert_eq!(dec!(3.0), acc.available.amount());
         assert_eq!(dec!(6.0), acc.held.amount());
@@ -60,15 +85,66 @@ mod dispute_tests {
             frozen: false,
             available: Balance::new(dec!(0.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Deposited, dec!(5.0)))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Deposited, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_dispute(1);
+        let got = acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default());
         assert_eq!(Ok(()), got);
         assert_eq!(dec!(-5.0), acc.available.amount());
         assert_eq!(dec!(6.0), acc.held.amount());
     }
 
+    #[test]
+    // When negative available funds aren't allowed, a dispute on funds that
+    // were already withdrawn is rejected.
+    fn test_dispute_overdraft_not_allowed() {
+        let config = LedgerConfig {
+            allow_negative_available: false,
+            ..LedgerConfig::default()
+        };
+        for (available, want_ok) in [(dec!(0), false), (dec!(4.9999), false), (dec!(5), true)] {
+            let mut acc = Account {
+                frozen: false,
+                available: Balance::new(available, dec!(0)),
+                held: Balance::new(dec!(1.0), dec!(0)),
+                tx_states: HashMap::from([(
+                    TransactionId(1),
+                    (TransactionState::Deposited, dec!(5.0), ClientId(1)),
+                )]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            };
+
+            let got = acc.apply_dispute(ClientId(1), TransactionId(1), &config);
+            if want_ok {
+                assert_eq!(Ok(()), got);
+                assert_eq!(dec!(0), acc.available.amount());
+                assert_eq!(dec!(6.0), acc.held.amount());
+            } else {
+                assert_eq!(
+                    Err(TransactionError::NotEnoughFunds {
+                        tx_id: TransactionId(1),
+                        client_id: ClientId(1),
+                        amount: Some(dec!(5.0)),
+                    }),
+                    got
+                );
+                assert_eq!(available, acc.available.amount());
+                assert_eq!(dec!(1.0), acc.held.amount());
+                assert!(acc.can_dispute(TransactionId(1)));
+            }
+        }
+    }
+
     #[test]
     fn test_resolve_nok_overflow_held() {
         let very_big_number = Decimal::from_str("70000000000000000000000000000").unwrap();
@@ -77,10 +153,17 @@ mod dispute_tests {
             frozen: false,
             available: Balance::new(dec!(0), dec!(0)),
             held: Balance::new(very_big_number, dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Deposited, very_big_number))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Deposited, very_big_number, ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_dispute(1);
+        let got = acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default());
         assert_eq!(Err(TransactionError::Overflow), got);
         assert_eq!(dec!(0), acc.available.amount());
         assert_eq!(very_big_number, acc.held.amount());
@@ -94,10 +177,17 @@ mod dispute_tests {
             frozen: false,
             available: Balance::new(dec!(0), very_big_number),
             held: Balance::new(dec!(0), dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Deposited, very_big_number))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Deposited, very_big_number, ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_dispute(1);
+        let got = acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default());
         assert_eq!(Err(TransactionError::Overflow), got);
         assert_eq!(-very_big_number, acc.available.amount());
         assert_eq!(dec!(0), acc.held.amount());
@@ -110,18 +200,29 @@ mod dispute_tests {
             available: Balance::new(dec!(10.0), dec!(0)),
             held: Balance::new(dec!(10.0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_dispute(1);
-        assert_eq!(Err(TransactionError::UnknownTransaction), got);
+        let got = acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default());
+        assert_eq!(
+            Err(TransactionError::UnknownTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: None,
+            }),
+            got
+        );
         assert_eq!(dec!(10.0), acc.available.amount());
         assert_eq!(dec!(10.0), acc.held.amount());
-        assert_eq!(false, acc.frozen);
+        assert!(!acc.frozen);
     }
 
     #[test]
     fn test_dispute_incorrect_state() {
-        for state in vec![
+        for state in [
             TransactionState::Withdrawn,
             TransactionState::Disputed,
             TransactionState::ChargedBack,
@@ -130,13 +231,54 @@ mod dispute_tests {
                 frozen: false,
                 available: Balance::new(dec!(99.99), dec!(0)),
                 held: Balance::new(dec!(88.88), dec!(0)),
-                tx_states: HashMap::from([(1, (state, dec!(123.456)))]),
+                tx_states: HashMap::from([(TransactionId(1), (state, dec!(123.456), ClientId(1)))]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
             };
 
-            let got = acc.apply_dispute(1);
-            assert_eq!(Err(TransactionError::InvalidTransaction), got);
+            let got = acc.apply_dispute(ClientId(1), TransactionId(1), &LedgerConfig::default());
+            assert_eq!(
+                Err(TransactionError::InvalidTransaction {
+                    tx_id: TransactionId(1),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(123.456)),
+                }),
+                got
+            );
             assert_eq!(dec!(99.99), acc.available.amount());
             assert_eq!(dec!(88.88), acc.held.amount());
         }
     }
+
+    #[test]
+    // A client can't dispute a transaction created by another client.
+    fn test_dispute_client_mismatch() {
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(dec!(8.0), dec!(0)),
+            held: Balance::new(dec!(5.0), dec!(0)),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Deposited, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let got = acc.apply_dispute(ClientId(2), TransactionId(1), &LedgerConfig::default());
+        assert_eq!(
+            Err(TransactionError::ClientMismatch {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: None,
+            }),
+            got
+        );
+        assert_eq!(dec!(8.0), acc.available.amount());
+        assert_eq!(dec!(5.0), acc.held.amount());
+    }
 }
@@ -1,7 +1,10 @@
+#[allow(clippy::module_inception)]
 pub mod account;
 pub mod balance;
 mod chargeback;
 mod deposit;
 mod dispute;
 mod resolve;
+pub mod snapshot;
+mod transfer;
 mod withdrawal;
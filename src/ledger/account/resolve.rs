@@ -1,14 +1,23 @@
-use crate::ledger::TransactionId;
+use crate::ledger::{ClientId, TransactionId};
 
 use super::account::{Account, TransactionError, TransactionState};
 
 impl Account {
-    pub(super) fn apply_resolve(&mut self, tx_id: TransactionId) -> Result<(), TransactionError> {
-        let (tx_state, amount) = self.get_tx_state(tx_id)?;
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub(super) fn apply_resolve(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+    ) -> Result<(), TransactionError> {
+        let (tx_state, amount) = self.get_referenced_tx_state(client_id, tx_id)?;
         match tx_state {
             TransactionState::Disputed => {
                 if self.held.amount() < amount {
-                    return Err(TransactionError::NotEnoughFunds);
+                    return Err(TransactionError::NotEnoughFunds {
+                        tx_id,
+                        client_id,
+                        amount: Some(amount),
+                    });
                 }
 
                 // Due to the previous check on the amount, we can't get an
@@ -17,19 +26,25 @@ impl Account {
                 self.available.add(amount)?;
                 self.held.subtract(amount)?;
 
-                self.tx_states
-                    .insert(tx_id, (TransactionState::Deposited, amount));
+                self.set_tx_state(tx_id, TransactionState::Deposited);
 
                 Ok(())
             }
-            _ => Err(TransactionError::InvalidTransaction),
+            _ => Err(TransactionError::InvalidTransaction {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            }),
         }
     }
 }
 
 #[cfg(test)]
 mod resolve_tests {
-    use crate::ledger::account::{account::TransactionError, balance::Balance};
+    use crate::ledger::{
+        account::{account::TransactionError, balance::Balance},
+        ClientId, TransactionId,
+    };
 
     use super::{Account, TransactionState};
     use rust_decimal::Decimal;
@@ -42,10 +57,17 @@ mod resolve_tests {
             frozen: false,
             available: Balance::new(dec!(10.0), dec!(0)),
             held: Balance::new(dec!(10.0), dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Disputed, dec!(5.0)))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_resolve(1);
+        let got = acc.apply_resolve(ClientId(1), TransactionId(1));
         assert_eq!(Ok(()), got);
         assert_eq!(dec!(15.0), acc.available.amount());
         assert_eq!(dec!(5.0), acc.held.amount());
@@ -57,11 +79,25 @@ mod resolve_tests {
             frozen: false,
             available: Balance::new(dec!(10.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Disputed, dec!(5.0)))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_resolve(1);
-        assert_eq!(Err(TransactionError::NotEnoughFunds), got);
+        let got = acc.apply_resolve(ClientId(1), TransactionId(1));
+        assert_eq!(
+            Err(TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: Some(dec!(5.0)),
+            }),
+            got
+        );
         assert_eq!(dec!(10.0), acc.available.amount());
         assert_eq!(dec!(1.0), acc.held.amount());
     }
@@ -74,10 +110,17 @@ mod resolve_tests {
             frozen: false,
             available: Balance::new(very_big_number, dec!(0)),
             held: Balance::new(very_big_number, dec!(0)),
-            tx_states: HashMap::from([(1, (TransactionState::Disputed, very_big_number))]),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, very_big_number, ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_resolve(1);
+        let got = acc.apply_resolve(ClientId(1), TransactionId(1));
         assert_eq!(Err(TransactionError::Overflow), got);
         assert_eq!(very_big_number, acc.available.amount());
         assert_eq!(very_big_number, acc.held.amount());
@@ -90,18 +133,29 @@ mod resolve_tests {
             available: Balance::new(dec!(10.0), dec!(0)),
             held: Balance::new(dec!(10.0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_resolve(1);
-        assert_eq!(Err(TransactionError::UnknownTransaction), got);
+        let got = acc.apply_resolve(ClientId(1), TransactionId(1));
+        assert_eq!(
+            Err(TransactionError::UnknownTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: None,
+            }),
+            got
+        );
         assert_eq!(dec!(10.0), acc.available.amount());
         assert_eq!(dec!(10.0), acc.held.amount());
-        assert_eq!(false, acc.frozen);
+        assert!(!acc.frozen);
     }
 
     #[test]
     fn test_resolve_incorrect_state() {
-        for state in vec![
+        for state in [
             TransactionState::Withdrawn,
             TransactionState::Deposited,
             TransactionState::ChargedBack,
@@ -110,13 +164,54 @@ mod resolve_tests {
                 frozen: false,
                 available: Balance::new(dec!(99.99), dec!(0)),
                 held: Balance::new(dec!(88.88), dec!(0)),
-                tx_states: HashMap::from([(1, (state, dec!(123.456)))]),
+                tx_states: HashMap::from([(TransactionId(1), (state, dec!(123.456), ClientId(1)))]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
             };
 
-            let got = acc.apply_resolve(1);
-            assert_eq!(Err(TransactionError::InvalidTransaction), got);
+            let got = acc.apply_resolve(ClientId(1), TransactionId(1));
+            assert_eq!(
+                Err(TransactionError::InvalidTransaction {
+                    tx_id: TransactionId(1),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(123.456)),
+                }),
+                got
+            );
             assert_eq!(dec!(99.99), acc.available.amount());
             assert_eq!(dec!(88.88), acc.held.amount());
         }
     }
+
+    #[test]
+    // A client can't resolve a transaction created by another client.
+    fn test_resolve_client_mismatch() {
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(dec!(8.0), dec!(0)),
+            held: Balance::new(dec!(5.0), dec!(0)),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                (TransactionState::Disputed, dec!(5.0), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let got = acc.apply_resolve(ClientId(2), TransactionId(1));
+        assert_eq!(
+            Err(TransactionError::ClientMismatch {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: None,
+            }),
+            got
+        );
+        assert_eq!(dec!(8.0), acc.available.amount());
+        assert_eq!(dec!(5.0), acc.held.amount());
+    }
 }
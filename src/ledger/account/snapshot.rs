@@ -0,0 +1,181 @@
+use crate::ledger::{Amount, ClientId, TransactionId};
+
+use super::{
+    account::{Account, TransactionMetadata},
+    balance::Balance,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The full state of an account, that can be persisted and restored later
+/// without having to process all the transactions again.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub frozen: bool,
+    pub available_credit: Amount,
+    pub available_debit: Amount,
+    pub held_credit: Amount,
+    pub held_debit: Amount,
+
+    // The transaction states are stored as their `TransactionState::as_str()`
+    // label (e.g. "deposited"), to keep the snapshot format independent from
+    // the enum.
+    pub tx_states: HashMap<TransactionId, (String, Amount, ClientId)>,
+
+    // Snapshots taken before metadata existed don't have this field.
+    #[serde(default)]
+    pub tx_metadata: HashMap<TransactionId, TransactionMetadata>,
+
+    #[serde(default)]
+    pub last_tx_id: Option<TransactionId>,
+
+    #[serde(default)]
+    pub tx_fees: HashMap<TransactionId, Amount>,
+
+    #[serde(default)]
+    pub tx_chargebacks: HashMap<TransactionId, Amount>,
+}
+
+impl Account {
+    /// Take a snapshot of the current state of the account.
+    #[must_use]
+    pub fn snapshot(&self) -> AccountSnapshot {
+        AccountSnapshot {
+            frozen: self.frozen,
+            available_credit: self.available.credit(),
+            available_debit: self.available.debit(),
+            held_credit: self.held.credit(),
+            held_debit: self.held.debit(),
+            tx_states: self
+                .tx_states
+                .iter()
+                .map(|(tx_id, (tx_state, amount, client_id))| {
+                    (*tx_id, (tx_state.as_str().to_string(), *amount, *client_id))
+                })
+                .collect(),
+            tx_metadata: self.tx_metadata.clone(),
+            last_tx_id: self.last_tx_id,
+            tx_fees: self.tx_fees.clone(),
+            tx_chargebacks: self.tx_chargebacks.clone(),
+        }
+    }
+
+    /// Restore an account from a snapshot taken with `snapshot()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot contains an unknown transaction state.
+    pub fn from_snapshot(snap: AccountSnapshot) -> Result<Self, &'static str> {
+        let tx_states = snap
+            .tx_states
+            .into_iter()
+            .map(|(tx_id, (tx_state, amount, client_id))| {
+                let tx_state = tx_state.parse()?;
+                Ok((tx_id, (tx_state, amount, client_id)))
+            })
+            .collect::<Result<_, &'static str>>()?;
+
+        Ok(Self {
+            frozen: snap.frozen,
+            available: Balance::new(snap.available_credit, snap.available_debit),
+            held: Balance::new(snap.held_credit, snap.held_debit),
+            tx_states,
+            tx_metadata: snap.tx_metadata,
+            last_tx_id: snap.last_tx_id,
+            tx_fees: snap.tx_fees,
+            tx_chargebacks: snap.tx_chargebacks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::{Account, AccountSnapshot};
+    use crate::ledger::{
+        transaction::{Transaction, Type},
+        ClientId, TransactionId,
+    };
+
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut acc = Account::new();
+        for (tx_type, tx_id) in [
+            (Type::Deposit(dec!(10)), 1),
+            (Type::Deposit(dec!(5)), 2),
+            (Type::Withdrawal(dec!(3)), 3),
+            (Type::Dispute, 2),
+        ] {
+            let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(tx_id)).unwrap();
+            acc.apply(&transaction)
+                .expect("should apply the transaction");
+        }
+
+        let snap = acc.snapshot();
+        assert_eq!(
+            AccountSnapshot {
+                frozen: false,
+                available_credit: dec!(15),
+                available_debit: dec!(8),
+                held_credit: dec!(5),
+                held_debit: dec!(0),
+                tx_states: HashMap::from([
+                    (
+                        TransactionId(1),
+                        ("deposited".to_string(), dec!(10), ClientId(1))
+                    ),
+                    (
+                        TransactionId(2),
+                        ("disputed".to_string(), dec!(5), ClientId(1))
+                    ),
+                    (
+                        TransactionId(3),
+                        ("withdrawn".to_string(), dec!(3), ClientId(1))
+                    ),
+                ]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: Some(TransactionId(3)),
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            },
+            snap
+        );
+
+        let json = serde_json::to_string(&snap).unwrap();
+        let mut restored = Account::from_snapshot(serde_json::from_str(&json).unwrap()).unwrap();
+
+        // The restored account should still know about the disputed deposit.
+        let chargeback = Transaction::new(Type::Chargeback, ClientId(1), TransactionId(2)).unwrap();
+        restored
+            .apply(&chargeback)
+            .expect("should apply a chargeback");
+
+        assert!(restored.frozen);
+        assert_eq!(dec!(7), restored.available.amount());
+        assert_eq!(dec!(0), restored.held.amount());
+        assert_eq!(1, restored.chargeback_count());
+    }
+
+    #[test]
+    fn test_from_snapshot_unknown_state() {
+        let snap = AccountSnapshot {
+            frozen: false,
+            available_credit: dec!(0),
+            available_debit: dec!(0),
+            held_credit: dec!(0),
+            held_debit: dec!(0),
+            tx_states: HashMap::from([(
+                TransactionId(1),
+                ("refunded".to_string(), dec!(10), ClientId(1)),
+            )]),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        assert!(Account::from_snapshot(snap).is_err());
+    }
+}
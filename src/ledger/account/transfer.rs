@@ -0,0 +1,224 @@
+use crate::ledger::{Amount, ClientId, LedgerConfig, TransactionId};
+
+use super::{
+    account::{Account, TransactionError},
+    balance::Balance,
+};
+
+/// What the debit side of a transfer changes on the sender's account, saved
+/// before applying it, so it can be rolled back if the credit side fails.
+pub struct TransferCheckpoint {
+    available: Balance,
+    last_tx_id: Option<TransactionId>,
+}
+
+// Each side of a transfer is recorded as a regular withdrawal or deposit, with
+// the same transaction ID on both accounts, and goes through the same checks,
+// e.g. `max_account_balance` for the recipient. Transfers are not withdrawals
+// from the engine, so no withdrawal fee is taken.
+impl Account {
+    /// Debit side of a transfer: the funds leave the sender's account.
+    #[tracing::instrument(level = "debug", skip(self, config))]
+    pub(crate) fn apply_transfer_out(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        amount: Amount,
+        config: &LedgerConfig,
+    ) -> Result<(), TransactionError> {
+        self.check_transfer(client_id, tx_id, amount, config)?;
+        let config = LedgerConfig {
+            withdrawal_fee_bps: 0,
+            ..*config
+        };
+        self.apply_withdrawal(client_id, tx_id, amount, &config)?;
+        self.last_tx_id = self.last_tx_id.max(Some(tx_id));
+        Ok(())
+    }
+
+    /// Credit side of a transfer: the funds arrive on the recipient's account.
+    #[tracing::instrument(level = "debug", skip(self, config))]
+    pub(crate) fn apply_transfer_in(
+        &mut self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        amount: Amount,
+        config: &LedgerConfig,
+    ) -> Result<(), TransactionError> {
+        self.check_transfer(client_id, tx_id, amount, config)?;
+        self.apply_deposit(client_id, tx_id, amount, config)?;
+        self.last_tx_id = self.last_tx_id.max(Some(tx_id));
+        Ok(())
+    }
+
+    /// Save what `apply_transfer_out()` changes, without copying the whole
+    /// transaction history.
+    pub(crate) fn transfer_checkpoint(&self) -> TransferCheckpoint {
+        TransferCheckpoint {
+            available: self.available.clone(),
+            last_tx_id: self.last_tx_id,
+        }
+    }
+
+    /// Undo `apply_transfer_out()`, e.g. when the recipient rejected the funds.
+    pub(crate) fn roll_back_transfer_out(
+        &mut self,
+        tx_id: TransactionId,
+        checkpoint: TransferCheckpoint,
+    ) {
+        self.available = checkpoint.available;
+        self.last_tx_id = checkpoint.last_tx_id;
+        self.tx_states.remove(&tx_id);
+    }
+
+    fn check_transfer(
+        &self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        amount: Amount,
+        config: &LedgerConfig,
+    ) -> Result<(), TransactionError> {
+        self.check_not_frozen(client_id, tx_id, amount)?;
+        self.check_tx_order(client_id, tx_id, Some(amount), config)
+    }
+
+    const fn check_not_frozen(
+        &self,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        amount: Amount,
+    ) -> Result<(), TransactionError> {
+        if self.frozen {
+            return Err(TransactionError::FrozenAccount {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod transfer_tests {
+    use crate::ledger::{
+        account::{account::TransactionError, balance::Balance},
+        ClientId, LedgerConfig, TransactionId,
+    };
+
+    use super::Account;
+    use crate::ledger::transaction::{Transaction, Type};
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_transfer_frozen_account() {
+        let mut acc = Account {
+            frozen: true,
+            available: Balance::new(dec!(10), dec!(0)),
+            held: Balance::new(dec!(0), dec!(0)),
+            tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let want = Err(TransactionError::FrozenAccount {
+            tx_id: TransactionId(1),
+            client_id: ClientId(2),
+            amount: Some(dec!(5)),
+        });
+        assert_eq!(
+            want,
+            acc.apply_transfer_out(
+                ClientId(2),
+                TransactionId(1),
+                dec!(5),
+                &LedgerConfig::default()
+            )
+        );
+        assert_eq!(
+            want,
+            acc.apply_transfer_in(
+                ClientId(2),
+                TransactionId(1),
+                dec!(5),
+                &LedgerConfig::default()
+            )
+        );
+        assert_eq!(dec!(10), acc.available.amount());
+        assert_eq!(0, acc.transaction_count());
+    }
+
+    #[test]
+    fn test_transfer_ok() {
+        let mut sender = Account {
+            frozen: false,
+            available: Balance::new(dec!(10), dec!(0)),
+            held: Balance::new(dec!(0), dec!(0)),
+            tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+        let mut recipient = Account::new();
+
+        sender
+            .apply_transfer_out(
+                ClientId(1),
+                TransactionId(1),
+                dec!(4),
+                &LedgerConfig::default(),
+            )
+            .unwrap();
+        recipient
+            .apply_transfer_in(
+                ClientId(2),
+                TransactionId(1),
+                dec!(4),
+                &LedgerConfig::default(),
+            )
+            .unwrap();
+
+        assert_eq!(dec!(6), sender.available.amount());
+        assert_eq!(dec!(4), recipient.available.amount());
+    }
+
+    #[test]
+    // Account::apply() only has access to one account, so it can't apply a
+    // transfer on its own.
+    fn test_apply_transfer_on_account() {
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(dec!(10), dec!(0)),
+            held: Balance::new(dec!(0), dec!(0)),
+            tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+        let transfer = Transaction::new(
+            Type::Transfer {
+                to_client: ClientId(2),
+                amount: dec!(5),
+            },
+            ClientId(1),
+            TransactionId(1),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(TransactionError::InvalidTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: Some(dec!(5)),
+            }),
+            acc.apply(&transfer)
+        );
+        assert_eq!(dec!(10), acc.available.amount());
+    }
+}
@@ -1,37 +1,73 @@
-use crate::ledger::{Amount, TransactionId};
+use crate::ledger::{Amount, ClientId, LedgerConfig, TransactionId};
 
 use super::account::{Account, TransactionError, TransactionState};
+use rust_decimal_macros::dec;
 
 impl Account {
+    #[tracing::instrument(level = "debug", skip(self, config))]
     pub(super) fn apply_withdrawal(
         &mut self,
+        client_id: ClientId,
         tx_id: TransactionId,
         amount: Amount,
+        config: &LedgerConfig,
     ) -> Result<(), TransactionError> {
-        if amount > self.available.amount() {
-            return Err(TransactionError::NotEnoughFunds);
+        if amount.is_zero() {
+            return Err(TransactionError::ZeroAmount);
+        }
+
+        // A negative withdrawal would actually add money to the account.
+        if amount < dec!(0) {
+            return Err(TransactionError::NegativeAmount);
+        }
+
+        let fee = withdrawal_fee(amount, config);
+        let total = amount.checked_add(fee).ok_or(TransactionError::Overflow)?;
+        if !self.available.is_sufficient_for(total) {
+            return Err(TransactionError::NotEnoughFunds {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
         }
 
         // If we've already seen that transaction, we probably have a data issue.
         if self.tx_states.contains_key(&tx_id) {
-            return Err(TransactionError::DuplicateTransaction);
+            return Err(TransactionError::DuplicateTransaction {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
         }
 
-        self.available.subtract(amount)?;
+        self.available.subtract(total)?;
         self.tx_states
-            .insert(tx_id, (TransactionState::Withdrawn, amount));
+            .insert(tx_id, (TransactionState::Withdrawn, amount, client_id));
+        if !fee.is_zero() {
+            self.tx_fees.insert(tx_id, fee);
+        }
 
         Ok(())
     }
 }
 
+// The fee is rounded like the amounts, so the balances keep the same precision.
+fn withdrawal_fee(amount: Amount, config: &LedgerConfig) -> Amount {
+    (amount * Amount::from(config.withdrawal_fee_bps) / dec!(10000))
+        .round_dp_with_strategy(config.decimal_precision, config.rounding_strategy)
+}
+
 #[cfg(test)]
 mod withdrawal_tests {
-    use crate::ledger::account::{account::TransactionError, balance::Balance};
+    use crate::ledger::{
+        account::{account::TransactionError, balance::Balance},
+        ClientId, LedgerConfig, TransactionId,
+    };
 
     use super::{Account, TransactionState};
+    use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, str::FromStr};
 
     #[test]
     fn test_withdrawal_ok() {
@@ -40,14 +76,80 @@ mod withdrawal_tests {
             available: Balance::new(dec!(3.0), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_withdrawal(1, dec!(3.0));
+        let got = acc.apply_withdrawal(
+            ClientId(1),
+            TransactionId(1),
+            dec!(3.0),
+            &LedgerConfig::default(),
+        );
         assert_eq!(Ok(()), got);
         assert_eq!(dec!(0), acc.available.amount());
         assert_eq!(dec!(1.0), acc.held.amount());
     }
 
+    #[test]
+    // The fee is deducted on top of the amount, and recorded separately.
+    fn test_withdrawal_fee() {
+        let config = LedgerConfig {
+            withdrawal_fee_bps: 25,
+            ..LedgerConfig::default()
+        };
+        for (amount, want_fee) in [
+            (dec!(100), dec!(0.25)),
+            (dec!(3), dec!(0.0075)),
+            // Rounded to the ledger's precision.
+            (dec!(10.0001), dec!(0.025)),
+        ] {
+            let mut acc = Account::new();
+            acc.available = Balance::new(dec!(200), dec!(0));
+
+            let got = acc.apply_withdrawal(ClientId(1), TransactionId(1), amount, &config);
+            assert_eq!(Ok(()), got);
+            assert_eq!(Some(want_fee), acc.get_tx_fee(TransactionId(1)));
+            assert_eq!(dec!(200) - amount - want_fee, acc.available.amount());
+            assert_eq!(
+                Some(&(TransactionState::Withdrawn, amount, ClientId(1))),
+                acc.tx_states.get(&TransactionId(1))
+            );
+
+            let saved = bincode::serialize(&acc).unwrap();
+            let restored: Account = bincode::deserialize(&saved).unwrap();
+            assert_eq!(Some(want_fee), restored.get_tx_fee(TransactionId(1)));
+        }
+
+        // There must be enough funds for the fee as well.
+        let mut acc = Account::new();
+        acc.available = Balance::new(dec!(100), dec!(0));
+        let got = acc.apply_withdrawal(ClientId(1), TransactionId(1), dec!(100), &config);
+        assert_eq!(
+            Err(TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: Some(dec!(100)),
+            }),
+            got
+        );
+        assert_eq!(dec!(100), acc.available.amount());
+        assert_eq!(None, acc.get_tx_fee(TransactionId(1)));
+
+        // No fee by default.
+        let got = acc.apply_withdrawal(
+            ClientId(1),
+            TransactionId(1),
+            dec!(100),
+            &LedgerConfig::default(),
+        );
+        assert_eq!(Ok(()), got);
+        assert_eq!(None, acc.get_tx_fee(TransactionId(1)));
+        assert!(acc.available.is_zero());
+    }
+
     #[test]
     fn test_withdrawal_not_enough_funds() {
         let mut acc = Account {
@@ -55,17 +157,89 @@ mod withdrawal_tests {
             available: Balance::new(dec!(2.5), dec!(0)),
             held: Balance::new(dec!(1.0), dec!(0)),
             tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
         };
 
-        let got = acc.apply_withdrawal(1, dec!(3.0));
-        assert_eq!(Err(TransactionError::NotEnoughFunds), got);
+        let got = acc.apply_withdrawal(
+            ClientId(1),
+            TransactionId(1),
+            dec!(3.0),
+            &LedgerConfig::default(),
+        );
+        assert_eq!(
+            Err(TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(1),
+                client_id: ClientId(1),
+                amount: Some(dec!(3.0)),
+            }),
+            got
+        );
         assert_eq!(dec!(2.5), acc.available.amount());
         assert_eq!(dec!(1.0), acc.held.amount());
     }
 
+    #[test]
+    fn test_withdrawal_non_positive_amount() {
+        for (amount, want_err) in [
+            (dec!(-0.0001), TransactionError::NegativeAmount),
+            (dec!(-1000), TransactionError::NegativeAmount),
+            (dec!(0), TransactionError::ZeroAmount),
+            (dec!(0.0000), TransactionError::ZeroAmount),
+        ] {
+            let mut acc = Account {
+                frozen: false,
+                available: Balance::new(dec!(3.0), dec!(0)),
+                held: Balance::new(dec!(1.0), dec!(0)),
+                tx_states: HashMap::new(),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
+            };
+
+            let got = acc.apply_withdrawal(
+                ClientId(1),
+                TransactionId(1),
+                amount,
+                &LedgerConfig::default(),
+            );
+            assert_eq!(Err(want_err), got);
+            assert_eq!(dec!(3.0), acc.available.amount());
+            assert_eq!(dec!(1.0), acc.held.amount());
+            assert!(acc.tx_states.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_withdrawal_very_large_amount() {
+        let very_big_number = Decimal::from_str("70000000000000000000000000000").unwrap();
+        let mut acc = Account {
+            frozen: false,
+            available: Balance::new(very_big_number, dec!(0)),
+            held: Balance::new(dec!(0), dec!(0)),
+            tx_states: HashMap::new(),
+            tx_metadata: HashMap::new(),
+            last_tx_id: None,
+            tx_fees: HashMap::new(),
+            tx_chargebacks: HashMap::new(),
+        };
+
+        let got = acc.apply_withdrawal(
+            ClientId(1),
+            TransactionId(1),
+            very_big_number,
+            &LedgerConfig::default(),
+        );
+        assert_eq!(Ok(()), got);
+        assert_eq!(dec!(0), acc.available.amount());
+    }
+
     #[test]
     fn test_withdrawal_already_exists() {
-        for state in vec![
+        for state in [
             TransactionState::Withdrawn,
             TransactionState::Deposited,
             TransactionState::Disputed,
@@ -75,11 +249,27 @@ mod withdrawal_tests {
                 frozen: false,
                 available: Balance::new(dec!(99.99), dec!(0)),
                 held: Balance::new(dec!(88.88), dec!(0)),
-                tx_states: HashMap::from([(1, (state, dec!(123.456)))]),
+                tx_states: HashMap::from([(TransactionId(1), (state, dec!(123.456), ClientId(1)))]),
+                tx_metadata: HashMap::new(),
+                last_tx_id: None,
+                tx_fees: HashMap::new(),
+                tx_chargebacks: HashMap::new(),
             };
 
-            let got = acc.apply_withdrawal(1, dec!(3.0));
-            assert_eq!(Err(TransactionError::DuplicateTransaction), got);
+            let got = acc.apply_withdrawal(
+                ClientId(1),
+                TransactionId(1),
+                dec!(3.0),
+                &LedgerConfig::default(),
+            );
+            assert_eq!(
+                Err(TransactionError::DuplicateTransaction {
+                    tx_id: TransactionId(1),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(3.0)),
+                }),
+                got
+            );
             assert_eq!(dec!(99.99), acc.available.amount());
             assert_eq!(dec!(88.88), acc.held.amount());
         }
@@ -0,0 +1,276 @@
+use super::{
+    account::account::{Account, TransactionError},
+    ledger::{reject_transfer, BuildSummary, Ledger},
+    transaction::Transaction,
+    ClientId, LedgerConfig, TransactionId,
+};
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::mpsc::{self, SyncSender},
+    thread::JoinHandle,
+};
+
+// What an actor hands back once its channel is closed: its account, the IDs
+// of the transactions it created, and how many transactions it applied.
+type ActorResult = (Account, Vec<TransactionId>, BuildSummary);
+
+type Actor = (SyncSender<Transaction>, JoinHandle<ActorResult>);
+
+// Each actor has its own channel, and a bounded channel is allocated upfront,
+// so they are kept small: there can be one per client.
+const ACTOR_CHANNEL_CAPACITY: usize = 64;
+
+/// Same as `ledger::build_with_config()`, but each account is owned by its own
+/// thread (an actor), which applies the transactions it receives one after the
+/// other.
+///
+/// The transactions are routed to the actor of their client, which is started
+/// the first time the client is seen. Like `ledger::build_parallel()`, it must
+/// only be used when the clients are independent from each other: transaction
+/// IDs are only checked for uniqueness for each client, and transfers are
+/// rejected. `config.max_errors` and `config.stop_on_error` are ignored as
+/// well.
+///
+/// There is one thread per client, so it's only suited to inputs with a
+/// moderate number of clients.
+///
+/// # Errors
+///
+/// Returns an error if an actor thread can't be started, e.g. when the system
+/// limit on the number of threads is reached. The actors already started are
+/// stopped first.
+///
+/// # Panics
+///
+/// Panics if an actor thread panicked.
+pub fn build_actors(
+    transactions: impl IntoIterator<Item = Transaction>,
+    errors: &SyncSender<TransactionError>,
+    config: LedgerConfig,
+) -> std::io::Result<(Ledger, BuildSummary)> {
+    let config = LedgerConfig {
+        max_errors: None,
+        stop_on_error: false,
+        ..config
+    };
+
+    let mut rejected = BuildSummary::default();
+    let mut actors: HashMap<ClientId, Actor> = HashMap::new();
+
+    for transaction in transactions {
+        if let Err(err) = reject_transfer(&transaction) {
+            rejected.transactions_failed += 1;
+            let _ = errors.send(err);
+            continue;
+        }
+
+        let (actor, _) = match actors.entry(transaction.client_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match spawn_actor(errors.clone(), config) {
+                Ok(actor) => entry.insert(actor),
+                Err(err) => {
+                    join_actors(actors);
+                    return Err(err);
+                }
+            },
+        };
+        actor
+            .send(transaction)
+            .expect("an actor thread stopped early");
+    }
+
+    let (ledger, summary) = join_actors(actors);
+    Ok((ledger.with_config(config), summary + rejected))
+}
+
+// Closes the channels, and collects the accounts once the actors are done.
+fn join_actors(actors: HashMap<ClientId, Actor>) -> (Ledger, BuildSummary) {
+    let mut accounts = HashMap::new();
+    let mut seen_tx_ids = HashMap::new();
+    let mut summary = BuildSummary::default();
+    for (client_id, (actor, handle)) in actors {
+        // Closes the channel, so the actor can finish.
+        drop(actor);
+        let (account, tx_ids, actor_summary) = handle.join().expect("an actor thread panicked");
+        accounts.insert(client_id, account);
+        seen_tx_ids.extend(tx_ids.into_iter().map(|tx_id| (tx_id, client_id)));
+        summary = summary + actor_summary;
+    }
+
+    let ledger = Ledger::from_parts(accounts, seen_tx_ids);
+    summary.count_accounts(&ledger);
+    (ledger, summary)
+}
+
+fn spawn_actor(
+    errors: SyncSender<TransactionError>,
+    config: LedgerConfig,
+) -> std::io::Result<Actor> {
+    let (sender, receiver) = mpsc::sync_channel::<Transaction>(ACTOR_CHANNEL_CAPACITY);
+    let handle = std::thread::Builder::new().spawn(move || {
+        let mut account = Account::new();
+        let mut tx_ids = Vec::new();
+        let mut summary = BuildSummary::default();
+
+        for transaction in receiver {
+            match account.apply_with_config(&transaction, &config) {
+                Ok(()) => {
+                    summary.transactions_processed += 1;
+                    if transaction.amount().is_some() {
+                        tx_ids.push(transaction.tx_id);
+                    }
+                }
+                Err(err) => {
+                    summary.transactions_failed += 1;
+                    let _ = errors.send(err);
+                }
+            }
+        }
+
+        (account, tx_ids, summary)
+    })?;
+
+    Ok((sender, handle))
+}
+
+#[cfg(test)]
+mod actor_tests {
+    use super::build_actors;
+    use crate::ledger::{
+        account::account::TransactionError,
+        ledger::BuildSummary,
+        transaction::{Transaction, Type},
+        ClientId, LedgerConfig, TransactionId,
+    };
+
+    use rust_decimal_macros::dec;
+    use std::sync::mpsc;
+
+    #[test]
+    // Transactions sent rapidly to the same actor are applied in order.
+    fn test_build_actors_sequencing() {
+        let mut transactions = Vec::new();
+        for client_id in 1..=100 {
+            let tx_id = TransactionId(u64::from(client_id));
+            transactions.extend([
+                Transaction::new(Type::Deposit(dec!(10)), ClientId(client_id), tx_id).unwrap(),
+                Transaction::new(Type::Dispute, ClientId(client_id), tx_id).unwrap(),
+                Transaction::new(Type::Chargeback, ClientId(client_id), tx_id).unwrap(),
+            ]);
+        }
+
+        let (errors_tx, errors) = mpsc::sync_channel(300);
+        let (ledger, summary) =
+            build_actors(transactions, &errors_tx, LedgerConfig::default()).unwrap();
+        drop(errors_tx);
+
+        assert_eq!(0, errors.into_iter().count());
+        assert_eq!(
+            BuildSummary {
+                transactions_processed: 300,
+                transactions_failed: 0,
+                accounts_created: 100,
+                accounts_frozen: 100,
+            },
+            summary
+        );
+        for (_, account) in ledger.accounts() {
+            assert!(account.frozen);
+            assert_eq!(dec!(0), account.total_amount());
+        }
+    }
+
+    #[test]
+    fn test_build_actors_errors() {
+        let (errors_tx, errors) = mpsc::sync_channel(3);
+        let (mut ledger, summary) = build_actors(
+            vec![
+                Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+                Transaction::new(Type::Withdrawal(dec!(20)), ClientId(1), TransactionId(2))
+                    .unwrap(),
+                Transaction::new(
+                    Type::Transfer {
+                        to_client: ClientId(2),
+                        amount: dec!(5),
+                    },
+                    ClientId(1),
+                    TransactionId(3),
+                )
+                .unwrap(),
+            ],
+            &errors_tx,
+            LedgerConfig::default(),
+        )
+        .unwrap();
+        drop(errors_tx);
+
+        let mut errors: Vec<TransactionError> = errors.into_iter().collect();
+        errors.sort_by_key(|err| err.context().map(|(tx_id, _, _)| tx_id));
+        assert_eq!(
+            vec![
+                TransactionError::NotEnoughFunds {
+                    tx_id: TransactionId(2),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(20)),
+                },
+                TransactionError::InvalidTransaction {
+                    tx_id: TransactionId(3),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(5)),
+                },
+            ],
+            errors
+        );
+        assert_eq!(1, summary.transactions_processed);
+        assert_eq!(2, summary.transactions_failed);
+
+        // The transaction IDs used by the actors are known to the ledger.
+        assert_eq!(
+            Err(TransactionError::DuplicateTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(1)),
+            }),
+            ledger.apply_transaction(
+                &Transaction::new(Type::Deposit(dec!(1)), ClientId(2), TransactionId(1)).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    // The actors apply the transactions with the given config.
+    fn test_build_actors_config() {
+        let config = LedgerConfig {
+            withdrawal_fee_bps: 100,
+            max_deposit_amount: Some(dec!(50)),
+            ..LedgerConfig::default()
+        };
+        let (errors_tx, errors) = mpsc::sync_channel(1);
+        let (ledger, summary) = build_actors(
+            vec![
+                Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+                Transaction::new(Type::Deposit(dec!(100)), ClientId(1), TransactionId(2)).unwrap(),
+                Transaction::new(Type::Withdrawal(dec!(5)), ClientId(1), TransactionId(3)).unwrap(),
+            ],
+            &errors_tx,
+            config,
+        )
+        .unwrap();
+        drop(errors_tx);
+
+        assert_eq!(
+            vec![TransactionError::AmountTooLarge {
+                tx_id: TransactionId(2),
+                client_id: ClientId(1),
+                amount: Some(dec!(100)),
+            }],
+            errors.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(2, summary.transactions_processed);
+        assert_eq!(
+            dec!(4.95),
+            ledger.get_account(ClientId(1)).unwrap().available.amount()
+        );
+    }
+}
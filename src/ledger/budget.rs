@@ -0,0 +1,106 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// The errors counted towards `LedgerConfig::max_errors`, shared by the
+/// parser and the ledger, so the invalid records and the failed transactions
+/// are counted together.
+///
+/// The parser records each invalid record along with how many transactions
+/// it read before it. Before applying a transaction, the ledger only counts
+/// the invalid records found before it in the input, so where the processing
+/// stops doesn't depend on how far ahead of the ledger the parser is.
+///
+/// Clones share the same budget.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorBudget {
+    max_errors: Option<usize>,
+    // For each invalid record, how many transactions were read before it.
+    // The records found once the budget is exhausted are not kept.
+    parse_errors: Arc<Mutex<Vec<u64>>>,
+}
+
+impl ErrorBudget {
+    /// A budget of `max_errors` errors, or an unlimited one with `None`.
+    #[must_use]
+    pub fn new(max_errors: Option<usize>) -> Self {
+        Self {
+            // Not allowing any error still means stopping at the first one.
+            max_errors: max_errors.map(|max_errors| max_errors.max(1)),
+            parse_errors: Arc::default(),
+        }
+    }
+
+    /// Record an invalid record, found after `transactions_read` transactions.
+    ///
+    /// Returns whether the invalid records alone exhaust the budget, in which
+    /// case the parser can stop reading.
+    #[must_use]
+    pub fn record_parse_error(&self, transactions_read: u64) -> bool {
+        let Some(max_errors) = self.max_errors else {
+            return false;
+        };
+
+        let mut parse_errors = self.parse_errors();
+        if parse_errors.len() < max_errors {
+            parse_errors.push(transactions_read);
+        }
+        parse_errors.len() >= max_errors
+    }
+
+    /// Whether the budget is exhausted before the transaction at `index` in
+    /// the input (counting transactions only) is applied, once
+    /// `transactions_failed` of the previous ones failed.
+    #[must_use]
+    pub fn is_exhausted(&self, index: u64, transactions_failed: u64) -> bool {
+        let Some(max_errors) = self.max_errors else {
+            return false;
+        };
+
+        // The parse errors are recorded in order.
+        let parse_errors = self
+            .parse_errors()
+            .partition_point(|&transactions_read| transactions_read <= index);
+        usize::try_from(transactions_failed).map_or(true, |failed| {
+            failed.saturating_add(parse_errors) >= max_errors
+        })
+    }
+
+    fn parse_errors(&self) -> MutexGuard<'_, Vec<u64>> {
+        // The list is always left consistent, even if a thread panicked.
+        self.parse_errors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[test]
+// Only the invalid records found before a transaction should count for it.
+fn test_error_budget() {
+    let budget = ErrorBudget::new(Some(2));
+    assert!(!budget.is_exhausted(0, 0));
+
+    // An invalid record between the transactions 1 and 2.
+    assert!(!budget.record_parse_error(2));
+    assert!(!budget.is_exhausted(1, 1));
+    assert!(!budget.is_exhausted(2, 0));
+    assert!(budget.is_exhausted(2, 1));
+
+    // Shared by the clones.
+    let parser_budget = budget.clone();
+    assert!(parser_budget.record_parse_error(5));
+    assert!(!budget.is_exhausted(4, 0));
+    assert!(budget.is_exhausted(5, 0));
+}
+
+#[test]
+fn test_error_budget_unlimited() {
+    let budget = ErrorBudget::new(None);
+    for index in 0..100 {
+        assert!(!budget.record_parse_error(index));
+        assert!(!budget.is_exhausted(index, index));
+    }
+
+    // No error allowed.
+    let budget = ErrorBudget::new(Some(0));
+    assert!(!budget.is_exhausted(0, 0));
+    assert!(budget.is_exhausted(1, 1));
+}
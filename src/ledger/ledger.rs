@@ -1,43 +1,1889 @@
 use super::{
     account::account::{Account, TransactionError},
-    transaction::Transaction,
-    ClientId,
+    budget::ErrorBudget,
+    transaction::{Transaction, Type},
+    Amount, ClientId, LedgerConfig, TransactionId,
 };
 
-use std::collections::HashMap;
-use std::sync::mpsc::{self, Receiver, Sender};
+use dashmap::{mapref::entry::Entry, DashMap};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc;
+
+/// Statistics about a ledger build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildSummary {
+    pub transactions_processed: u64,
+    pub transactions_failed: u64,
+    pub accounts_created: u64,
+    pub accounts_frozen: u64,
+}
+
+/// All the client accounts, built by applying transactions one after the
+/// other.
+///
+/// It can be saved once built, and loaded again later to apply more
+/// transactions, without having to process the previous ones again.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Ledger {
+    accounts: HashMap<ClientId, Account>,
+
+    // Transaction IDs are globally unique: accounts only know about their own
+    // transactions, so the IDs already used by any client, and the client that
+    // used them, are tracked here.
+    seen_tx_ids: HashMap<TransactionId, ClientId>,
+
+    // The config isn't saved with the ledger: a loaded ledger uses the default
+    // one, unless another one is given with `with_config()`.
+    #[serde(skip)]
+    config: LedgerConfig,
+}
+
+impl Ledger {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the transactions with the checks enabled in `config`, e.g.
+    /// `enforce_monotonic_tx_id`.
+    #[must_use]
+    pub const fn with_config(mut self, config: LedgerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // For ledgers built from accounts processed independently from each other.
+    pub(super) fn from_parts(
+        accounts: HashMap<ClientId, Account>,
+        seen_tx_ids: HashMap<TransactionId, ClientId>,
+    ) -> Self {
+        Self {
+            accounts,
+            seen_tx_ids,
+            config: LedgerConfig::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn get_account(&self, client_id: ClientId) -> Option<&Account> {
+        self.accounts.get(&client_id)
+    }
+
+    /// All the accounts, in an arbitrary order.
+    pub fn accounts(&self) -> impl Iterator<Item = (ClientId, &Account)> {
+        self.accounts
+            .iter()
+            .map(|(client_id, account)| (*client_id, account))
+    }
+
+    /// All the accounts, by ascending client ID, e.g. for reproducible reports.
+    pub fn iter_clients(&self) -> impl Iterator<Item = (ClientId, &Account)> {
+        let mut client_ids: Vec<ClientId> = self.accounts.keys().copied().collect();
+        client_ids.sort_unstable();
+        client_ids
+            .into_iter()
+            .map(|client_id| (client_id, &self.accounts[&client_id]))
+    }
+
+    /// The accounts frozen after a chargeback, in an arbitrary order.
+    pub fn frozen_accounts(&self) -> impl Iterator<Item = (ClientId, &Account)> {
+        self.accounts().filter(|(_, account)| account.frozen)
+    }
+
+    /// The transactions currently disputed, with the client that disputed
+    /// them, sorted by client ID then transaction ID.
+    #[must_use]
+    pub fn disputed_transaction_ids(&self) -> Vec<(ClientId, TransactionId)> {
+        let mut disputed: Vec<(ClientId, TransactionId)> = self
+            .accounts()
+            .flat_map(|(client_id, account)| {
+                account
+                    .pending_disputes()
+                    .map(move |tx_id| (client_id, tx_id))
+            })
+            .collect();
+        disputed.sort_unstable();
+        disputed
+    }
+
+    #[must_use]
+    pub fn account_count(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Number of clients, i.e. of accounts. Same as `account_count()`.
+    #[must_use]
+    pub fn client_count(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Number of frozen accounts. It goes through all the accounts.
+    #[must_use]
+    pub fn frozen_count(&self) -> usize {
+        self.frozen_accounts().count()
+    }
+
+    /// Number of accounts that are not frozen.
+    #[must_use]
+    pub fn active_count(&self) -> usize {
+        self.client_count() - self.frozen_count()
+    }
+
+    /// Sum of the held funds of all the accounts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TransactionError::Overflow` if the sum overflows.
+    pub fn total_held(&self) -> Result<Amount, TransactionError> {
+        self.sum_balances(|account| account.held.amount())
+    }
+
+    /// Sum of the available funds of all the accounts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TransactionError::Overflow` if the sum overflows.
+    pub fn total_available(&self) -> Result<Amount, TransactionError> {
+        self.sum_balances(|account| account.available.amount())
+    }
+
+    fn sum_balances(
+        &self,
+        balance: impl Fn(&Account) -> Amount,
+    ) -> Result<Amount, TransactionError> {
+        self.accounts
+            .values()
+            .try_fold(Amount::ZERO, |total, account| {
+                total
+                    .checked_add(balance(account))
+                    .ok_or(TransactionError::Overflow)
+            })
+    }
+
+    /// Save the ledger as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer cannot be written to.
+    pub fn save_to_writer(&self, writer: impl std::io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(writer, self)
+    }
+
+    /// Load a ledger saved with `save_to_writer()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader cannot be read from, or doesn't contain
+    /// a valid ledger.
+    pub fn load_from_reader(reader: impl std::io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+    /// Save the ledger with bincode, which is faster and more compact than JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer cannot be written to.
+    pub fn save_to_bincode(&self, writer: impl std::io::Write) -> Result<(), bincode::Error> {
+        bincode::serialize_into(writer, self)
+    }
+
+    /// Load a ledger saved with `save_to_bincode()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader cannot be read from, or doesn't contain
+    /// a valid ledger.
+    pub fn load_from_bincode(reader: impl std::io::Read) -> Result<Self, bincode::Error> {
+        bincode::deserialize_from(reader)
+    }
+
+    /// Consume the ledger, returning the accounts by ascending client ID.
+    // The HashMap iteration order is arbitrary: sorting requires collecting
+    // the accounts again.
+    pub fn into_sorted_accounts(self) -> impl Iterator<Item = (ClientId, Account)> {
+        self.accounts
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+    }
+
+    /// Apply a single transaction, creating the client account if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction can't be applied, e.g. when its ID
+    /// was already used, or the account doesn't have enough funds. The balances
+    /// are left unchanged, but the account is still created.
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(tx_id = %transaction.tx_id, client_id = %transaction.client_id)
+    )]
+    pub fn apply_transaction(&mut self, transaction: &Transaction) -> Result<(), TransactionError> {
+        #[cfg(feature = "metrics")]
+        let was_frozen = self
+            .accounts
+            .get(&transaction.client_id)
+            .is_some_and(|account| account.frozen);
+
+        let result = self.try_apply_transaction(transaction);
+
+        #[cfg(feature = "metrics")]
+        {
+            let account = self.accounts.get(&transaction.client_id);
+            let disputed_amount = match transaction.tx_type {
+                // Only what wasn't charged back yet is disputed.
+                Type::Dispute => account.and_then(|account| {
+                    let chargedback = account
+                        .get_tx_chargedback_amount(transaction.tx_id)
+                        .unwrap_or_default();
+                    account
+                        .get_tx_amount(transaction.tx_id)
+                        .map(|amount| amount - chargedback)
+                }),
+                _ => None,
+            };
+            crate::metrics::record_transaction(
+                result.is_ok(),
+                !was_frozen && account.is_some_and(|account| account.frozen),
+                disputed_amount,
+            );
+        }
+        result
+    }
+
+    fn try_apply_transaction(&mut self, transaction: &Transaction) -> Result<(), TransactionError> {
+        check_tx_id(
+            transaction,
+            self.seen_tx_ids.get(&transaction.tx_id).copied(),
+        )?;
+
+        match transaction.tx_type {
+            Type::Transfer { to_client, amount } => {
+                self.apply_transfer(transaction, to_client, amount)?;
+            }
+            _ => self
+                .accounts
+                .entry(transaction.client_id)
+                .or_default()
+                .apply_with_config(transaction, &self.config)?,
+        }
+
+        // A failed transaction can be retried with the same ID.
+        if transaction.amount().is_some() {
+            self.seen_tx_ids
+                .insert(transaction.tx_id, transaction.client_id);
+        }
+
+        Ok(())
+    }
+
+    /// Apply the transactions in order. A failed transaction doesn't stop the
+    /// following ones from being applied: the errors are returned instead,
+    /// along with the ID of the transaction that failed.
+    pub fn apply_transactions(
+        &mut self,
+        transactions: &[Transaction],
+    ) -> Vec<(TransactionId, TransactionError)> {
+        transactions
+            .iter()
+            .filter_map(|transaction| {
+                self.apply_transaction(transaction)
+                    .err()
+                    .map(|err| (transaction.tx_id, err))
+            })
+            .collect()
+    }
+
+    // A transfer is applied to both accounts, or to none of them. The recipient
+    // account is created if it doesn't exist yet.
+    fn apply_transfer(
+        &mut self,
+        transaction: &Transaction,
+        to_client: ClientId,
+        amount: Amount,
+    ) -> Result<(), TransactionError> {
+        let (client_id, tx_id) = (transaction.client_id, transaction.tx_id);
+        if to_client == client_id {
+            return Err(TransactionError::InvalidTransaction {
+                tx_id,
+                client_id,
+                amount: Some(amount),
+            });
+        }
+
+        // We can't borrow both accounts mutably from the HashMap at the same time,
+        // so the sender is taken out of the ledger while the transfer is applied.
+        let mut sender = self.accounts.remove(&client_id).unwrap_or_default();
+        let checkpoint = sender.transfer_checkpoint();
+
+        let result = sender
+            .apply_transfer_out(client_id, tx_id, amount, &self.config)
+            .and_then(|()| {
+                self.accounts
+                    .entry(to_client)
+                    .or_default()
+                    .apply_transfer_in(to_client, tx_id, amount, &self.config)
+                    // Roll back the sender side if the recipient side failed.
+                    .inspect_err(|_| sender.roll_back_transfer_out(tx_id, checkpoint))
+            });
+
+        self.accounts.insert(client_id, sender);
+        result
+    }
+}
+
+// Transaction IDs are globally unique: `seen_by` is the client that already
+// used the transaction's ID, if any.
+fn check_tx_id(
+    transaction: &Transaction,
+    seen_by: Option<ClientId>,
+) -> Result<(), TransactionError> {
+    // Only deposits, withdrawals and transfers create a new transaction, the
+    // other types reference an existing one.
+    let is_new_tx = transaction.amount().is_some();
+    let (tx_id, client_id) = (transaction.tx_id, transaction.client_id);
+    match seen_by {
+        Some(_) if is_new_tx => Err(TransactionError::DuplicateTransaction {
+            tx_id,
+            client_id,
+            amount: transaction.amount(),
+        }),
+        Some(tx_client_id) if tx_client_id != client_id => Err(TransactionError::ClientMismatch {
+            tx_id,
+            client_id,
+            amount: None,
+        }),
+        _ => Ok(()),
+    }
+}
+
+// Transfers involve two clients, so they can't be applied when the clients
+// are processed independently from each other.
+pub(super) const fn reject_transfer(transaction: &Transaction) -> Result<(), TransactionError> {
+    match transaction.tx_type {
+        Type::Transfer { amount, .. } => Err(TransactionError::InvalidTransaction {
+            tx_id: transaction.tx_id,
+            client_id: transaction.client_id,
+            amount: Some(amount),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// A sensible capacity for the bounded channels the errors are sent to.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1000;
 
 /// Build the ledger.
+///
 /// It takes a stream of transactions, and gradually builds itself.
-/// It will stream errors while building it, and once it has processed
-/// all the transactions, it will send all account balances to the relevant
-/// channel.
+/// It sends errors to the given channel while building it, and once it has
+/// processed all the transactions, it returns the ledger along with a summary
+/// of the build.
+///
+/// The channel is bounded, so building the ledger slows down rather than
+/// buffering an unlimited number of errors when they aren't read fast enough.
+/// They must be read from another thread, unless the channel can hold all of
+/// them.
 pub fn build(
-    transactions: Receiver<Transaction>,
-    accounts_tx: Sender<(ClientId, Account)>,
-) -> Receiver<TransactionError> {
-    let (tx, rx) = mpsc::channel();
+    transactions: impl IntoIterator<Item = Transaction>,
+    errors: &mpsc::SyncSender<TransactionError>,
+) -> (Ledger, BuildSummary) {
+    build_with_config(transactions, errors, LedgerConfig::default())
+}
+
+/// Same as `build()`, with the checks enabled in `config`.
+///
+/// With `config.max_errors`, the build stops once that many transactions
+/// failed. Use `build_with_budget()` to count the invalid records as well.
+pub fn build_with_config(
+    transactions: impl IntoIterator<Item = Transaction>,
+    errors: &mpsc::SyncSender<TransactionError>,
+    config: LedgerConfig,
+) -> (Ledger, BuildSummary) {
+    build_with_budget(
+        transactions,
+        errors,
+        config,
+        &ErrorBudget::new(config.max_errors),
+    )
+}
+
+/// Same as `build_with_config()`, but the build stops once `budget` is
+/// exhausted.
+///
+/// The invalid records the parser recorded in the budget are counted along
+/// with the failed transactions. It should be created with
+/// `config.max_errors`. The budget is checked before each transaction is applied, so the
+/// transactions after the last allowed error are never applied.
+pub fn build_with_budget(
+    transactions: impl IntoIterator<Item = Transaction>,
+    errors: &mpsc::SyncSender<TransactionError>,
+    config: LedgerConfig,
+    budget: &ErrorBudget,
+) -> (Ledger, BuildSummary) {
+    let (ledger, summary) = build_without_summary(transactions, errors, config, budget);
+    tracing::info!(?summary, "ledger built");
+    (ledger, summary)
+}
+
+// Same as `build_with_budget()`, without logging the summary, e.g. for each
+// client of a parallel build.
+fn build_without_summary(
+    transactions: impl IntoIterator<Item = Transaction>,
+    errors: &mpsc::SyncSender<TransactionError>,
+    config: LedgerConfig,
+    budget: &ErrorBudget,
+) -> (Ledger, BuildSummary) {
+    let mut ledger = Ledger::new().with_config(config);
+    let mut summary = BuildSummary::default();
+
+    for (index, transaction) in (0..).zip(transactions) {
+        // The invalid records before this transaction may have exhausted the
+        // budget.
+        if budget.is_exhausted(index, summary.transactions_failed) {
+            break;
+        }
 
-    // We apply all transactions in a new thread, to be able to stream errors as
-    // we go.
-    std::thread::spawn(move || {
-        let mut ledger: HashMap<ClientId, Account> = HashMap::new();
+        tracing::debug!(?transaction, "applying transaction");
+        // The errors are reported by whoever reads them from the channel.
+        if let Err(err) = ledger.apply_transaction(&transaction) {
+            summary.transactions_failed += 1;
+            // Callers are free to ignore the errors and drop the rx.
+            let _ = errors.send(err);
+            // The failures are counted here rather than by whoever reads the
+            // errors, so no transaction is applied after the budget is
+            // exhausted.
+            if config.stop_on_error || budget.is_exhausted(index, summary.transactions_failed) {
+                break;
+            }
+        } else {
+            summary.transactions_processed += 1;
+        }
+    }
+
+    summary.count_accounts(&ledger);
+    (ledger, summary)
+}
+
+/// Find the transactions that would fail, without building a ledger.
+///
+/// They are applied to a scratch ledger, which is dropped along with the
+/// returned iterator. Useful to validate transactions before actually applying
+/// them.
+///
+/// The transactions are applied as the iterator is consumed, so they don't
+/// have to fit in memory at once.
+///
+/// Like `build_with_config()`, it stops at the first failure with
+/// `config.stop_on_error`, or once `config.max_errors` transactions failed.
+pub fn dry_run(
+    transactions: impl IntoIterator<Item = Transaction>,
+    config: LedgerConfig,
+) -> impl Iterator<Item = (TransactionId, TransactionError)> {
+    dry_run_with_budget(transactions, config, ErrorBudget::new(config.max_errors))
+}
+
+/// Same as `dry_run()`, but it stops once `budget` is exhausted, like
+/// `build_with_budget()`.
+pub fn dry_run_with_budget(
+    transactions: impl IntoIterator<Item = Transaction>,
+    config: LedgerConfig,
+    budget: ErrorBudget,
+) -> impl Iterator<Item = (TransactionId, TransactionError)> {
+    let mut scratch = Ledger::new().with_config(config);
+    let mut transactions = transactions.into_iter();
+    let mut index = 0;
+    let mut transactions_failed = 0;
+    let mut stopped = false;
+    // The budget is checked before reading the next transaction, so the ones
+    // that are not applied are not consumed either.
+    std::iter::from_fn(move || {
+        while !stopped && !budget.is_exhausted(index, transactions_failed) {
+            let transaction = transactions.next()?;
+            let result = scratch.apply_transaction(&transaction);
+            if let Err(err) = result {
+                transactions_failed += 1;
+                stopped = config.stop_on_error || budget.is_exhausted(index, transactions_failed);
+                index += 1;
+                return Some((transaction.tx_id, err));
+            }
+            index += 1;
+        }
+        stopped = true;
+        None
+    })
+}
+
+/// Same as `build_with_config()`, but the transactions are read from an
+/// async stream, and `on_error` is called with each error instead of sending
+/// it to a channel.
+#[cfg(feature = "async")]
+pub async fn build_async(
+    transactions: impl futures::Stream<Item = Transaction>,
+    mut on_error: impl FnMut(TransactionError),
+    config: LedgerConfig,
+) -> (Ledger, BuildSummary) {
+    use futures::StreamExt;
+
+    let mut ledger = Ledger::new().with_config(config);
+    let mut summary = BuildSummary::default();
+    let budget = ErrorBudget::new(config.max_errors);
+
+    futures::pin_mut!(transactions);
+    let mut index = 0;
+    while let Some(transaction) = transactions.next().await {
+        if let Err(err) = ledger.apply_transaction(&transaction) {
+            summary.transactions_failed += 1;
+            on_error(err);
+            if config.stop_on_error || budget.is_exhausted(index, summary.transactions_failed) {
+                break;
+            }
+        } else {
+            summary.transactions_processed += 1;
+        }
+        index += 1;
+    }
+
+    summary.count_accounts(&ledger);
+    (ledger, summary)
+}
+
+/// Same as `build_with_config()`, but the transactions of different clients
+/// are applied in parallel.
+///
+/// The transactions are first grouped by client, and each client's
+/// transactions are then applied in order, so it must only be used when the
+/// clients are independent from each other: transfers are rejected.
+/// Transaction IDs are still checked for uniqueness across all the clients,
+/// while grouping them: an ID is taken by the first client using it, even if
+/// that transaction then fails.
+///
+/// The clients are not processed in the input order, so `config.max_errors`
+/// and `config.stop_on_error` are ignored.
+/// The errors are sent grouped by client rather than in the input order.
+pub fn build_parallel(
+    transactions: impl IntoIterator<Item = Transaction>,
+    errors: &mpsc::SyncSender<TransactionError>,
+    config: LedgerConfig,
+) -> (Ledger, BuildSummary) {
+    let config = LedgerConfig {
+        max_errors: None,
+        stop_on_error: false,
+        ..config
+    };
+
+    let mut rejected = BuildSummary::default();
+    let mut tx_id_owners: HashMap<TransactionId, ClientId> = HashMap::new();
+    let mut by_client: HashMap<ClientId, Vec<Transaction>> = HashMap::new();
+    for transaction in transactions {
+        if let Err(err) = reject_transfer(&transaction)
+            .and_then(|()| claim_tx_id(&mut tx_id_owners, &transaction))
+        {
+            rejected.transactions_failed += 1;
+            let _ = errors.send(err);
+            continue;
+        }
+
+        by_client
+            .entry(transaction.client_id)
+            .or_default()
+            .push(transaction);
+    }
+
+    let (ledger, summary) = by_client
+        .into_par_iter()
+        .map(|(_, transactions)| {
+            build_without_summary(transactions, errors, config, &ErrorBudget::default())
+        })
+        .reduce(
+            || (Ledger::new(), BuildSummary::default()),
+            |(mut ledger, summary), (client_ledger, client_summary)| {
+                // Each client has its own ledger, so they never overlap.
+                ledger.accounts.extend(client_ledger.accounts);
+                ledger.seen_tx_ids.extend(client_ledger.seen_tx_ids);
+                (ledger, summary + client_summary)
+            },
+        );
+
+    let summary = summary + rejected;
+    tracing::info!(?summary, "ledger built");
+    // The config is kept, for the transactions applied afterwards.
+    (ledger.with_config(config), summary)
+}
+
+// Rejects the transactions using an ID that belongs to another client. A
+// client reusing its own IDs is left to its account, which knows whether the
+// first transaction actually succeeded.
+fn claim_tx_id(
+    tx_id_owners: &mut HashMap<TransactionId, ClientId>,
+    transaction: &Transaction,
+) -> Result<(), TransactionError> {
+    match tx_id_owners.get(&transaction.tx_id) {
+        Some(&owner) if owner != transaction.client_id => check_tx_id(transaction, Some(owner)),
+        Some(_) => Ok(()),
+        None => {
+            if transaction.amount().is_some() {
+                tx_id_owners.insert(transaction.tx_id, transaction.client_id);
+            }
+            Ok(())
+        }
+    }
+}
+
+impl BuildSummary {
+    // Accounts are counted once all the transactions have been applied.
+    pub(super) fn count_accounts(&mut self, ledger: &Ledger) {
+        for (_, account) in ledger.accounts() {
+            self.accounts_created += 1;
+            if account.frozen {
+                self.accounts_frozen += 1;
+            }
+        }
+    }
+}
+
+impl std::ops::Add for BuildSummary {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            transactions_processed: self.transactions_processed + other.transactions_processed,
+            transactions_failed: self.transactions_failed + other.transactions_failed,
+            accounts_created: self.accounts_created + other.accounts_created,
+            accounts_frozen: self.accounts_frozen + other.accounts_frozen,
+        }
+    }
+}
+
+/// Same as `build_with_config()`, but the transactions are applied by several
+/// worker threads sharing the accounts.
+///
+/// Each client is assigned to a single worker, so its transactions are still
+/// applied in order. Like `build_parallel()`, it must only be used when the
+/// clients are independent from each other: transfers are rejected. When two
+/// clients use the same transaction ID, only one of them succeeds, but which
+/// one depends on the workers' timing.
+///
+/// The clients are not processed in the input order, so `config.max_errors`
+/// and `config.stop_on_error` are ignored.
+/// The errors are sent in the order the workers hit them.
+///
+/// # Panics
+///
+/// Panics if `workers` is 0, or if a worker thread panicked.
+pub fn build_concurrent(
+    transactions: impl IntoIterator<Item = Transaction>,
+    workers: usize,
+    errors: &mpsc::SyncSender<TransactionError>,
+    config: LedgerConfig,
+) -> (Ledger, BuildSummary) {
+    assert!(workers > 0, "at least one worker is needed");
+    let config = LedgerConfig {
+        max_errors: None,
+        stop_on_error: false,
+        ..config
+    };
+
+    let accounts: DashMap<ClientId, Account> = DashMap::new();
+    let seen_tx_ids: DashMap<TransactionId, ClientId> = DashMap::new();
+
+    let mut summary = std::thread::scope(|scope| {
+        let (senders, handles): (Vec<_>, Vec<_>) = (0..workers)
+            .map(|_| {
+                let (sender, receiver) =
+                    crossbeam_channel::bounded::<Transaction>(DEFAULT_CHANNEL_CAPACITY);
+                let (accounts, seen_tx_ids, config) = (&accounts, &seen_tx_ids, &config);
+                let handle = scope.spawn(move || {
+                    let mut summary = BuildSummary::default();
+                    for transaction in receiver {
+                        match apply_shared(accounts, seen_tx_ids, &transaction, config) {
+                            Ok(()) => summary.transactions_processed += 1,
+                            Err(err) => {
+                                summary.transactions_failed += 1;
+                                let _ = errors.send(err);
+                            }
+                        }
+                    }
+                    summary
+                });
+                (sender, handle)
+            })
+            .unzip();
 
         for transaction in transactions {
-            let account = ledger
-                .entry(transaction.client_id)
-                .or_insert_with(Account::new);
+            let worker = transaction.client_id.0 as usize % workers;
+            senders[worker]
+                .send(transaction)
+                .expect("a worker thread stopped early");
+        }
+        // Closes the channels, so the workers can finish.
+        drop(senders);
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("a worker thread panicked"))
+            .fold(BuildSummary::default(), |total, summary| total + summary)
+    });
+
+    let ledger = Ledger::from_parts(
+        accounts.into_iter().collect(),
+        seen_tx_ids.into_iter().collect(),
+    )
+    .with_config(config);
+    summary.count_accounts(&ledger);
+    (ledger, summary)
+}
+
+// The ID's entry is held on purpose until the transaction is applied.
+#[allow(clippy::significant_drop_tightening)]
+fn apply_shared(
+    accounts: &DashMap<ClientId, Account>,
+    seen_tx_ids: &DashMap<TransactionId, ClientId>,
+    transaction: &Transaction,
+    config: &LedgerConfig,
+) -> Result<(), TransactionError> {
+    reject_transfer(transaction)?;
 
-            if let Err(err) = account.apply(&transaction) {
-                tx.send(err).unwrap(); // Would only fail if the rx is disconnected, which should not happen here.
+    // The entry locks the ID's shard until the transaction is applied, so two
+    // workers can't both use the same ID. The IDs are always locked before
+    // the accounts, so the workers can't deadlock.
+    let tx_id_entry = seen_tx_ids.entry(transaction.tx_id);
+    let seen_by = match &tx_id_entry {
+        Entry::Occupied(entry) => Some(*entry.get()),
+        Entry::Vacant(_) => None,
+    };
+    check_tx_id(transaction, seen_by)?;
+
+    // The entry locks the account's shard until the transaction is applied.
+    accounts
+        .entry(transaction.client_id)
+        .or_default()
+        .apply_with_config(transaction, config)?;
+
+    if transaction.amount().is_some() {
+        tx_id_entry.insert(transaction.client_id);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_into_sorted_accounts() {
+    use rust_decimal_macros::dec;
+
+    // Deposit for clients in a scrambled order.
+    let transactions =
+        [7, 2, 9, 10, 1, 5, 3, 8, 6, 4]
+            .into_iter()
+            .enumerate()
+            .map(|(tx_id, client_id)| {
+                let tx_id = TransactionId(u64::try_from(tx_id).unwrap());
+                Transaction::new(Type::Deposit(dec!(1)), ClientId(client_id), tx_id).unwrap()
+            });
+
+    let (errors_tx, errors) = mpsc::sync_channel(10);
+    let (ledger, _) = build(transactions, &errors_tx);
+    drop(errors_tx);
+
+    let client_ids: Vec<ClientId> = ledger
+        .into_sorted_accounts()
+        .map(|(client_id, _)| client_id)
+        .collect();
+    assert_eq!((1..=10).map(ClientId).collect::<Vec<_>>(), client_ids);
+    assert_eq!(0, errors.into_iter().count());
+}
+
+#[test]
+fn test_ledger_accounts() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(5)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(3)), ClientId(1), TransactionId(3)).unwrap(),
+    ]);
+
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+    assert_eq!(2, ledger.account_count());
+    assert_eq!(
+        dec!(7),
+        ledger.get_account(ClientId(1)).unwrap().available.amount()
+    );
+    assert_eq!(
+        dec!(5),
+        ledger.get_account(ClientId(2)).unwrap().available.amount()
+    );
+    assert!(ledger.get_account(ClientId(3)).is_none());
+
+    let mut client_ids: Vec<ClientId> = ledger.accounts().map(|(client_id, _)| client_id).collect();
+    client_ids.sort_unstable();
+    assert_eq!(vec![ClientId(1), ClientId(2)], client_ids);
+}
+
+#[test]
+fn test_ledger_empty() {
+    let ledger = Ledger::new();
+    assert_eq!(0, ledger.account_count());
+    assert_eq!(0, ledger.accounts().count());
+    assert!(ledger.get_account(ClientId(1)).is_none());
+}
+
+#[test]
+// Transactions can still be applied once the ledger has been built.
+fn test_apply_transaction() {
+    use rust_decimal_macros::dec;
+
+    let (mut ledger, errors) = build_ledger(vec![Transaction::new(
+        Type::Deposit(dec!(10)),
+        ClientId(1),
+        TransactionId(1),
+    )
+    .unwrap()]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    ledger
+        .apply_transaction(
+            &Transaction::new(Type::Deposit(dec!(2.5)), ClientId(1), TransactionId(2)).unwrap(),
+        )
+        .unwrap();
+    ledger
+        .apply_transaction(
+            &Transaction::new(Type::Deposit(dec!(3)), ClientId(2), TransactionId(3)).unwrap(),
+        )
+        .unwrap();
+
+    assert_eq!(
+        dec!(12.5),
+        ledger.get_account(ClientId(1)).unwrap().available.amount()
+    );
+    assert_eq!(
+        dec!(3),
+        ledger.get_account(ClientId(2)).unwrap().available.amount()
+    );
+
+    // IDs used while building the ledger are still known.
+    assert_eq!(
+        Err(TransactionError::DuplicateTransaction {
+            tx_id: TransactionId(1),
+            client_id: ClientId(2),
+            amount: Some(dec!(1)),
+        }),
+        ledger.apply_transaction(
+            &Transaction::new(Type::Deposit(dec!(1)), ClientId(2), TransactionId(1)).unwrap(),
+        )
+    );
+}
+
+#[test]
+// A failed transaction doesn't prevent the following ones from being applied.
+fn test_apply_transactions() {
+    use rust_decimal_macros::dec;
+
+    let mut ledger = Ledger::new();
+    let errors = ledger.apply_transactions(&[
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(20)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(4)), ClientId(1), TransactionId(3)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(4)).unwrap(),
+    ]);
+
+    assert_eq!(
+        vec![
+            (
+                TransactionId(2),
+                TransactionError::NotEnoughFunds {
+                    tx_id: TransactionId(2),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(20)),
+                }
+            ),
+            (
+                TransactionId(4),
+                TransactionError::UnknownTransaction {
+                    tx_id: TransactionId(4),
+                    client_id: ClientId(1),
+                    amount: None,
+                }
+            ),
+        ],
+        errors
+    );
+    assert_eq!(
+        dec!(6),
+        ledger.get_account(ClientId(1)).unwrap().available.amount()
+    );
+}
+
+#[test]
+// A dry run should report the errors the transactions would have when applied
+// one after the other.
+fn test_dry_run() {
+    use rust_decimal_macros::dec;
+
+    let errors: Vec<_> = dry_run(
+        [
+            Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+            Transaction::new(Type::Withdrawal(dec!(4)), ClientId(1), TransactionId(2)).unwrap(),
+            Transaction::new(Type::Withdrawal(dec!(7)), ClientId(1), TransactionId(3)).unwrap(),
+            Transaction::new(Type::Deposit(dec!(5)), ClientId(2), TransactionId(4)).unwrap(),
+        ],
+        LedgerConfig::default(),
+    )
+    .collect();
+
+    assert_eq!(
+        vec![(
+            TransactionId(3),
+            TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(3),
+                client_id: ClientId(1),
+                amount: Some(dec!(7)),
+            }
+        )],
+        errors
+    );
+}
+
+#[test]
+// The transactions should be applied as the errors are read, rather than all
+// at once.
+fn test_dry_run_streaming() {
+    use rust_decimal_macros::dec;
+
+    // Withdrawals from an empty account, forever.
+    let transactions = (1..).map(|tx_id| {
+        Transaction::new(Type::Withdrawal(dec!(1)), ClientId(1), TransactionId(tx_id)).unwrap()
+    });
+
+    let tx_ids: Vec<_> = dry_run(transactions, LedgerConfig::default())
+        .take(3)
+        .map(|(tx_id, _)| tx_id)
+        .collect();
+    assert_eq!(
+        vec![TransactionId(1), TransactionId(2), TransactionId(3)],
+        tx_ids
+    );
+}
+
+#[test]
+fn test_iter_clients() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(1)), ClientId(5), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(2)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(3)), ClientId(10), TransactionId(3)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    let clients: Vec<(ClientId, Amount)> = ledger
+        .iter_clients()
+        .map(|(client_id, account)| (client_id, account.available.amount()))
+        .collect();
+    assert_eq!(
+        vec![
+            (ClientId(2), dec!(2)),
+            (ClientId(5), dec!(1)),
+            (ClientId(10), dec!(3)),
+        ],
+        clients
+    );
+    assert_eq!(0, Ledger::new().iter_clients().count());
+}
+
+#[test]
+fn test_disputed_transaction_ids() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(1)), ClientId(3), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(2)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(3)), ClientId(2), TransactionId(3)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(4)), ClientId(2), TransactionId(4)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(3), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(2), TransactionId(3)).unwrap(),
+        Transaction::new(Type::Resolve, ClientId(1), TransactionId(2)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    assert_eq!(
+        vec![
+            (ClientId(2), TransactionId(3)),
+            (ClientId(3), TransactionId(1)),
+        ],
+        ledger.disputed_transaction_ids()
+    );
+    assert!(Ledger::new().disputed_transaction_ids().is_empty());
+}
+
+#[test]
+fn test_frozen_accounts() {
+    use rust_decimal_macros::dec;
+
+    let mut transactions = Vec::new();
+    for client_id in 1..=5 {
+        let tx_id = TransactionId(u64::from(client_id));
+        transactions
+            .push(Transaction::new(Type::Deposit(dec!(10)), ClientId(client_id), tx_id).unwrap());
+        if client_id % 2 == 0 {
+            transactions.push(Transaction::new(Type::Dispute, ClientId(client_id), tx_id).unwrap());
+            transactions
+                .push(Transaction::new(Type::Chargeback, ClientId(client_id), tx_id).unwrap());
+        }
+    }
+    let (ledger, errors) = build_ledger(transactions);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    let mut frozen: Vec<ClientId> = ledger
+        .frozen_accounts()
+        .map(|(client_id, _)| client_id)
+        .collect();
+    frozen.sort_unstable();
+    assert_eq!(vec![ClientId(2), ClientId(4)], frozen);
+}
+
+#[test]
+fn test_client_counts() {
+    use rust_decimal_macros::dec;
+
+    let mut transactions = Vec::new();
+    for client_id in 1..=5 {
+        let tx_id = TransactionId(u64::from(client_id));
+        transactions
+            .push(Transaction::new(Type::Deposit(dec!(10)), ClientId(client_id), tx_id).unwrap());
+        if client_id % 2 == 0 {
+            transactions.push(Transaction::new(Type::Dispute, ClientId(client_id), tx_id).unwrap());
+            transactions
+                .push(Transaction::new(Type::Chargeback, ClientId(client_id), tx_id).unwrap());
+        }
+    }
+    let (ledger, errors) = build_ledger(transactions);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    assert_eq!(5, ledger.client_count());
+    assert_eq!(2, ledger.frozen_count());
+    assert_eq!(3, ledger.active_count());
+
+    let ledger = Ledger::new();
+    assert_eq!(
+        (0, 0, 0),
+        (
+            ledger.client_count(),
+            ledger.frozen_count(),
+            ledger.active_count()
+        )
+    );
+}
+
+#[test]
+fn test_frozen_accounts_none() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Resolve, ClientId(1), TransactionId(1)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    assert_eq!(0, ledger.frozen_accounts().count());
+    assert_eq!(0, Ledger::new().frozen_accounts().count());
+}
+
+#[test]
+fn test_ledger_totals() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(2.5)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(7)), ClientId(2), TransactionId(3)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(1.25)), ClientId(2), TransactionId(4)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(3)), ClientId(3), TransactionId(5)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(3), TransactionId(5)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    // Client 1: 10 available, 2.5 held.
+    // Client 2: 7 - 1.25 = 5.75 available.
+    // Client 3: 3 held.
+    assert_eq!(Ok(dec!(15.75)), ledger.total_available());
+    assert_eq!(Ok(dec!(5.5)), ledger.total_held());
+
+    assert_eq!(Ok(dec!(0)), Ledger::new().total_available());
+    assert_eq!(Ok(dec!(0)), Ledger::new().total_held());
+}
+
+#[test]
+fn test_ledger_totals_overflow() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(Amount::MAX), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(1)), ClientId(2), TransactionId(2)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    assert_eq!(Err(TransactionError::Overflow), ledger.total_available());
+    assert_eq!(Ok(dec!(0)), ledger.total_held());
+}
+
+#[test]
+// A saved ledger should behave exactly like the original one once loaded.
+fn test_ledger_save_load() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(5)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(3)), ClientId(2), TransactionId(3)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(2), TransactionId(3)).unwrap(),
+        Transaction::new(Type::Chargeback, ClientId(2), TransactionId(3)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    let mut saved = Vec::new();
+    ledger.save_to_writer(&mut saved).unwrap();
+    let mut ledger = Ledger::load_from_reader(saved.as_slice()).unwrap();
+
+    let errors = ledger.apply_transactions(&[
+        // The disputed deposit is still known...
+        Transaction::new(Type::Resolve, ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(12)), ClientId(1), TransactionId(4)).unwrap(),
+        // ... and so are the transaction IDs already used.
+        Transaction::new(Type::Deposit(dec!(1)), ClientId(1), TransactionId(3)).unwrap(),
+        // The frozen account is still frozen.
+        Transaction::new(Type::Deposit(dec!(1)), ClientId(2), TransactionId(5)).unwrap(),
+    ]);
+
+    assert_eq!(
+        vec![
+            (
+                TransactionId(3),
+                TransactionError::DuplicateTransaction {
+                    tx_id: TransactionId(3),
+                    client_id: ClientId(1),
+                    amount: Some(dec!(1)),
+                }
+            ),
+            (
+                TransactionId(5),
+                TransactionError::FrozenAccount {
+                    tx_id: TransactionId(5),
+                    client_id: ClientId(2),
+                    amount: Some(dec!(1)),
+                }
+            ),
+        ],
+        errors
+    );
+
+    let client_1 = ledger.get_account(ClientId(1)).unwrap();
+    assert_eq!(dec!(3), client_1.available.amount());
+    assert_eq!(dec!(0), client_1.held.amount());
+    let client_2 = ledger.get_account(ClientId(2)).unwrap();
+    assert!(client_2.frozen);
+    assert_eq!(dec!(0), client_2.total_amount());
+}
+
+#[test]
+fn test_ledger_save_load_bincode() {
+    use rust_decimal_macros::dec;
+
+    let (ledger, errors) = build_ledger(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(5.1234)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(2)).unwrap(),
+    ]);
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+
+    let mut saved = Vec::new();
+    ledger.save_to_bincode(&mut saved).unwrap();
+    let mut ledger = Ledger::load_from_bincode(saved.as_slice()).unwrap();
+
+    ledger
+        .apply_transaction(
+            &Transaction::new(Type::Chargeback, ClientId(1), TransactionId(2)).unwrap(),
+        )
+        .unwrap();
+
+    let client_1 = ledger.get_account(ClientId(1)).unwrap();
+    assert!(client_1.frozen);
+    assert_eq!(dec!(10), client_1.available.amount());
+    assert_eq!(dec!(0), client_1.held.amount());
+    assert!(Ledger::load_from_bincode(&b"not a ledger"[..]).is_err());
+}
+
+#[test]
+fn test_ledger_load_invalid() {
+    assert!(Ledger::load_from_reader(&b"not a ledger"[..]).is_err());
+    assert!(Ledger::load_from_reader(&br#"{"accounts": {}}"#[..]).is_err());
+}
+
+#[test]
+// With a capacity of 1 for both the transactions and the errors, the ledger
+// can only make progress as they are sent and read.
+fn test_build_bounded_channels() {
+    use rust_decimal_macros::dec;
+
+    let (transactions_tx, transactions) = mpsc::sync_channel(1);
+    let producer = std::thread::spawn(move || {
+        for tx_id in 0..100 {
+            // Every other transaction is a withdrawal that fails.
+            let tx_type = if tx_id % 2 == 0 {
+                Type::Deposit(dec!(1))
+            } else {
+                Type::Withdrawal(dec!(1000))
             };
+            let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(tx_id)).unwrap();
+            transactions_tx.send(transaction).unwrap();
         }
+    });
+
+    let (errors_tx, errors) = mpsc::sync_channel(1);
+    let consumer = std::thread::spawn(move || errors.into_iter().count());
+
+    let (ledger, summary) = build(transactions, &errors_tx);
+    drop(errors_tx);
 
-        // We can only start sending account information once we have processed all the transactions.
-        for (client_id, account) in ledger {
-            accounts_tx.send((client_id, account)).unwrap(); // Would only fail if the rx is disconnected, which should not happen here.
+    producer.join().unwrap();
+    assert_eq!(50, consumer.join().unwrap());
+    assert_eq!(50, summary.transactions_processed);
+    assert_eq!(50, summary.transactions_failed);
+    assert_eq!(
+        dec!(50),
+        ledger.get_account(ClientId(1)).unwrap().available.amount()
+    );
+}
+
+#[test]
+// Independent clients should end up exactly the same as when the
+// transactions are applied sequentially.
+fn test_build_parallel() {
+    use rust_decimal_macros::dec;
+
+    let transactions = || {
+        let mut transactions = Vec::new();
+        for client_id in 0..50 {
+            let tx_id = |n: u32| TransactionId(u64::from(client_id * 10 + n));
+            for (tx_type, tx_id) in [
+                (Type::Deposit(dec!(10)), tx_id(0)),
+                (Type::Deposit(dec!(2.5)), tx_id(1)),
+                (Type::Withdrawal(dec!(4)), tx_id(2)),
+                (Type::Withdrawal(dec!(100)), tx_id(3)),
+                (Type::Dispute, tx_id(client_id % 3)),
+                (Type::Chargeback, tx_id(client_id % 5)),
+                (Type::Deposit(dec!(1)), tx_id(4)),
+            ] {
+                transactions.push(Transaction::new(tx_type, ClientId(client_id), tx_id).unwrap());
+            }
         }
+        transactions
+    };
+
+    let (sequential_ledger, sequential_errors) = build_ledger(transactions());
+
+    let (errors_tx, errors) = mpsc::sync_channel(350);
+    let (parallel_ledger, parallel_summary) =
+        build_parallel(transactions(), &errors_tx, LedgerConfig::default());
+    drop(errors_tx);
+    let parallel_errors: Vec<TransactionError> = errors.into_iter().collect();
+
+    assert_eq!(sequential_errors.len(), parallel_errors.len());
+    assert_eq!(
+        u64::try_from(sequential_errors.len()).unwrap(),
+        parallel_summary.transactions_failed
+    );
+    assert_eq!(50, parallel_summary.accounts_created);
+    assert_eq!(
+        sequential_ledger.frozen_accounts().count(),
+        usize::try_from(parallel_summary.accounts_frozen).unwrap()
+    );
+    assert_eq!(
+        sequential_ledger
+            .into_sorted_accounts()
+            .map(|(client_id, account)| (client_id, account.snapshot()))
+            .collect::<Vec<_>>(),
+        parallel_ledger
+            .into_sorted_accounts()
+            .map(|(client_id, account)| (client_id, account.snapshot()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_build_parallel_rejects_transfers() {
+    use rust_decimal_macros::dec;
+
+    let (errors_tx, errors) = mpsc::sync_channel(2);
+    let (ledger, summary) = build_parallel(
+        vec![
+            Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+            Transaction::new(
+                Type::Transfer {
+                    to_client: ClientId(2),
+                    amount: dec!(4),
+                },
+                ClientId(1),
+                TransactionId(2),
+            )
+            .unwrap(),
+        ],
+        &errors_tx,
+        LedgerConfig::default(),
+    );
+    drop(errors_tx);
+
+    assert_eq!(
+        vec![TransactionError::InvalidTransaction {
+            tx_id: TransactionId(2),
+            client_id: ClientId(1),
+            amount: Some(dec!(4)),
+        }],
+        errors.into_iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        BuildSummary {
+            transactions_processed: 1,
+            transactions_failed: 1,
+            accounts_created: 1,
+            accounts_frozen: 0,
+        },
+        summary
+    );
+    assert_eq!(
+        dec!(10),
+        ledger.get_account(ClientId(1)).unwrap().available.amount()
+    );
+}
+
+#[test]
+// A transaction ID used by a client can't be used by another one, even when
+// the clients are processed in parallel: the result should be the same as
+// when they are applied sequentially, with the same config.
+fn test_build_parallel_global_tx_ids() {
+    use rust_decimal_macros::dec;
+
+    let transactions = || {
+        [
+            (Type::Deposit(dec!(10)), 1, 1),
+            // Client 1 already used these IDs.
+            (Type::Deposit(dec!(5)), 2, 1),
+            (Type::Dispute, 2, 1),
+            (Type::Deposit(dec!(5)), 2, 2),
+            // Client 2 already used this ID.
+            (Type::Withdrawal(dec!(1)), 1, 2),
+            (Type::Withdrawal(dec!(1)), 1, 3),
+            (Type::Deposit(dec!(3)), 1, 4),
+            (Type::Deposit(dec!(3)), 1, 4),
+            (Type::Dispute, 1, 4),
+        ]
+        .into_iter()
+        .map(|(tx_type, client_id, tx_id)| {
+            Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap()
+        })
+    };
+    let config = LedgerConfig {
+        withdrawal_fee_bps: 100,
+        ..LedgerConfig::default()
+    };
+    let sorted_errors = |errors: mpsc::Receiver<TransactionError>| {
+        let mut errors: Vec<TransactionError> = errors.into_iter().collect();
+        errors.sort_by_key(|err| {
+            err.context()
+                .map(|(tx_id, client_id, _)| (tx_id, client_id))
+        });
+        errors
+    };
+
+    let (errors_tx, errors) = mpsc::sync_channel(9);
+    let (sequential_ledger, sequential_summary) =
+        build_with_config(transactions(), &errors_tx, config);
+    drop(errors_tx);
+    let sequential_errors = sorted_errors(errors);
+
+    let (errors_tx, errors) = mpsc::sync_channel(9);
+    let (parallel_ledger, parallel_summary) = build_parallel(transactions(), &errors_tx, config);
+    drop(errors_tx);
+
+    assert_eq!(4, sequential_errors.len());
+    assert_eq!(sequential_errors, sorted_errors(errors));
+    assert_eq!(sequential_summary, parallel_summary);
+    assert_eq!(
+        dec!(11.99),
+        parallel_ledger
+            .get_account(ClientId(1))
+            .unwrap()
+            .total_amount()
+    );
+    assert_eq!(
+        sequential_ledger
+            .into_sorted_accounts()
+            .map(|(client_id, account)| (client_id, account.snapshot()))
+            .collect::<Vec<_>>(),
+        parallel_ledger
+            .into_sorted_accounts()
+            .map(|(client_id, account)| (client_id, account.snapshot()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+// Each client's transactions are still applied in order, so the accounts
+// should end up exactly the same as when the transactions are applied
+// sequentially.
+fn test_build_concurrent() {
+    use rust_decimal_macros::dec;
+
+    // 1000 clients with 50 transactions each. Disputes, resolves and
+    // chargebacks reference the deposit made by the same client a few
+    // transactions before.
+    let transactions = || {
+        (0..50_000_u32).map(|n| {
+            let client_id = ClientId(n % 1000);
+            let tx_id = |offset: u32| TransactionId(u64::from(n - offset * 1000));
+            let (tx_type, tx_id) = match (n / 1000) % 10 {
+                0 | 3 | 6 => (Type::Deposit(dec!(10)), tx_id(0)),
+                1 | 4 => (Type::Withdrawal(dec!(7.5)), tx_id(0)),
+                2 | 8 => (Type::Withdrawal(dec!(100)), tx_id(0)),
+                5 => (Type::Dispute, tx_id(2)),
+                7 if client_id.0.is_multiple_of(2) => (Type::Resolve, tx_id(4)),
+                7 => (Type::Dispute, tx_id(1)),
+                _ => (Type::Chargeback, tx_id(3)),
+            };
+            Transaction::new(tx_type, client_id, tx_id).unwrap()
+        })
+    };
+
+    // The limits and fees apply in both cases.
+    let config = LedgerConfig {
+        withdrawal_fee_bps: 100,
+        max_account_balance: Some(dec!(25)),
+        ..LedgerConfig::default()
+    };
+
+    // Both builds send their errors to the same channel.
+    let (errors_tx, errors) = mpsc::sync_channel(2 * 50_000);
+    let (sequential_ledger, sequential_summary) =
+        build_with_config(transactions(), &errors_tx, config);
+    let (concurrent_ledger, concurrent_summary) =
+        build_concurrent(transactions(), 8, &errors_tx, config);
+    drop(errors_tx);
+
+    // Make sure the workload isn't trivial.
+    assert!(sequential_summary.transactions_failed > 0);
+    assert!(sequential_summary.accounts_frozen > 0);
+    assert_eq!(
+        2 * sequential_summary.transactions_failed,
+        u64::try_from(errors.into_iter().count()).unwrap()
+    );
+    assert_eq!(sequential_summary, concurrent_summary);
+    assert_eq!(
+        sequential_ledger
+            .into_sorted_accounts()
+            .map(|(client_id, account)| (client_id, account.snapshot()))
+            .collect::<Vec<_>>(),
+        concurrent_ledger
+            .into_sorted_accounts()
+            .map(|(client_id, account)| (client_id, account.snapshot()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+// When several clients use the same transaction ID at the same time, only one
+// of them should succeed.
+fn test_build_concurrent_tx_id_uniqueness() {
+    use rust_decimal_macros::dec;
+
+    let transactions = (0..1000).map(|client_id| {
+        Transaction::new(
+            Type::Deposit(dec!(10)),
+            ClientId(client_id),
+            TransactionId(1),
+        )
+        .unwrap()
+    });
+
+    let (errors_tx, errors) = mpsc::sync_channel(1000);
+    let (ledger, summary) = build_concurrent(transactions, 8, &errors_tx, LedgerConfig::default());
+    drop(errors_tx);
+
+    assert_eq!(1, summary.transactions_processed);
+    assert_eq!(999, summary.transactions_failed);
+    assert!(errors
+        .into_iter()
+        .all(|err| matches!(err, TransactionError::DuplicateTransaction { .. })));
+    assert_eq!(dec!(10), ledger.total_available().unwrap());
+}
+
+#[cfg(test)]
+fn build_ledger(transactions: Vec<Transaction>) -> (Ledger, Vec<TransactionError>) {
+    // There can't be more errors than transactions.
+    let (errors_tx, errors) = mpsc::sync_channel(transactions.len());
+    let (ledger, _) = build(transactions, &errors_tx);
+    drop(errors_tx);
+
+    (ledger, errors.into_iter().collect())
+}
+
+#[cfg(test)]
+fn build_accounts(
+    transactions: Vec<Transaction>,
+) -> (HashMap<ClientId, Account>, Vec<TransactionError>) {
+    let (ledger, errors) = build_ledger(transactions);
+    (ledger.accounts, errors)
+}
+
+#[test]
+fn test_transfer() {
+    use rust_decimal_macros::dec;
+
+    let transfer = |amount| Type::Transfer {
+        to_client: ClientId(2),
+        amount,
+    };
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(1)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(transfer(dec!(4)), ClientId(1), TransactionId(3)).unwrap(),
+    ]);
+
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+    assert_eq!(dec!(6), accounts[&ClientId(1)].available.amount());
+    assert_eq!(dec!(5), accounts[&ClientId(2)].available.amount());
+}
+
+#[test]
+// The recipient doesn't need to exist before the transfer.
+fn test_transfer_new_recipient() {
+    use rust_decimal_macros::dec;
+
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(
+            Type::Transfer {
+                to_client: ClientId(3),
+                amount: dec!(10),
+            },
+            ClientId(1),
+            TransactionId(2),
+        )
+        .unwrap(),
+    ]);
+
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+    assert_eq!(dec!(0), accounts[&ClientId(1)].available.amount());
+    assert_eq!(dec!(10), accounts[&ClientId(3)].available.amount());
+}
+
+#[test]
+fn test_transfer_not_enough_funds() {
+    use rust_decimal_macros::dec;
+
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(
+            Type::Transfer {
+                to_client: ClientId(2),
+                amount: dec!(10.5),
+            },
+            ClientId(1),
+            TransactionId(2),
+        )
+        .unwrap(),
+    ]);
+
+    assert_eq!(
+        vec![TransactionError::NotEnoughFunds {
+            tx_id: TransactionId(2),
+            client_id: ClientId(1),
+            amount: Some(dec!(10.5)),
+        }],
+        errors
+    );
+    assert_eq!(dec!(10), accounts[&ClientId(1)].available.amount());
+    assert!(!accounts.contains_key(&ClientId(2)));
+}
+
+#[test]
+// When the recipient side fails, the sender should get its funds back.
+fn test_transfer_rollback() {
+    use rust_decimal_macros::dec;
+
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(5)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Chargeback, ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(
+            Type::Transfer {
+                to_client: ClientId(2),
+                amount: dec!(4),
+            },
+            ClientId(1),
+            TransactionId(3),
+        )
+        .unwrap(),
+        // Transferring to oneself makes no sense.
+        Transaction::new(
+            Type::Transfer {
+                to_client: ClientId(1),
+                amount: dec!(4),
+            },
+            ClientId(1),
+            TransactionId(4),
+        )
+        .unwrap(),
+    ]);
+
+    assert_eq!(
+        vec![
+            TransactionError::FrozenAccount {
+                tx_id: TransactionId(3),
+                client_id: ClientId(2),
+                amount: Some(dec!(4)),
+            },
+            TransactionError::InvalidTransaction {
+                tx_id: TransactionId(4),
+                client_id: ClientId(1),
+                amount: Some(dec!(4)),
+            },
+        ],
+        errors
+    );
+    assert_eq!(dec!(10), accounts[&ClientId(1)].available.amount());
+    assert_eq!(1, accounts[&ClientId(1)].transaction_count());
+    assert_eq!(dec!(0), accounts[&ClientId(2)].available.amount());
+}
+
+#[test]
+// Transfers go through the same checks as deposits and withdrawals: a
+// recipient can't go over `max_account_balance`, and the sender gets its funds
+// back.
+fn test_transfer_max_account_balance() {
+    use rust_decimal_macros::dec;
+
+    let transfer = |amount, tx_id| {
+        Transaction::new(
+            Type::Transfer {
+                to_client: ClientId(2),
+                amount,
+            },
+            ClientId(1),
+            TransactionId(tx_id),
+        )
+        .unwrap()
+    };
+    let mut ledger = Ledger::new().with_config(LedgerConfig {
+        max_account_balance: Some(dec!(10)),
+        ..LedgerConfig::default()
     });
+    let errors = ledger.apply_transactions(&[
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(8)), ClientId(2), TransactionId(2)).unwrap(),
+        transfer(dec!(4), 3),
+    ]);
+
+    assert_eq!(
+        vec![(
+            TransactionId(3),
+            TransactionError::AmountTooLarge {
+                tx_id: TransactionId(3),
+                client_id: ClientId(2),
+                amount: Some(dec!(4)),
+            }
+        )],
+        errors
+    );
+    let sender = ledger.get_account(ClientId(1)).unwrap();
+    assert_eq!(dec!(10), sender.available.credit());
+    assert_eq!(dec!(0), sender.available.debit());
+    assert_eq!(1, sender.transaction_count());
+    assert_eq!(
+        dec!(8),
+        ledger.get_account(ClientId(2)).unwrap().total_amount()
+    );
+
+    // The transaction ID can be used again, up to the limit.
+    ledger.apply_transaction(&transfer(dec!(2), 3)).unwrap();
+    assert_eq!(
+        dec!(8),
+        ledger.get_account(ClientId(1)).unwrap().total_amount()
+    );
+    assert_eq!(
+        dec!(10),
+        ledger.get_account(ClientId(2)).unwrap().total_amount()
+    );
+}
+
+#[test]
+fn test_build_summary() {
+    use rust_decimal_macros::dec;
+
+    let transactions = [
+        (Type::Deposit(dec!(10)), 1, 1),
+        (Type::Withdrawal(dec!(20)), 1, 2),
+        (Type::Deposit(dec!(5)), 2, 3),
+        (Type::Dispute, 2, 3),
+        (Type::Chargeback, 2, 3),
+        (Type::Deposit(dec!(5)), 2, 4),
+        // The account is created, even if the transaction fails.
+        (Type::Dispute, 3, 5),
+    ]
+    .into_iter()
+    .map(|(tx_type, client_id, tx_id)| {
+        Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap()
+    });
+
+    let (errors_tx, errors) = mpsc::sync_channel(7);
+    let (ledger, summary) = build(transactions, &errors_tx);
+    drop(errors_tx);
+
+    assert_eq!(3, ledger.account_count());
+    assert_eq!(3, errors.into_iter().count());
+    assert_eq!(
+        BuildSummary {
+            transactions_processed: 4,
+            transactions_failed: 3,
+            accounts_created: 3,
+            accounts_frozen: 1,
+        },
+        summary
+    );
+}
+
+#[test]
+// With `stop_on_error`, the transactions after the first failed one should not
+// be applied.
+fn test_build_stop_on_error() {
+    use rust_decimal_macros::dec;
+
+    let transactions = [
+        (Type::Deposit(dec!(10)), 1, 1),
+        (Type::Withdrawal(dec!(20)), 1, 2),
+        (Type::Deposit(dec!(5)), 2, 3),
+        (Type::Withdrawal(dec!(20)), 2, 4),
+    ]
+    .into_iter()
+    .map(|(tx_type, client_id, tx_id)| {
+        Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap()
+    });
+
+    let (errors_tx, errors) = mpsc::sync_channel(4);
+    let config = LedgerConfig {
+        stop_on_error: true,
+        ..LedgerConfig::default()
+    };
+    let (ledger, summary) = build_with_config(transactions, &errors_tx, config);
+    drop(errors_tx);
+
+    assert_eq!(1, ledger.account_count());
+    assert_eq!(1, errors.into_iter().count());
+    assert_eq!(1, summary.transactions_processed);
+    assert_eq!(1, summary.transactions_failed);
+}
+
+#[test]
+// Two clients can't use the same transaction ID.
+fn test_global_transaction_id_uniqueness() {
+    use rust_decimal_macros::dec;
+
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(5)), ClientId(2), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(5)), ClientId(2), TransactionId(1)).unwrap(),
+        // A failed transaction doesn't use its ID...
+        Transaction::new(Type::Withdrawal(dec!(5)), ClientId(2), TransactionId(2)).unwrap(),
+        // ... so it can be used again.
+        Transaction::new(Type::Deposit(dec!(3)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+    ]);
+
+    assert_eq!(
+        vec![
+            TransactionError::DuplicateTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(5)),
+            },
+            TransactionError::DuplicateTransaction {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: Some(dec!(5)),
+            },
+            TransactionError::NotEnoughFunds {
+                tx_id: TransactionId(2),
+                client_id: ClientId(2),
+                amount: Some(dec!(5)),
+            },
+        ],
+        errors
+    );
+    assert_eq!(dec!(0), accounts[&ClientId(1)].available.amount());
+    assert_eq!(dec!(10), accounts[&ClientId(1)].held.amount());
+    assert_eq!(dec!(3), accounts[&ClientId(2)].available.amount());
+}
+
+#[test]
+// A client can't dispute a transaction created by another client.
+fn test_client_mismatch() {
+    use rust_decimal_macros::dec;
+
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(5)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(2), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(3), TransactionId(3)).unwrap(),
+    ]);
+
+    assert_eq!(
+        vec![
+            TransactionError::ClientMismatch {
+                tx_id: TransactionId(1),
+                client_id: ClientId(2),
+                amount: None,
+            },
+            TransactionError::UnknownTransaction {
+                tx_id: TransactionId(3),
+                client_id: ClientId(3),
+                amount: None,
+            },
+        ],
+        errors
+    );
+    assert_eq!(dec!(10), accounts[&ClientId(1)].available.amount());
+    assert_eq!(dec!(5), accounts[&ClientId(2)].available.amount());
+    assert_eq!(dec!(0), accounts[&ClientId(2)].held.amount());
+}
+
+#[test]
+// Transaction IDs used to be u32: make sure larger IDs are supported.
+fn test_large_transaction_id() {
+    use rust_decimal_macros::dec;
+
+    let tx_id = TransactionId(u64::from(u32::MAX) + 1);
+    let (accounts, errors) = build_accounts(vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), tx_id).unwrap(),
+        Transaction::new(
+            Type::Deposit(dec!(10)),
+            ClientId(1),
+            TransactionId(u64::MAX),
+        )
+        .unwrap(),
+        // Would collide with the first deposit if the ID was truncated.
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(0)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), tx_id).unwrap(),
+    ]);
 
-    rx
+    assert_eq!(Vec::<TransactionError>::new(), errors);
+    assert_eq!(dec!(20), accounts[&ClientId(1)].available.amount());
+    assert_eq!(dec!(10), accounts[&ClientId(1)].held.amount());
 }
@@ -1,20 +1,148 @@
 pub mod account;
+pub mod actor;
+pub mod budget;
+#[allow(clippy::module_inception)]
 pub mod ledger;
 pub mod transaction;
 
-// Using named types doesn't provide any compiler help, but it helps a lot with
-// readability.
+use serde::{Deserialize, Serialize};
+
+// Using named types helps a lot with readability.
 // Consider the following, when creating the ledger HashMap:
-// (1) ledger: HashMap<u16, Account>
+// (1) ledger: HashMap<u32, Account>
 // (2) ledger: HashMap<ClientId, Account>
 // Implementation (1) would most likely need comments, and could be confusing.
 // Implementation (2) is self-explanatory.
 // Besides, maintenance is easier: changing client ids e.g. from u16 to u32 is trivial.
-pub type ClientId = u16;
-pub type TransactionId = u32;
+// They are newtypes rather than type aliases, so the compiler catches a client
+// id used in place of a transaction id, or the other way around.
+// Serde sees them as plain integers.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ClientId(pub u32);
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct TransactionId(pub u64);
+
+impl std::fmt::Display for ClientId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for ClientId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl std::fmt::Display for TransactionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for TransactionId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+#[test]
+fn test_ids_display_and_from_str() {
+    assert_eq!("42", ClientId(42).to_string());
+    assert_eq!("42", TransactionId(42).to_string());
+    assert_eq!(Ok(ClientId(42)), "42".parse());
+    assert_eq!(Ok(TransactionId(u64::MAX)), u64::MAX.to_string().parse());
+    assert!("-1".parse::<ClientId>().is_err());
+    assert!("4294967296".parse::<ClientId>().is_err());
+    assert!("abc".parse::<TransactionId>().is_err());
+}
+
+#[test]
+// IDs are (de)serialized as plain integers.
+fn test_ids_serde() {
+    assert_eq!("42", serde_json::to_string(&ClientId(42)).unwrap());
+    assert_eq!("42", serde_json::to_string(&TransactionId(42)).unwrap());
+    assert_eq!(ClientId(42), serde_json::from_str("42").unwrap());
+    assert_eq!(TransactionId(42), serde_json::from_str("42").unwrap());
+}
 
 // I decided to use a decimal library instead of the built-in f32 type, to be
 // safer when dealing with money, and making the decimal precision easier to
 // deal with.
 pub type Amount = rust_decimal::Decimal;
-const DECIMAL_PRECISION: u32 = 4;
+
+/// Settings applied when creating and processing transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // Independent settings.
+pub struct LedgerConfig {
+    /// Number of decimal places amounts are rounded to.
+    pub decimal_precision: u32,
+
+    /// How amounts with more decimal places than `decimal_precision` are
+    /// rounded, e.g. banker's rounding (the default) or half up.
+    pub rounding_strategy: rust_decimal::RoundingStrategy,
+
+    /// Number of errors (invalid records and failed transactions together)
+    /// after which the processing is aborted. Unlimited by default.
+    ///
+    /// The invalid records are only counted when the parser and the ledger
+    /// share an `ErrorBudget` (see `ledger::build_with_budget()`).
+    pub max_errors: Option<usize>,
+
+    /// Whether the processing stops at the first transaction that can't be
+    /// applied. The transactions after it are ignored.
+    pub stop_on_error: bool,
+
+    /// Whether the transactions should only be validated, without keeping
+    /// their effect on the accounts (see `ledger::dry_run()`).
+    pub dry_run: bool,
+
+    /// Whether each client's deposits and withdrawals must have increasing
+    /// transaction IDs. An ID lower than or equal to the highest one seen so
+    /// far for the client is then rejected.
+    pub enforce_monotonic_tx_id: bool,
+
+    /// Fee taken on each withdrawal, in basis points of the amount withdrawn
+    /// (e.g. 25 = 0.25%). It is deducted from the available funds on top of
+    /// the amount. No fee by default.
+    pub withdrawal_fee_bps: u16,
+
+    /// Largest amount a single deposit can have. Unlimited by default.
+    pub max_deposit_amount: Option<Amount>,
+
+    /// Largest total (available and held) an account can reach through
+    /// deposits. Unlimited by default.
+    pub max_account_balance: Option<Amount>,
+
+    /// Whether a dispute can make the available funds negative, when they
+    /// were already withdrawn. Allowed by default.
+    pub allow_negative_available: bool,
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            decimal_precision: 4,
+            rounding_strategy: rust_decimal::RoundingStrategy::MidpointNearestEven,
+            max_errors: None,
+            stop_on_error: false,
+            dry_run: false,
+            enforce_monotonic_tx_id: false,
+            withdrawal_fee_bps: 0,
+            max_deposit_amount: None,
+            max_account_balance: None,
+            allow_negative_available: true,
+        }
+    }
+}
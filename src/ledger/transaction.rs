@@ -1,51 +1,562 @@
-use super::{Amount, ClientId, TransactionId};
+use super::{Amount, ClientId, LedgerConfig, TransactionId};
 
-#[derive(Debug, PartialEq)]
+use chrono::{DateTime, Utc};
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Amounts are serialized as strings, so no precision is lost.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Type {
     Withdrawal(Amount), // Add a debit to the available balance.
     Deposit(Amount),    // Add a credit to the available balance.
     Dispute,            // Move funds from available to held.
     Resolve,            // Move funds from held to available.
     Chargeback,         // Remove funds from held. Immediately freeze the account.
+    // Remove part of the disputed funds from held. The rest stays disputed,
+    // and the account is only frozen if nothing is left.
+    PartialChargeback(Amount),
+    // Move available funds to another client's account.
+    Transfer { to_client: ClientId, amount: Amount },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedTransaction")]
 pub struct Transaction {
+    #[serde(rename = "type")]
     pub(super) tx_type: Type,
+
+    #[serde(rename = "client")]
     pub(super) client_id: ClientId,
+
+    #[serde(rename = "tx")]
     pub(super) tx_id: TransactionId,
+
+    // Free-form text about the transaction. It doesn't change how the
+    // transaction is applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) memo: Option<String>,
+
+    // When the transaction happened, for audit trails. It doesn't change how
+    // the transaction is applied either: they are still applied in order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) timestamp: Option<DateTime<Utc>>,
+}
+
+// Deserialized transactions go through Transaction::new() as well, so they are
+// subject to the same checks.
+#[derive(Deserialize)]
+struct UncheckedTransaction {
+    #[serde(rename = "type")]
+    tx_type: Type,
+
+    #[serde(rename = "client")]
+    client_id: ClientId,
+
+    #[serde(rename = "tx")]
+    tx_id: TransactionId,
+
+    #[serde(default)]
+    memo: Option<String>,
+
+    #[serde(default)]
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<UncheckedTransaction> for Transaction {
+    type Error = &'static str;
+
+    fn try_from(tx: UncheckedTransaction) -> Result<Self, Self::Error> {
+        Ok(Self::new(tx.tx_type, tx.client_id, tx.tx_id)?
+            .with_memo(tx.memo)
+            .with_timestamp(tx.timestamp))
+    }
 }
 
 impl Transaction {
-    // The new() function ensures we can only create amounts with a decimal precision of 4.
-    pub fn new(tx_type: Type, client_id: ClientId, tx_id: TransactionId) -> Self {
+    /// Same as `with_config()`, using the default `LedgerConfig`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the amount is zero or negative once rounded.
+    pub fn new(
+        tx_type: Type,
+        client_id: ClientId,
+        tx_id: TransactionId,
+    ) -> Result<Self, &'static str> {
+        Self::with_config(tx_type, client_id, tx_id, LedgerConfig::default())
+    }
+
+    // The with_config() function ensures we can only create amounts with the
+    // configured decimal precision (4 by default).
+    // It also rejects deposits and withdrawals that don't have a positive amount: a
+    // "deposit" of -1000 would otherwise silently drain the account, and an empty
+    // deposit most likely hides a data quality issue.
+    /// Create a transaction, rounding its amount according to the config.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the amount is zero or negative once rounded.
+    pub fn with_config(
+        tx_type: Type,
+        client_id: ClientId,
+        tx_id: TransactionId,
+        config: LedgerConfig,
+    ) -> Result<Self, &'static str> {
         let tx_type = match tx_type {
-            Type::Deposit(amount) => Type::Deposit(amount.round_dp(super::DECIMAL_PRECISION)),
-            Type::Withdrawal(amount) => Type::Withdrawal(amount.round_dp(super::DECIMAL_PRECISION)),
+            Type::Deposit(amount) => Type::Deposit(round_positive(amount, config)?),
+            Type::Withdrawal(amount) => Type::Withdrawal(round_positive(amount, config)?),
+            Type::PartialChargeback(amount) => {
+                Type::PartialChargeback(round_positive(amount, config)?)
+            }
+            Type::Transfer { to_client, amount } => Type::Transfer {
+                to_client,
+                amount: round_positive(amount, config)?,
+            },
             _ => tx_type,
         };
 
-        Self {
+        Ok(Self {
             tx_type,
             client_id,
             tx_id,
+            memo: None,
+            timestamp: None,
+        })
+    }
+
+    /// Attach free-form text to the transaction.
+    #[must_use]
+    pub fn with_memo(mut self, memo: Option<String>) -> Self {
+        self.memo = memo;
+        self
+    }
+
+    #[must_use]
+    pub fn memo(&self) -> Option<&str> {
+        self.memo.as_deref()
+    }
+
+    /// Record when the transaction happened.
+    #[must_use]
+    pub const fn with_timestamp(mut self, timestamp: Option<DateTime<Utc>>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    #[must_use]
+    pub const fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    /// The amount of a deposit, withdrawal or transfer. Other transaction types
+    /// only reference an existing transaction, so they don't have an amount:
+    /// the amount of a partial chargeback is only part of the referenced one.
+    #[must_use]
+    pub const fn amount(&self) -> Option<Amount> {
+        match self.tx_type {
+            Type::Deposit(amount) | Type::Withdrawal(amount) | Type::Transfer { amount, .. } => {
+                Some(amount)
+            }
+            Type::Dispute | Type::Resolve | Type::Chargeback | Type::PartialChargeback(_) => None,
+        }
+    }
+
+    /// SHA-256 of the transaction content, used to detect transactions that
+    /// were delivered more than once.
+    ///
+    /// # Panics
+    ///
+    /// Never: serializing a transaction to JSON cannot fail.
+    #[must_use]
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let content = serde_json::to_vec(&(self.tx_id, self.client_id, &self.tx_type))
+            .expect("a transaction can always be serialized");
+
+        Sha256::digest(content).into()
+    }
+}
+
+impl Transaction {
+    /// Build a transaction for the given client and ID, e.g.
+    /// `Transaction::builder(client_id, tx_id).memo("salary").deposit(amount)`.
+    #[must_use]
+    pub fn builder(client_id: ClientId, tx_id: TransactionId) -> TransactionBuilder {
+        TransactionBuilder {
+            client_id,
+            tx_id,
+            config: LedgerConfig::default(),
+            memo: None,
+            timestamp: None,
         }
     }
 }
 
+/// Sets the optional fields of a `Transaction`, then creates it with the same
+/// checks as `Transaction::with_config()`, e.g. that amounts are positive.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    client_id: ClientId,
+    tx_id: TransactionId,
+    config: LedgerConfig,
+    memo: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl TransactionBuilder {
+    /// How the amount is rounded. Uses the default `LedgerConfig` otherwise.
+    #[must_use]
+    pub const fn config(mut self, config: LedgerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    #[must_use]
+    pub fn memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    #[must_use]
+    pub const fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the amount is not positive once rounded.
+    pub fn deposit(self, amount: Amount) -> Result<Transaction, &'static str> {
+        self.build(Type::Deposit(amount))
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the amount is not positive once rounded.
+    pub fn withdrawal(self, amount: Amount) -> Result<Transaction, &'static str> {
+        self.build(Type::Withdrawal(amount))
+    }
+
+    /// # Errors
+    ///
+    /// Never: a dispute has no amount to check.
+    pub fn dispute(self) -> Result<Transaction, &'static str> {
+        self.build(Type::Dispute)
+    }
+
+    /// # Errors
+    ///
+    /// Never: a resolve has no amount to check.
+    pub fn resolve(self) -> Result<Transaction, &'static str> {
+        self.build(Type::Resolve)
+    }
+
+    /// # Errors
+    ///
+    /// Never: a chargeback has no amount to check.
+    pub fn chargeback(self) -> Result<Transaction, &'static str> {
+        self.build(Type::Chargeback)
+    }
+
+    fn build(self, tx_type: Type) -> Result<Transaction, &'static str> {
+        Ok(
+            Transaction::with_config(tx_type, self.client_id, self.tx_id, self.config)?
+                .with_memo(self.memo)
+                .with_timestamp(self.timestamp),
+        )
+    }
+}
+
+// Rounding happens before the checks, so an amount too small to be
+// represented with our precision (e.g. 0.00001) is rejected as well.
+// Trailing zeros are removed, so equal amounts are always represented the
+// same way, e.g. in fingerprints.
+fn round_positive(amount: Amount, config: LedgerConfig) -> Result<Amount, &'static str> {
+    let amount = amount
+        .round_dp_with_strategy(config.decimal_precision, config.rounding_strategy)
+        .normalize();
+    if amount.is_zero() {
+        return Err("amount must not be zero");
+    }
+    if amount < dec!(0) {
+        return Err("amount must be positive");
+    }
+
+    Ok(amount)
+}
+
 #[test]
 // Decimal precision is 4 places. We should be unable to have more precise amounts.
 fn test_transaction_decimal_precision() {
-    use rust_decimal_macros::dec;
-
-    for (raw_amount, want_amount) in vec![
+    for (raw_amount, want_amount) in [
         (dec!(1.0), dec!(1.0)),
         (dec!(0.999999), dec!(1.0)),
         (dec!(1.0000001), dec!(1.0)),
         (dec!(1.2345), dec!(1.2345)),
         (dec!(1.23459), dec!(1.2346)),
     ] {
-        let tx = Transaction::new(Type::Withdrawal(raw_amount), 1, 1);
+        let tx =
+            Transaction::new(Type::Withdrawal(raw_amount), ClientId(1), TransactionId(1)).unwrap();
         assert_eq!(Type::Withdrawal(want_amount), tx.tx_type);
     }
 }
+
+#[test]
+// The decimal precision can be changed through the LedgerConfig.
+fn test_transaction_custom_decimal_precision() {
+    let config = LedgerConfig {
+        decimal_precision: 2,
+        ..LedgerConfig::default()
+    };
+    for (raw_amount, want) in [
+        (dec!(1.0), Ok(dec!(1.0))),
+        (dec!(1.234), Ok(dec!(1.23))),
+        (dec!(1.235), Ok(dec!(1.24))),
+        (dec!(0.001), Err("amount must not be zero")),
+    ] {
+        let got = Transaction::with_config(
+            Type::Deposit(raw_amount),
+            ClientId(1),
+            TransactionId(1),
+            config,
+        );
+        assert_eq!(want.map(Type::Deposit), got.map(|tx| tx.tx_type));
+    }
+}
+
+#[test]
+// Half up and banker's rounding only differ when the digit before the midpoint
+// is even.
+fn test_transaction_rounding_strategy() {
+    use rust_decimal::RoundingStrategy;
+
+    for (raw_amount, strategy, want_amount) in [
+        (
+            dec!(1.23455),
+            RoundingStrategy::MidpointAwayFromZero,
+            dec!(1.2346),
+        ),
+        (
+            dec!(1.23455),
+            RoundingStrategy::MidpointNearestEven,
+            dec!(1.2346),
+        ),
+        (
+            dec!(2.44545),
+            RoundingStrategy::MidpointAwayFromZero,
+            dec!(2.4455),
+        ),
+        (
+            dec!(2.44545),
+            RoundingStrategy::MidpointNearestEven,
+            dec!(2.4454),
+        ),
+        (dec!(2.44545), RoundingStrategy::ToZero, dec!(2.4454)),
+    ] {
+        let config = LedgerConfig {
+            rounding_strategy: strategy,
+            ..LedgerConfig::default()
+        };
+        let tx = Transaction::with_config(
+            Type::Deposit(raw_amount),
+            ClientId(1),
+            TransactionId(1),
+            config,
+        )
+        .unwrap();
+        assert_eq!(Type::Deposit(want_amount), tx.tx_type);
+    }
+}
+
+#[test]
+// Deposits and withdrawals must have a strictly positive amount.
+fn test_transaction_non_positive_amount() {
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    for (amount, want_err) in [
+        (dec!(-0.0001), "amount must be positive"),
+        (dec!(-1000), "amount must be positive"),
+        (dec!(0), "amount must not be zero"),
+        (dec!(0.00001), "amount must not be zero"),
+    ] {
+        assert_eq!(
+            Err(want_err),
+            Transaction::new(Type::Deposit(amount), ClientId(1), TransactionId(1))
+        );
+        assert_eq!(
+            Err(want_err),
+            Transaction::new(Type::Withdrawal(amount), ClientId(1), TransactionId(1))
+        );
+    }
+
+    let very_big_number = Decimal::from_str("70000000000000000000000000000").unwrap();
+    assert!(Transaction::new(
+        Type::Deposit(very_big_number),
+        ClientId(1),
+        TransactionId(1)
+    )
+    .is_ok());
+    assert!(Transaction::new(
+        Type::Withdrawal(very_big_number),
+        ClientId(1),
+        TransactionId(1)
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_transaction_builder() {
+    let timestamp: DateTime<Utc> = "2024-01-31T12:00:00Z".parse().unwrap();
+    let tx = Transaction::builder(ClientId(1), TransactionId(2))
+        .memo("salary")
+        .timestamp(timestamp)
+        .deposit(dec!(1.23456))
+        .unwrap();
+    assert_eq!(
+        Transaction::new(Type::Deposit(dec!(1.2346)), ClientId(1), TransactionId(2))
+            .unwrap()
+            .with_memo(Some("salary".to_string()))
+            .with_timestamp(Some(timestamp)),
+        tx
+    );
+
+    for (got, want_type) in [
+        (
+            Transaction::builder(ClientId(1), TransactionId(2)).withdrawal(dec!(3)),
+            Type::Withdrawal(dec!(3)),
+        ),
+        (
+            Transaction::builder(ClientId(1), TransactionId(2)).dispute(),
+            Type::Dispute,
+        ),
+        (
+            Transaction::builder(ClientId(1), TransactionId(2)).resolve(),
+            Type::Resolve,
+        ),
+        (
+            Transaction::builder(ClientId(1), TransactionId(2)).chargeback(),
+            Type::Chargeback,
+        ),
+    ] {
+        assert_eq!(
+            Ok(Transaction::new(want_type, ClientId(1), TransactionId(2)).unwrap()),
+            got
+        );
+    }
+
+    // The amounts are checked like with Transaction::new().
+    let builder = Transaction::builder(ClientId(1), TransactionId(2));
+    assert_eq!(
+        Err("amount must be positive"),
+        builder.clone().deposit(dec!(-1))
+    );
+    assert_eq!(Err("amount must not be zero"), builder.withdrawal(dec!(0)));
+
+    let config = LedgerConfig {
+        decimal_precision: 2,
+        ..LedgerConfig::default()
+    };
+    let tx = Transaction::builder(ClientId(1), TransactionId(2))
+        .config(config)
+        .deposit(dec!(1.234))
+        .unwrap();
+    assert_eq!(Some(dec!(1.23)), tx.amount());
+}
+
+#[test]
+fn test_transaction_serde_round_trip() {
+    for tx_type in [
+        Type::Deposit(dec!(1.2345)),
+        Type::Withdrawal(dec!(100)),
+        Type::Dispute,
+        Type::Resolve,
+        Type::Chargeback,
+        Type::Transfer {
+            to_client: ClientId(3),
+            amount: dec!(4.5),
+        },
+    ] {
+        let tx = Transaction::new(tx_type, ClientId(1), TransactionId(2)).unwrap();
+        let json = serde_json::to_string(&tx).unwrap();
+        assert_eq!(tx, serde_json::from_str(&json).unwrap(), "{json}");
+    }
+
+    let tx = Transaction::new(Type::Deposit(dec!(1.5)), ClientId(1), TransactionId(2)).unwrap();
+    assert_eq!(
+        r#"{"type":{"deposit":"1.5"},"client":1,"tx":2}"#,
+        serde_json::to_string(&tx).unwrap()
+    );
+    let tx = Transaction::new(Type::Dispute, ClientId(1), TransactionId(2)).unwrap();
+    assert_eq!(
+        r#"{"type":"dispute","client":1,"tx":2}"#,
+        serde_json::to_string(&tx).unwrap()
+    );
+}
+
+#[test]
+// Deserialization shouldn't bypass the checks from Transaction::new().
+fn test_transaction_deserialize_invalid() {
+    for json in [
+        r#"{"type":{"deposit":"-1.5"},"client":1,"tx":2}"#,
+        r#"{"type":{"withdrawal":"0"},"client":1,"tx":2}"#,
+        r#"{"type":"refund","client":1,"tx":2}"#,
+    ] {
+        assert!(serde_json::from_str::<Transaction>(json).is_err(), "{json}");
+    }
+
+    let tx: Transaction =
+        serde_json::from_str(r#"{"type":{"deposit":"1.23456"},"client":1,"tx":2}"#).unwrap();
+    assert_eq!(Some(dec!(1.2346)), tx.amount());
+}
+
+#[test]
+fn test_transaction_fingerprint() {
+    let tx = Transaction::new(Type::Deposit(dec!(1.5)), ClientId(1), TransactionId(2)).unwrap();
+    let same_tx =
+        Transaction::new(Type::Deposit(dec!(1.5)), ClientId(1), TransactionId(2)).unwrap();
+    assert_eq!(tx.fingerprint(), same_tx.fingerprint());
+    // Trailing zeros don't change the amount.
+    let same_tx =
+        Transaction::new(Type::Deposit(dec!(1.5000)), ClientId(1), TransactionId(2)).unwrap();
+    assert_eq!(tx.fingerprint(), same_tx.fingerprint());
+
+    for other_tx in [
+        Transaction::new(Type::Deposit(dec!(1.6)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(1.5)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(1.5)), ClientId(2), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Deposit(dec!(1.5)), ClientId(1), TransactionId(3)).unwrap(),
+        // Client and transaction IDs swapped.
+        Transaction::new(Type::Deposit(dec!(1.5)), ClientId(2), TransactionId(1)).unwrap(),
+    ] {
+        assert_ne!(tx.fingerprint(), other_tx.fingerprint(), "{other_tx:?}");
+    }
+}
+
+#[test]
+fn test_transaction_hash() {
+    use std::collections::HashSet;
+
+    let mut transactions = HashSet::new();
+    for (tx_type, client_id, tx_id) in [
+        (Type::Deposit(dec!(1.5)), 1, 1),
+        (Type::Deposit(dec!(2)), 1, 1),
+        (Type::Withdrawal(dec!(1.5)), 1, 1),
+        (Type::Dispute, 1, 1),
+        (Type::Dispute, 2, 1),
+        (Type::Dispute, 1, 2),
+    ] {
+        assert!(transactions
+            .insert(Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap()));
+    }
+    assert_eq!(6, transactions.len());
+
+    // Inserting the same transaction again doesn't change the set.
+    assert!(!transactions.insert(
+        Transaction::new(Type::Deposit(dec!(1.5)), ClientId(1), TransactionId(1)).unwrap()
+    ));
+    assert!(!transactions
+        .insert(Transaction::new(Type::Dispute, ClientId(2), TransactionId(1)).unwrap()));
+    assert_eq!(6, transactions.len());
+}
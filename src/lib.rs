@@ -1,7 +1,13 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+// Duplicated transitive dependencies are out of our control.
+#![allow(clippy::multiple_crate_versions)]
 
-mod error_handler;
-mod input;
-mod ledger;
-mod output;
+pub mod error_handler;
+pub mod input;
+pub mod ledger;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod output;
+pub mod report;
 pub mod run;
+pub mod strategy;
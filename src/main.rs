@@ -1,13 +1,181 @@
-use std::fs::File;
-use transaction_engine::run::run;
+use clap::Parser;
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
+use transaction_engine::{
+    input::{self, ParseConfig},
+    ledger::LedgerConfig,
+    output::{OutputConfig, OutputFormat},
+    run::{self, run_with_strategy},
+    strategy::{ErrorStrategy, LogStrategy, SinkStrategy},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// The transactions to process. They are read from stdin if omitted.
+    input_file: Option<String>,
+
+    /// Write the accounts to this file instead of stdout.
+    #[arg(long, value_name = "PATH")]
+    output: Option<String>,
+
+    /// The format of both the transactions and the accounts: csv, tsv or ndjson.
+    // Parsed by hand, so an unknown format is reported like the other errors.
+    #[arg(long, default_value = "csv")]
+    format: String,
+
+    /// The format of the transactions, if different from --format.
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<String>,
+
+    /// The format of the accounts, if different from --format.
+    #[arg(long, value_name = "FORMAT")]
+    output_format: Option<String>,
+
+    /// Don't write the accounts without any funds, unless they are frozen.
+    #[arg(long)]
+    skip_empty: bool,
+
+    /// Abort the processing on the first error.
+    #[arg(long)]
+    strict: bool,
+
+    /// Abort the processing once this many records couldn't be read or applied.
+    #[arg(long, value_name = "N")]
+    max_errors: Option<usize>,
+
+    /// Only validate the transactions: the ones that would fail are written
+    /// instead of the accounts.
+    #[arg(long)]
+    dry_run: bool,
+}
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    assert!(args.len() >= 2);
-    let filename = &args[1];
+    // The errors are logged as warnings, e.g. with `RUST_LOG=warn`.
+    env_logger::init();
+    // On SIGINT or SIGTERM, the accounts processed so far are still written.
+    run::handle_shutdown_signals().expect("could not handle the shutdown signals");
+
+    let Args {
+        input_file: filename,
+        output: output_path,
+        format,
+        input_format,
+        output_format,
+        skip_empty,
+        strict,
+        max_errors,
+        dry_run,
+    } = Args::parse();
+    let parse_format = |format: &str| -> OutputFormat {
+        format
+            .parse()
+            .unwrap_or_else(|err: String| exit_with_error(&err))
+    };
+    let input_format = parse_format(input_format.as_deref().unwrap_or(&format));
+    let output_format = parse_format(output_format.as_deref().unwrap_or(&format));
+
+    // Without a file name, we read the transactions from stdin instead,
+    // e.g. `cat transactions.csv | cargo run > accounts.csv`.
+    let input_stream: Box<dyn Read + Send> = match filename {
+        Some(filename) => match File::open(&filename) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                exit_with_error(&format!("could not open the input file {filename}: {err}"))
+            }
+        },
+        None => Box::new(std::io::stdin()),
+    };
 
-    let input_stream = File::open(filename).expect("could not open the file");
-    let output_stream = std::io::stdout();
+    // An existing output file is truncated.
+    let output_stream: Box<dyn Write> = match output_path {
+        Some(path) => match File::create(&path) {
+            Ok(file) => Box::new(file),
+            Err(err) => exit_with_error(&format!("could not create the output file {path}: {err}")),
+        },
+        None => Box::new(std::io::stdout()),
+    };
+
+    let output_config = OutputConfig {
+        format: output_format,
+        skip_zero_balance: skip_empty,
+        ..OutputConfig::default()
+    };
+    // In strict mode, the processing is aborted on the first error: the
+    // records after an invalid one are not read, the transactions after a
+    // failed one are not applied, and the accounts processed so far are still
+    // written. The error is printed as well.
+    let error_strategy: Box<dyn ErrorStrategy> = if strict {
+        Box::new(LogStrategy::default())
+    } else {
+        Box::new(SinkStrategy)
+    };
+    let ledger_config = LedgerConfig {
+        max_errors,
+        stop_on_error: strict,
+        dry_run,
+        ..LedgerConfig::default()
+    };
+    let parse_config = ParseConfig::builder()
+        .stop_on_error(strict)
+        .max_errors(max_errors);
+
+    let result = match input_format {
+        OutputFormat::Csv => run_with_strategy(
+            input_stream,
+            output_stream,
+            |input_stream, budget| {
+                input::parse_with_budget(input_stream, parse_config.build(), budget)
+            },
+            output_config,
+            error_strategy,
+            ledger_config,
+        ),
+        OutputFormat::Tsv => run_with_strategy(
+            input_stream,
+            output_stream,
+            |input_stream, budget| {
+                input::parse_with_budget(
+                    input_stream,
+                    parse_config.delimiter(b'\t').build(),
+                    budget,
+                )
+            },
+            output_config,
+            error_strategy,
+            ledger_config,
+        ),
+        OutputFormat::Ndjson => run_with_strategy(
+            input_stream,
+            output_stream,
+            |input_stream, budget| {
+                input::parse_ndjson_with_budget(input_stream, parse_config.build(), budget)
+            },
+            output_config,
+            error_strategy,
+            ledger_config,
+        ),
+    };
+
+    // The output is still written when some records or transactions are
+    // invalid, but we exit with a non-zero code so CI pipelines can detect it.
+    match result {
+        Ok(run_result) => {
+            eprintln!("{run_result}");
+            if run::shutdown_requested() {
+                exit_with_error("interrupted: the remaining transactions were not processed");
+            }
+            if run_result.parse_errors > 0 || run_result.transactions_failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Err(err) => exit_with_error(&format!("failed to write the output: {err}")),
+    }
+}
 
-    run(input_stream, output_stream);
+fn exit_with_error(msg: &str) -> ! {
+    eprintln!("{msg}");
+    std::process::exit(1);
 }
@@ -0,0 +1,77 @@
+//! Prometheus metrics about the transactions applied to the ledgers, e.g. to
+//! be served on `/metrics` by a service embedding the engine.
+//!
+//! The metrics are registered in the default registry, so they are also
+//! returned by `prometheus::gather()`.
+
+use crate::ledger::Amount;
+
+use prometheus::{
+    register_counter, register_int_counter, register_int_counter_vec, Counter, Encoder, IntCounter,
+    IntCounterVec, TextEncoder,
+};
+use rust_decimal::prelude::ToPrimitive;
+use std::sync::LazyLock;
+
+static TRANSACTIONS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "transactions_total",
+        "Transactions applied to a ledger, by status (ok or error).",
+        &["status"]
+    )
+    .expect("the metric should only be registered once")
+});
+
+static ACCOUNTS_FROZEN_TOTAL: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "accounts_frozen_total",
+        "Accounts frozen after a chargeback."
+    )
+    .expect("the metric should only be registered once")
+});
+
+static DISPUTED_AMOUNT_TOTAL: LazyLock<Counter> = LazyLock::new(|| {
+    register_counter!(
+        "disputed_amount_total",
+        "Sum of the amounts of the disputed transactions."
+    )
+    .expect("the metric should only be registered once")
+});
+
+/// Record a transaction applied to a ledger: whether it succeeded, whether it
+/// froze the account, and the amount it disputed if any.
+pub(crate) fn record_transaction(ok: bool, froze_account: bool, disputed_amount: Option<Amount>) {
+    if !ok {
+        TRANSACTIONS_TOTAL.with_label_values(&["error"]).inc();
+        return;
+    }
+    TRANSACTIONS_TOTAL.with_label_values(&["ok"]).inc();
+
+    if froze_account {
+        ACCOUNTS_FROZEN_TOTAL.inc();
+    }
+    if let Some(amount) = disputed_amount {
+        // Prometheus counters are floats: the amount loses some precision.
+        DISPUTED_AMOUNT_TOTAL.inc_by(amount.to_f64().unwrap_or_default());
+    }
+}
+
+/// All the metrics of the default registry, in the Prometheus text format.
+///
+/// # Panics
+///
+/// Panics if the metrics can't be encoded, which only happens if a metric was
+/// registered with an invalid name.
+#[must_use]
+pub fn get_metrics() -> String {
+    // The metrics only appear once they have been used at least once.
+    LazyLock::force(&TRANSACTIONS_TOTAL);
+    LazyLock::force(&ACCOUNTS_FROZEN_TOTAL);
+    LazyLock::force(&DISPUTED_AMOUNT_TOTAL);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("the metrics should be valid");
+    String::from_utf8(buffer).expect("the text format should be UTF-8")
+}
@@ -1,20 +1,23 @@
-use crate::ledger::{account::account::Account, Amount, ClientId};
+use crate::ledger::{
+    account::account::{Account, TransactionError},
+    Amount, ClientId, TransactionId,
+};
 
-use serde::Serialize;
-use std::sync::mpsc::Receiver;
+use serde::{Serialize, Serializer};
+use std::str::FromStr;
 
 #[derive(Serialize)]
 struct AccountRecord {
     #[serde(rename = "client")]
     client_id: ClientId,
 
-    #[serde(rename = "available")]
+    #[serde(rename = "available", serialize_with = "serialize_amount")]
     available_amount: Amount,
 
-    #[serde(rename = "held")]
+    #[serde(rename = "held", serialize_with = "serialize_amount")]
     held_amount: Amount,
 
-    #[serde(rename = "total")]
+    #[serde(rename = "total", serialize_with = "serialize_amount")]
     total_amount: Amount,
 
     #[serde(rename = "locked")]
@@ -33,12 +36,143 @@ impl AccountRecord {
     }
 }
 
-// Writes the received accounts to the given stream.
+/// Formats an amount with exactly 4 decimal places, e.g. `1` as `1.0000`.
+/// Amounts with more decimal places are rounded.
+#[must_use]
+pub fn format_amount(amount: Amount) -> String {
+    format!("{:.4}", amount.round_dp(4))
+}
+
+// Decimals are serialized without trailing zeros, e.g. `5.0` rather than
+// `5.0000`: always write the 4 decimal places instead.
+// They are written as strings in JSON as well: JSON numbers are commonly
+// parsed as floating point numbers, which could silently round the amounts.
+#[allow(clippy::trivially_copy_pass_by_ref)] // Required by serde.
+fn serialize_amount<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_amount(*amount))
+}
+
+/// The format the accounts are written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Delimiter-separated values, using `OutputConfig::delimiter`.
+    #[default]
+    Csv,
+
+    /// Tab-separated values.
+    Tsv,
+
+    /// Newline-delimited JSON, one object per account.
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!("unknown format {s}, expected csv, tsv or ndjson")),
+        }
+    }
+}
+
+/// How the accounts should be written by `write_with_format()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputConfig {
+    pub format: OutputFormat,
+
+    /// Single byte separating the fields, only used by the CSV format.
+    pub delimiter: u8,
+
+    /// Skip the accounts with no funds at all, unless they are frozen.
+    pub skip_zero_balance: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Csv,
+            delimiter: b',',
+            skip_zero_balance: false,
+        }
+    }
+}
+
+/// Writes the given accounts to the given stream, in the configured format.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+pub fn write_with_format(
+    output_stream: impl std::io::Write,
+    accounts: impl IntoIterator<Item = (ClientId, Account)>,
+    config: OutputConfig,
+) -> Result<(), std::io::Error> {
+    let accounts = accounts
+        .into_iter()
+        .filter(|(_, account)| !config.skips(account));
+
+    match config.format {
+        OutputFormat::Csv => write_delimited(output_stream, accounts, config.delimiter),
+        OutputFormat::Tsv => write_delimited(output_stream, accounts, b'\t'),
+        OutputFormat::Ndjson => write_json_lines(output_stream, accounts),
+    }
+}
+
+impl OutputConfig {
+    // Whether the account is left out of the output.
+    pub(crate) fn skips(self, account: &Account) -> bool {
+        self.skip_zero_balance && is_empty(account)
+    }
+}
+
+// A frozen account is never considered empty: it is worth reporting even
+// without any funds left, e.g. after a chargeback.
+fn is_empty(account: &Account) -> bool {
+    !account.frozen
+        && account.available.is_zero()
+        && account.held.is_zero()
+        && account.total_amount().is_zero()
+}
+
+/// Writes the given accounts to the given stream.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
 pub fn write(
     output_stream: impl std::io::Write,
-    accounts: Receiver<(ClientId, Account)>,
+    accounts: impl IntoIterator<Item = (ClientId, Account)>,
 ) -> Result<(), std::io::Error> {
-    let mut writer = csv::Writer::from_writer(output_stream);
+    write_delimited(output_stream, accounts.into_iter(), b',')
+}
+
+/// Same as `write()`, but only writes the accounts for which `filter` returns
+/// `true`, e.g. `|account| account.frozen` to only write the frozen accounts.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+pub fn write_filtered(
+    output_stream: impl std::io::Write,
+    accounts: impl IntoIterator<Item = (ClientId, Account)>,
+    filter: impl Fn(&Account) -> bool,
+) -> Result<(), std::io::Error> {
+    let accounts = accounts.into_iter().filter(|(_, account)| filter(account));
+    write_delimited(output_stream, accounts, b',')
+}
+
+fn write_delimited(
+    output_stream: impl std::io::Write,
+    accounts: impl Iterator<Item = (ClientId, Account)>,
+    delimiter: u8,
+) -> Result<(), std::io::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(output_stream);
 
     for (client_id, account) in accounts {
         let record = AccountRecord::new(client_id, &account);
@@ -48,18 +182,119 @@ pub fn write(
     Ok(())
 }
 
+/// Writes the given accounts to the given stream as newline-delimited
+/// JSON (NDJSON), one JSON object per account.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+pub fn write_ndjson(
+    output_stream: impl std::io::Write,
+    accounts: impl IntoIterator<Item = (ClientId, Account)>,
+) -> Result<(), std::io::Error> {
+    write_json_lines(output_stream, accounts.into_iter())
+}
+
+fn write_json_lines(
+    mut output_stream: impl std::io::Write,
+    accounts: impl Iterator<Item = (ClientId, Account)>,
+) -> Result<(), std::io::Error> {
+    for (client_id, account) in accounts {
+        let record = AccountRecord::new(client_id, &account);
+        serde_json::to_writer(&mut output_stream, &record)?;
+        output_stream.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Writes the given accounts to the given stream as a single JSON array.
+///
+/// Unlike the other formats, the accounts are only written once they have
+/// all been read.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+pub fn write_json_array(
+    output_stream: impl std::io::Write,
+    accounts: impl IntoIterator<Item = (ClientId, Account)>,
+) -> Result<(), std::io::Error> {
+    let records: Vec<AccountRecord> = accounts
+        .into_iter()
+        .map(|(client_id, account)| AccountRecord::new(client_id, &account))
+        .collect();
+
+    serde_json::to_writer(output_stream, &records)?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct FailureRecord {
+    tx: TransactionId,
+    error: String,
+}
+
+/// Writes the transactions that failed and why, e.g. as returned by
+/// `ledger::dry_run()`, instead of the accounts.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+pub fn write_failures(
+    mut output_stream: impl std::io::Write,
+    failures: impl IntoIterator<Item = (TransactionId, TransactionError)>,
+    config: OutputConfig,
+) -> Result<(), std::io::Error> {
+    let records = failures.into_iter().map(|(tx, err)| FailureRecord {
+        tx,
+        error: err.to_string(),
+    });
+
+    match config.format {
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if config.format == OutputFormat::Tsv {
+                b'\t'
+            } else {
+                config.delimiter
+            };
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(output_stream);
+            // Unlike `serialize()`, always writes the header row.
+            writer.write_record(["tx", "error"])?;
+            for record in records {
+                writer.write_record([record.tx.to_string(), record.error])?;
+            }
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut output_stream, &record)?;
+                output_stream.write_all(b"\n")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod write_tests {
-    use crate::ledger::account::{account::Account, balance::Balance};
+    use super::{OutputConfig, OutputFormat};
+    use crate::ledger::{
+        account::{account::Account, balance::Balance},
+        Amount, ClientId,
+    };
 
     use rust_decimal_macros::dec;
-    use std::sync::mpsc;
+    use std::sync::mpsc::{self, Receiver};
 
     #[test]
     fn test_write_accounts() {
         let (accounts_tx, accounts) = mpsc::channel();
         let mut output_stream = Vec::new();
-        for account in vec![
+        for account in [
             (1, dec!(5.0), dec!(1.0), false),
             (2, dec!(1.234), dec!(123.4), false),
             (3, dec!(500.005), dec!(600.006), true),
@@ -69,17 +304,351 @@ mod write_tests {
             account.available = Balance::new(available, dec!(0));
             account.held = Balance::new(held, dec!(0));
             account.frozen = frozen;
-            accounts_tx.send((client_id, account)).unwrap();
+            accounts_tx.send((ClientId(client_id), account)).unwrap();
         }
         drop(accounts_tx);
 
         super::write(&mut output_stream, accounts).unwrap();
 
-        let want = r#"client,available,held,total,locked
-1,5.0,1.0,6.0,false
-2,1.234,123.4,124.634,false
-3,500.005,600.006,1100.011,true
-"#;
+        let want = r"client,available,held,total,locked
+1,5.0000,1.0000,6.0000,false
+2,1.2340,123.4000,124.6340,false
+3,500.0050,600.0060,1100.0110,true
+";
         assert_eq!(want.to_string(), String::from_utf8(output_stream).unwrap(),);
     }
+
+    #[test]
+    fn test_write_accounts_ndjson() {
+        let (accounts_tx, accounts) = mpsc::channel();
+        let mut output_stream = Vec::new();
+        for account in [
+            (1, dec!(5.0), dec!(1.0), false),
+            (2, dec!(1.234), dec!(123.4), false),
+            (3, dec!(500.005), dec!(600.006), true),
+            // e.g. a dispute on a deposit that has already been withdrawn.
+            (4, dec!(-2.5), dec!(5), false),
+        ] {
+            let (client_id, available, held, frozen) = account;
+            let mut account = Account::new();
+            account.available = Balance::new(available, dec!(0));
+            account.held = Balance::new(held, dec!(0));
+            account.frozen = frozen;
+            accounts_tx.send((ClientId(client_id), account)).unwrap();
+        }
+        drop(accounts_tx);
+
+        super::write_ndjson(&mut output_stream, accounts).unwrap();
+
+        let want = r#"{"client":1,"available":"5.0000","held":"1.0000","total":"6.0000","locked":false}
+{"client":2,"available":"1.2340","held":"123.4000","total":"124.6340","locked":false}
+{"client":3,"available":"500.0050","held":"600.0060","total":"1100.0110","locked":true}
+{"client":4,"available":"-2.5000","held":"5.0000","total":"2.5000","locked":false}
+"#;
+        assert_eq!(want.to_string(), String::from_utf8(output_stream).unwrap());
+    }
+
+    fn some_accounts() -> Receiver<(ClientId, Account)> {
+        let (accounts_tx, accounts) = mpsc::channel();
+        for (client_id, available, held, frozen) in [
+            (1, dec!(5.0), dec!(1.0), false),
+            (2, dec!(1.234), dec!(123.4), false),
+            (3, dec!(-500.005), dec!(600.006), true),
+        ] {
+            let mut account = Account::new();
+            account.available = Balance::new(available, dec!(0));
+            account.held = Balance::new(held, dec!(0));
+            account.frozen = frozen;
+            accounts_tx.send((ClientId(client_id), account)).unwrap();
+        }
+
+        accounts
+    }
+
+    // Read the written accounts back, regardless of the format.
+    fn read_back(
+        format: OutputFormat,
+        output: &str,
+    ) -> Vec<(ClientId, Amount, Amount, Amount, bool)> {
+        match format {
+            OutputFormat::Csv | OutputFormat::Tsv => csv::ReaderBuilder::new()
+                .delimiter(if format == OutputFormat::Tsv {
+                    b'\t'
+                } else {
+                    b','
+                })
+                .from_reader(output.as_bytes())
+                .deserialize()
+                .map(Result::unwrap)
+                .collect(),
+            OutputFormat::Ndjson => output
+                .lines()
+                .map(|line| {
+                    let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                    let amount = |field: &str| value[field].as_str().unwrap().parse().unwrap();
+                    (
+                        ClientId(u32::try_from(value["client"].as_u64().unwrap()).unwrap()),
+                        amount("available"),
+                        amount("held"),
+                        amount("total"),
+                        value["locked"].as_bool().unwrap(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    // Whatever the format, the same values should be written.
+    fn test_write_with_format() {
+        let want = vec![
+            (ClientId(1), dec!(5), dec!(1), dec!(6), false),
+            (ClientId(2), dec!(1.234), dec!(123.4), dec!(124.634), false),
+            (
+                ClientId(3),
+                dec!(-500.005),
+                dec!(600.006),
+                dec!(100.001),
+                true,
+            ),
+        ];
+
+        for format in [OutputFormat::Csv, OutputFormat::Tsv, OutputFormat::Ndjson] {
+            let mut output_stream = Vec::new();
+            let config = OutputConfig {
+                format,
+                ..OutputConfig::default()
+            };
+            super::write_with_format(&mut output_stream, some_accounts(), config).unwrap();
+
+            let output = String::from_utf8(output_stream).unwrap();
+            assert_eq!(want, read_back(format, &output), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_write_with_custom_delimiter() {
+        let mut output_stream = Vec::new();
+        let config = OutputConfig {
+            format: OutputFormat::Csv,
+            delimiter: b';',
+            ..OutputConfig::default()
+        };
+        super::write_with_format(&mut output_stream, some_accounts(), config).unwrap();
+
+        let want = "client;available;held;total;locked
+1;5.0000;1.0000;6.0000;false
+2;1.2340;123.4000;124.6340;false
+3;-500.0050;600.0060;100.0010;true
+";
+        assert_eq!(want, String::from_utf8(output_stream).unwrap());
+    }
+
+    #[test]
+    fn test_write_filtered() {
+        let mut output_stream = Vec::new();
+        super::write_filtered(&mut output_stream, some_accounts(), |account| {
+            account.frozen
+        })
+        .unwrap();
+
+        let want = "client,available,held,total,locked
+3,-500.0050,600.0060,100.0010,true
+";
+        assert_eq!(want, String::from_utf8(output_stream).unwrap());
+    }
+
+    #[test]
+    fn test_write_filtered_non_zero_held() {
+        let (accounts_tx, accounts) = mpsc::channel();
+        for (client_id, held) in [(1, dec!(0)), (2, dec!(1.5)), (3, dec!(0))] {
+            let mut account = Account::new();
+            account.held = Balance::new(held, dec!(0));
+            accounts_tx.send((ClientId(client_id), account)).unwrap();
+        }
+        drop(accounts_tx);
+
+        let mut output_stream = Vec::new();
+        super::write_filtered(&mut output_stream, accounts, |account| {
+            !account.held.amount().is_zero()
+        })
+        .unwrap();
+
+        let want = "client,available,held,total,locked
+2,0.0000,1.5000,1.5000,false
+";
+        assert_eq!(want, String::from_utf8(output_stream).unwrap());
+    }
+
+    #[test]
+    fn test_skip_zero_balance() {
+        let (accounts_tx, accounts) = mpsc::channel();
+        for (client_id, available, held, frozen) in [
+            // e.g. a deposit followed by a full withdrawal.
+            (1, dec!(0), dec!(0), false),
+            // e.g. a chargeback.
+            (2, dec!(0), dec!(0), true),
+            (3, dec!(0), dec!(1.5), false),
+            // e.g. a dispute on a deposit that has already been withdrawn.
+            (4, dec!(-1), dec!(1), false),
+        ] {
+            let mut account = Account::new();
+            account.available = Balance::new(available, dec!(0));
+            account.held = Balance::new(held, dec!(0));
+            account.frozen = frozen;
+            accounts_tx.send((ClientId(client_id), account)).unwrap();
+        }
+        drop(accounts_tx);
+
+        let mut output_stream = Vec::new();
+        let config = OutputConfig {
+            skip_zero_balance: true,
+            ..OutputConfig::default()
+        };
+        super::write_with_format(&mut output_stream, accounts, config).unwrap();
+
+        let want = "client,available,held,total,locked
+2,0.0000,0.0000,0.0000,true
+3,0.0000,1.5000,1.5000,false
+4,-1.0000,1.0000,0.0000,false
+";
+        assert_eq!(want, String::from_utf8(output_stream).unwrap());
+    }
+
+    #[test]
+    fn test_write_json_array() {
+        let mut output_stream = Vec::new();
+        super::write_json_array(&mut output_stream, some_accounts()).unwrap();
+
+        let output = String::from_utf8(output_stream).unwrap();
+        let records: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+        assert_eq!(3, records.len());
+        for (record, (client_id, available, held, total, locked)) in records.iter().zip([
+            (1, "5.0000", "1.0000", "6.0000", false),
+            (2, "1.2340", "123.4000", "124.6340", false),
+            (3, "-500.0050", "600.0060", "100.0010", true),
+        ]) {
+            assert_eq!(client_id, record["client"]);
+            assert_eq!(available, record["available"]);
+            assert_eq!(held, record["held"]);
+            assert_eq!(total, record["total"]);
+            assert_eq!(locked, record["locked"]);
+        }
+    }
+
+    #[test]
+    fn test_write_json_array_no_accounts() {
+        let (_, accounts) = mpsc::channel();
+        let mut output_stream = Vec::new();
+        super::write_json_array(&mut output_stream, accounts).unwrap();
+
+        assert_eq!("[]", String::from_utf8(output_stream).unwrap());
+    }
+
+    #[test]
+    // A ledger saved with bincode and loaded again should be written exactly
+    // like the original one.
+    fn test_write_ledger_from_bincode() {
+        use crate::ledger::{
+            ledger::Ledger,
+            transaction::{Transaction, Type},
+            TransactionId,
+        };
+
+        let mut ledger = Ledger::new();
+        for (tx_type, client_id, tx_id) in [
+            (Type::Deposit(dec!(10)), 1, 1),
+            (Type::Deposit(dec!(2.1234)), 1, 2),
+            (Type::Dispute, 1, 2),
+            (Type::Deposit(dec!(3)), 2, 3),
+            (Type::Withdrawal(dec!(0.5)), 2, 4),
+            (Type::Deposit(dec!(7)), 3, 5),
+            (Type::Dispute, 3, 5),
+            (Type::Chargeback, 3, 5),
+        ] {
+            let transaction =
+                Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap();
+            ledger.apply_transaction(&transaction).unwrap();
+        }
+
+        let mut saved = Vec::new();
+        ledger.save_to_bincode(&mut saved).unwrap();
+        let loaded = Ledger::load_from_bincode(saved.as_slice()).unwrap();
+
+        let mut want = Vec::new();
+        super::write(&mut want, ledger.into_sorted_accounts()).unwrap();
+        let mut got = Vec::new();
+        super::write(&mut got, loaded.into_sorted_accounts()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(want).unwrap(),
+            String::from_utf8(got).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_failures() {
+        use crate::ledger::{account::account::TransactionError, TransactionId};
+
+        let failures = || {
+            vec![(
+                TransactionId(3),
+                TransactionError::UnknownTransaction {
+                    tx_id: TransactionId(3),
+                    client_id: ClientId(1),
+                    amount: None,
+                },
+            )]
+        };
+        let err = failures()[0].1.to_string();
+
+        for (format, want) in [
+            // The error is quoted, as it contains commas.
+            (OutputFormat::Csv, format!("tx,error\n3,\"{err}\"\n")),
+            (OutputFormat::Tsv, format!("tx\terror\n3\t{err}\n")),
+            (
+                OutputFormat::Ndjson,
+                format!("{{\"tx\":3,\"error\":\"{err}\"}}\n"),
+            ),
+        ] {
+            let mut output_stream = Vec::new();
+            let config = OutputConfig {
+                format,
+                ..OutputConfig::default()
+            };
+            super::write_failures(&mut output_stream, failures(), config).unwrap();
+            assert_eq!(
+                want,
+                String::from_utf8(output_stream).unwrap(),
+                "{format:?}"
+            );
+        }
+
+        // The header row is written even without any failure.
+        let mut output_stream = Vec::new();
+        super::write_failures(&mut output_stream, vec![], OutputConfig::default()).unwrap();
+        assert_eq!("tx,error\n", String::from_utf8(output_stream).unwrap());
+    }
+
+    #[test]
+    fn test_format_amount() {
+        for (amount, want) in [
+            (dec!(1), "1.0000"),
+            (dec!(0), "0.0000"),
+            (dec!(1.5), "1.5000"),
+            (dec!(1.23456), "1.2346"),
+            (dec!(-0.5), "-0.5000"),
+            (dec!(123456789.1234), "123456789.1234"),
+        ] {
+            assert_eq!(want, super::format_amount(amount), "{amount}");
+        }
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!(Ok(OutputFormat::Csv), "csv".parse());
+        assert_eq!(Ok(OutputFormat::Tsv), "tsv".parse());
+        assert_eq!(Ok(OutputFormat::Ndjson), "ndjson".parse());
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
 }
@@ -0,0 +1,110 @@
+use crate::ledger::{account::account::Account, Amount, ClientId};
+
+use rust_decimal_macros::dec;
+
+/// Summary of the disputes and chargebacks across all the accounts, for
+/// compliance purposes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReconciliationReport {
+    pub total_clients: usize,
+    pub frozen_clients: usize,
+
+    /// Sum of the amounts of the transactions currently disputed.
+    pub total_disputed_amount: Amount,
+
+    /// Sum of the amounts of the transactions that were charged back.
+    pub total_chargedback_amount: Amount,
+
+    /// Sorted by ascending client ID.
+    pub frozen_client_ids: Vec<ClientId>,
+}
+
+/// Build a reconciliation report from all the accounts of the ledger.
+pub fn reconciliation_report<'a>(
+    accounts: impl IntoIterator<Item = (ClientId, &'a Account)>,
+) -> ReconciliationReport {
+    let mut report = ReconciliationReport {
+        total_clients: 0,
+        frozen_clients: 0,
+        total_disputed_amount: dec!(0),
+        total_chargedback_amount: dec!(0),
+        frozen_client_ids: Vec::new(),
+    };
+
+    for (client_id, account) in accounts {
+        report.total_clients += 1;
+        report.total_disputed_amount += account.disputed_amount();
+        report.total_chargedback_amount += account.chargedback_amount();
+        if account.frozen {
+            report.frozen_clients += 1;
+            report.frozen_client_ids.push(client_id);
+        }
+    }
+
+    report.frozen_client_ids.sort_unstable();
+    report
+}
+
+#[test]
+fn test_reconciliation_report() {
+    use crate::ledger::{
+        transaction::{Transaction, Type},
+        TransactionId,
+    };
+    use std::collections::HashMap;
+
+    let mut ledger: HashMap<ClientId, Account> = HashMap::new();
+    for (tx_type, client_id, tx_id) in [
+        (Type::Deposit(dec!(10)), 1, 1),
+        (Type::Deposit(dec!(2.5)), 1, 2),
+        (Type::Dispute, 1, 1),
+        (Type::Deposit(dec!(3)), 2, 3),
+        (Type::Dispute, 2, 3),
+        (Type::Chargeback, 2, 3),
+        (Type::Deposit(dec!(7)), 3, 4),
+        (Type::Dispute, 3, 4),
+        (Type::Resolve, 3, 4),
+        (Type::Deposit(dec!(1.5)), 4, 5),
+        (Type::Deposit(dec!(0.5)), 4, 6),
+        (Type::Dispute, 4, 5),
+        (Type::Dispute, 4, 6),
+        (Type::Chargeback, 4, 5),
+        (Type::Deposit(dec!(100)), 5, 7),
+    ] {
+        let transaction =
+            Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap();
+        ledger
+            .entry(ClientId(client_id))
+            .or_default()
+            .apply(&transaction)
+            .unwrap();
+    }
+
+    let report = reconciliation_report(
+        ledger
+            .iter()
+            .map(|(client_id, account)| (*client_id, account)),
+    );
+    assert_eq!(
+        ReconciliationReport {
+            total_clients: 5,
+            frozen_clients: 2,
+            // Client 1's tx 1, and client 4's tx 6.
+            total_disputed_amount: dec!(10.5),
+            // Client 2's tx 3, and client 4's tx 5.
+            total_chargedback_amount: dec!(4.5),
+            frozen_client_ids: vec![ClientId(2), ClientId(4)],
+        },
+        report
+    );
+}
+
+#[test]
+fn test_reconciliation_report_no_accounts() {
+    let report = reconciliation_report(std::iter::empty());
+    assert_eq!(0, report.total_clients);
+    assert_eq!(0, report.frozen_clients);
+    assert!(report.total_disputed_amount.is_zero());
+    assert!(report.total_chargedback_amount.is_zero());
+    assert!(report.frozen_client_ids.is_empty());
+}
@@ -1,28 +1,510 @@
-use crate::{error_handler, input::parse, ledger::ledger, output};
+use crate::{
+    error_handler,
+    input::{self, parse, parse_with_budget, ParseConfig},
+    ledger::{budget::ErrorBudget, ledger, transaction::Transaction, LedgerConfig},
+    output::{self, OutputConfig, OutputFormat},
+    strategy::{ErrorStrategy, SinkStrategy},
+};
 
-use std::sync::mpsc;
+use rust_decimal::RoundingStrategy;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    time::{Duration, Instant},
+};
 
+// Set once SIGINT or SIGTERM is received, for the whole process.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+// How often an idle run checks whether it should shut down, e.g. while
+// waiting for more transactions on stdin.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stop the runs gracefully on SIGINT or SIGTERM, instead of killing the
+/// process right away.
+///
+/// The transactions that were not received yet are ignored, but the ones
+/// already received are applied, and the accounts are written and flushed as
+/// usual. A second signal exits immediately, e.g. if the output can't be
+/// written.
+///
+/// This should only be called once, by the binary: a library shouldn't
+/// handle the signals of the whole process.
+///
+/// # Errors
+///
+/// Returns an error if the signal handler can't be installed, e.g. when
+/// another handler was already installed.
+pub fn handle_shutdown_signals() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if SHUTDOWN.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })
+}
+
+/// Whether a signal stopped the runs (see `handle_shutdown_signals()`).
+#[must_use]
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+/// What happened during a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunResult {
+    /// Transactions applied to the ledger.
+    pub transactions_ok: u64,
+
+    /// Transactions that could not be applied, e.g. for insufficient funds.
+    pub transactions_failed: u64,
+
+    /// Records that could not be read.
+    pub parse_errors: u64,
+
+    /// Accounts written to the output. Always 0 for a dry run, which writes
+    /// the failed transactions instead.
+    pub accounts_written: u64,
+
+    pub elapsed: Duration,
+}
+
+impl std::fmt::Display for RunResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} transaction(s) applied, {} failed, {} record(s) could not be read, {} account(s) written in {:.3}s",
+            self.transactions_ok,
+            self.transactions_failed,
+            self.parse_errors,
+            self.accounts_written,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Everything `run()` can be configured with.
+///
+/// The default config reads CSV, writes CSV, and ignores the errors.
+pub struct RunConfig {
+    /// How the input is read. Its `ledger_config` is used to build the ledger
+    /// as well.
+    pub parse_config: ParseConfig,
+
+    pub output_config: OutputConfig,
+
+    pub error_strategy: Box<dyn ErrorStrategy>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            parse_config: ParseConfig::default(),
+            output_config: OutputConfig::default(),
+            error_strategy: Box::new(SinkStrategy),
+        }
+    }
+}
+
+impl RunConfig {
+    /// Build a config, starting from the default values.
+    #[must_use]
+    pub fn builder() -> RunConfigBuilder {
+        RunConfigBuilder {
+            config: Self::default(),
+        }
+    }
+}
+
+/// Sets some of the `RunConfig` values, keeping the defaults for the others.
+pub struct RunConfigBuilder {
+    config: RunConfig,
+}
+
+impl RunConfigBuilder {
+    /// Single byte separating the fields of the input.
+    #[must_use]
+    pub const fn delimiter(mut self, delimiter: u8) -> Self {
+        self.config.parse_config.delimiter = delimiter;
+        self
+    }
+
+    /// Number of decimal places the amounts are rounded to.
+    #[must_use]
+    pub const fn decimal_precision(mut self, decimal_precision: u32) -> Self {
+        self.config.parse_config.ledger_config.decimal_precision = decimal_precision;
+        self
+    }
+
+    /// How the amounts are rounded to the decimal precision.
+    #[must_use]
+    pub const fn rounding_strategy(mut self, rounding_strategy: RoundingStrategy) -> Self {
+        self.config.parse_config.ledger_config.rounding_strategy = rounding_strategy;
+        self
+    }
+
+    /// Replace the whole ledger config, e.g. to enable checks that have no
+    /// dedicated method.
+    #[must_use]
+    pub const fn ledger_config(mut self, ledger_config: LedgerConfig) -> Self {
+        self.config.parse_config.ledger_config = ledger_config;
+        self
+    }
+
+    /// Number of errors after which the processing is aborted.
+    #[must_use]
+    pub const fn max_errors(mut self, max_errors: Option<usize>) -> Self {
+        self.config.parse_config.ledger_config.max_errors = max_errors;
+        self
+    }
+
+    /// Write the transactions that would fail instead of the accounts.
+    #[must_use]
+    pub const fn dry_run(mut self, dry_run: bool) -> Self {
+        self.config.parse_config.ledger_config.dry_run = dry_run;
+        self
+    }
+
+    #[must_use]
+    pub fn error_strategy(mut self, error_strategy: Box<dyn ErrorStrategy>) -> Self {
+        self.config.error_strategy = error_strategy;
+        self
+    }
+
+    /// Format of the output. The input is always delimited.
+    #[must_use]
+    pub const fn output_format(mut self, format: OutputFormat) -> Self {
+        self.config.output_config.format = format;
+        self
+    }
+
+    /// Skip the accounts with no funds at all, unless they are frozen.
+    #[must_use]
+    pub const fn skip_zero_balance(mut self, skip_zero_balance: bool) -> Self {
+        self.config.output_config.skip_zero_balance = skip_zero_balance;
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> RunConfig {
+        self.config
+    }
+}
+
+/// Read transactions from the input stream, build the ledger, and write the
+/// resulting account balances to the output stream, in the configured format.
+///
+/// Returns how many transactions were applied or failed, how many records
+/// could not be read, and how many accounts were written.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+///
+/// # Panics
+///
+/// Panics if one of the worker threads panicked, e.g. on the first error
+/// with `AbortStrategy`.
 pub fn run(
-    input_stream: (impl std::io::Read + Send + 'static),
+    input_stream: impl std::io::Read + Send + 'static,
+    output_stream: impl std::io::Write,
+    config: RunConfig,
+) -> std::io::Result<RunResult> {
+    let RunConfig {
+        parse_config,
+        output_config,
+        error_strategy,
+    } = config;
+
+    run_with_strategy(
+        input_stream,
+        output_stream,
+        |input_stream, budget| parse_with_budget(input_stream, parse_config, budget),
+        output_config,
+        error_strategy,
+        parse_config.ledger_config,
+    )
+}
+
+/// Same as `run()`, but reads the input stream with the given parser
+/// (e.g. `input::parse_ndjson`, or `input::parse` with a custom `ParseConfig`)
+/// instead of the default CSV one.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+///
+/// # Panics
+///
+/// Panics if one of the worker threads panicked.
+pub fn run_with<R>(
+    input_stream: R,
     output_stream: impl std::io::Write,
-) {
-    let (transactions, input_errors) = parse(input_stream);
+    parse: impl FnOnce(R) -> (Receiver<Transaction>, Receiver<input::Error>),
+    output_config: OutputConfig,
+) -> std::io::Result<RunResult> {
+    // Without `max_errors`, the budget is never exhausted.
+    run_with_strategy(
+        input_stream,
+        output_stream,
+        |input_stream, _| parse(input_stream),
+        output_config,
+        Box::new(SinkStrategy),
+        LedgerConfig::default(),
+    )
+}
 
-    let (account_tx, accounts) = mpsc::channel();
-    let transaction_errors = ledger::build(transactions, account_tx);
+/// Same as `run_with()`, but passes each error to the given strategy
+/// (e.g. `LogStrategy` to print them), instead of ignoring them.
+///
+/// The parser is given the error budget the ledger checks before applying
+/// each transaction, e.g. with `input::parse_with_budget()`, so the invalid
+/// records and the failed transactions are counted together: once
+/// `ledger_config.max_errors` of them are found, in the input order, the
+/// remaining transactions are not applied. A parser ignoring the budget only
+/// lets the failed transactions count.
+///
+/// With `ledger_config.dry_run`, the transactions that would fail are written
+/// instead of the accounts (see `output::write_failures()`).
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+///
+/// # Panics
+///
+/// Panics if one of the worker threads panicked, e.g. on the first error
+/// with `AbortStrategy`.
+pub fn run_with_strategy<R>(
+    input_stream: R,
+    mut output_stream: impl std::io::Write,
+    parse: impl FnOnce(R, ErrorBudget) -> (Receiver<Transaction>, Receiver<input::Error>),
+    output_config: OutputConfig,
+    error_strategy: Box<dyn ErrorStrategy>,
+    ledger_config: LedgerConfig,
+) -> std::io::Result<RunResult> {
+    let start = Instant::now();
+    let budget = ErrorBudget::new(ledger_config.max_errors);
+    let (transactions, input_errors) = parse(input_stream, budget.clone());
 
-    let error_handling_threads = error_handler::sink(input_errors, transaction_errors);
-    output::write(output_stream, accounts).expect("failed to write the output"); // Should not fail with stdout.
+    let (transaction_errors_tx, transaction_errors) =
+        mpsc::sync_channel(ledger::DEFAULT_CHANNEL_CAPACITY);
+    // The errors have to be handled while the ledger is being built:
+    // otherwise, the bounded channels would fill up and block it.
+    let error_handling_thread = std::thread::spawn(move || {
+        error_handler::handle(input_errors, transaction_errors, error_strategy.as_ref())
+    });
 
-    // Make sure we write all the errors as well.
-    for thread in error_handling_threads {
-        thread.join().expect("failed to join the threads");
+    // Once the budget is exhausted, the transactions receiver is dropped along
+    // with this iterator when the build returns, so the parser stops as well.
+    // The channel is polled, so a shutdown is noticed even when no transaction
+    // is coming.
+    let transactions = std::iter::from_fn(move || loop {
+        if shutdown_requested() {
+            return None;
+        }
+        match transactions.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+            Ok(transaction) => return Some(transaction),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    });
+    // The output is flushed before a panic from the error strategy is
+    // propagated, so the accounts are still written.
+    let mut run_result = RunResult::default();
+    let result = if ledger_config.dry_run {
+        // The failures are written as the transactions are applied.
+        let mut transaction_count = 0;
+        let failures = ledger::dry_run_with_budget(
+            transactions.inspect(|_| transaction_count += 1),
+            ledger_config,
+            budget,
+        )
+        .inspect(|(_, err)| {
+            run_result.transactions_failed += 1;
+            let _ = transaction_errors_tx.send(err.clone());
+        });
+        let result = output::write_failures(&mut output_stream, failures, output_config);
+        // Closes the channel, so the error handling thread can finish.
+        drop(transaction_errors_tx);
+
+        run_result.transactions_ok = transaction_count - run_result.transactions_failed;
+        result
+    } else {
+        let (ledger, summary) =
+            ledger::build_with_budget(transactions, &transaction_errors_tx, ledger_config, &budget);
+        // Closes the channel, so the error handling thread can finish.
+        drop(transaction_errors_tx);
+
+        run_result.transactions_ok = summary.transactions_processed;
+        run_result.transactions_failed = summary.transactions_failed;
+        let accounts = ledger.into_sorted_accounts().inspect(|(_, account)| {
+            if !output_config.skips(account) {
+                run_result.accounts_written += 1;
+            }
+        });
+        output::write_with_format(&mut output_stream, accounts, output_config)
     }
+    .and_then(|()| output_stream.flush());
+
+    // On shutdown, the parser may still be waiting for its input, e.g. on
+    // stdin, so the errors it has found so far can't be counted.
+    if shutdown_requested() && !error_handling_thread.is_finished() {
+        run_result.elapsed = start.elapsed();
+        return result.map(|()| run_result);
+    }
+
+    // Make sure we handle all the errors as well.
+    let (parse_errors, _) = error_handling_thread
+        .join()
+        .unwrap_or_else(|err| std::panic::resume_unwind(err));
+
+    run_result.parse_errors = parse_errors as u64;
+    run_result.elapsed = start.elapsed();
+    result.map(|()| run_result)
+}
+
+/// Same as `run()`, but reads the input stream asynchronously with
+/// `input::parse_async()`, without spawning any thread.
+///
+/// The errors are passed to the error strategy as they are found, in the
+/// input order, so `max_errors` counts the invalid records and the failed
+/// transactions exactly as they appear.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to, or with
+/// `ErrorKind::Unsupported` for a dry run.
+#[cfg(feature = "async")]
+pub async fn run_async(
+    input_stream: impl tokio::io::AsyncRead + Unpin,
+    mut output_stream: impl std::io::Write,
+    config: RunConfig,
+) -> std::io::Result<RunResult> {
+    use crate::ledger::account::account::TransactionError;
+    use futures::StreamExt;
+    use std::sync::atomic::AtomicUsize;
+
+    let start = Instant::now();
+    let RunConfig {
+        parse_config,
+        output_config,
+        error_strategy,
+    } = config;
+    let ledger_config = parse_config.ledger_config;
+    if ledger_config.dry_run {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "dry runs are not supported by run_async()",
+        ));
+    }
+
+    // Atomics rather than cells, so the future can be sent to another thread.
+    let (parse_errors, error_count) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let budget_exhausted = || {
+        ledger_config
+            .max_errors
+            .is_some_and(|max_errors| error_count.load(Ordering::Relaxed) >= max_errors)
+    };
+    let transactions = input::parse_async(input_stream, parse_config)
+        .take_while(|_| std::future::ready(!budget_exhausted()))
+        .filter_map(|record| {
+            let transaction = record
+                .inspect_err(|err| {
+                    tracing::warn!(?err, "failed to read record");
+                    parse_errors.fetch_add(1, Ordering::Relaxed);
+                    error_count.fetch_add(1, Ordering::Relaxed);
+                    error_strategy.on_parse_error(err);
+                })
+                .ok();
+            std::future::ready(transaction)
+        });
+    let on_error = |err: TransactionError| {
+        tracing::warn!(%err, "failed to apply transaction");
+        error_count.fetch_add(1, Ordering::Relaxed);
+        error_strategy.on_transaction_error(&err);
+    };
+    let (ledger, summary) = ledger::build_async(transactions, on_error, ledger_config).await;
+
+    let mut run_result = RunResult {
+        transactions_ok: summary.transactions_processed,
+        transactions_failed: summary.transactions_failed,
+        parse_errors: parse_errors.into_inner() as u64,
+        ..RunResult::default()
+    };
+    let accounts = ledger.into_sorted_accounts().inspect(|(_, account)| {
+        if !output_config.skips(account) {
+            run_result.accounts_written += 1;
+        }
+    });
+    output::write_with_format(&mut output_stream, accounts, output_config)
+        .and_then(|()| output_stream.flush())?;
+
+    run_result.elapsed = start.elapsed();
+    Ok(run_result)
+}
+
+/// Same as `run()`, but reads several input streams, one after the other.
+///
+/// All the transactions are applied to the same ledger: transactions from
+/// the second stream are applied after all the transactions from the first
+/// one, and so on. Each stream must have its own header row.
+///
+/// # Errors
+///
+/// Returns an error if the output stream cannot be written to.
+///
+/// # Panics
+///
+/// Panics if one of the worker threads panicked.
+pub fn run_multi(
+    input_streams: Vec<impl std::io::Read + Send + 'static>,
+    output_stream: impl std::io::Write,
+    output_config: OutputConfig,
+) -> std::io::Result<RunResult> {
+    run_with(input_streams, output_stream, parse_in_order, output_config)
+}
+
+// We can't simply chain the input streams together: the header row of the
+// second stream would be read as a record. Instead, each stream is parsed on
+// its own, and their transactions and errors are forwarded to a single pair of
+// channels, in order.
+// The transactions and the errors of a stream are forwarded at the same time:
+// otherwise, the parser could wait forever for one of its channels to be read.
+fn parse_in_order(
+    input_streams: Vec<impl std::io::Read + Send + 'static>,
+) -> (Receiver<Transaction>, Receiver<input::Error>) {
+    let (transaction_tx, transaction_rx) = mpsc::sync_channel(input::DEFAULT_CHANNEL_CAPACITY);
+    let (error_tx, error_rx) = mpsc::sync_channel(input::DEFAULT_CHANNEL_CAPACITY);
+
+    std::thread::spawn(move || {
+        for input_stream in input_streams {
+            let (transactions, errors) = parse(input_stream, ParseConfig::default());
+            let aborted = std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    // The errors are still read when they are ignored, so
+                    // the parser doesn't panic.
+                    for err in errors {
+                        let _ = error_tx.send(err);
+                    }
+                });
+                transactions
+                    .into_iter()
+                    .any(|transaction| transaction_tx.send(transaction).is_err())
+            });
+            if aborted {
+                return; // The processing was aborted.
+            }
+        }
+    });
+
+    (transaction_rx, error_rx)
 }
 
 #[test]
 fn end_to_end_test() {
-    let input = r#"type,       client, tx, amount
+    let input = r"type,       client, tx, amount
 deposit,    1,      1,  1.0
 deposit,    2,      2,  2.0
 badly formated record
@@ -30,34 +512,534 @@ deposit,    1,      3,  2.0
 withdrawal, 1,      4,  1.5
 withdrawal, 2,      5,  3.0
 another bad record
-    "#;
-
-    // Data can appear in any order (account 1 or account 2).
-    // To keep the tests as dumb as possible (i.e. I didn't want my test to parse anything),
-    // I'm just checking the output against two variants.
-    // Another solution could have been to always sort the accounts before writing them,
-    // but the assignment clearly states that ordering is not important - it felt
-    // unnecessary to add any logic for sorting.
-    let expected_output_variant_1 = r#"client,available,held,total,locked
-1,1.5,0,1.5,false
-2,2,0,2,false
-"#;
-    let expected_output_variant_2 = r#"client,available,held,total,locked
-2,2,0,2,false
-1,1.5,0,1.5,false
+    ";
+
+    // Accounts are always written sorted by client ID.
+    let expected_output = r"client,available,held,total,locked
+1,1.5000,0.0000,1.5000,false
+2,2.0000,0.0000,2.0000,false
+";
+
+    let mut output_stream = Vec::new();
+    let run_result = run(input.as_bytes(), &mut output_stream, RunConfig::default()).unwrap();
+
+    let actual_output = String::from_utf8(output_stream).unwrap();
+
+    assert_eq!(expected_output, actual_output);
+    // 2 badly formatted records and the trailing blank line, and client 2's
+    // withdrawal.
+    assert_eq!(
+        RunResult {
+            transactions_ok: 4,
+            transactions_failed: 1,
+            parse_errors: 3,
+            accounts_written: 2,
+            elapsed: run_result.elapsed,
+        },
+        run_result
+    );
+}
+
+#[test]
+fn end_to_end_test_run_config() {
+    let input = "type;client;tx;amount
+deposit;1;1;1.255
+deposit;2;2;2.0
+withdrawal;2;3;2.0
+";
+
+    let mut output_stream = Vec::new();
+    run(
+        input.as_bytes(),
+        &mut output_stream,
+        RunConfig::builder()
+            .delimiter(b';')
+            .decimal_precision(2)
+            .output_format(OutputFormat::Tsv)
+            .skip_zero_balance(true)
+            .error_strategy(Box::new(crate::strategy::AbortStrategy))
+            .build(),
+    )
+    .unwrap();
+
+    // Client 2 has no funds left, so it is skipped.
+    assert_eq!(
+        "client\tavailable\theld\ttotal\tlocked\n1\t1.2600\t0.0000\t1.2600\tfalse\n",
+        String::from_utf8(output_stream).unwrap()
+    );
+
+    let config = RunConfig::builder().build();
+    assert_eq!(ParseConfig::default(), config.parse_config);
+    assert_eq!(OutputConfig::default(), config.output_config);
+}
+
+#[test]
+// The parser can't get more than one transaction or error ahead of the ledger:
+// a large input should still be fully processed, without deadlocking.
+fn end_to_end_test_bounded_channels() {
+    use std::fmt::Write;
+
+    let mut input = String::from("type,client,tx,amount\n");
+    for tx_id in 0..10_000 {
+        writeln!(input, "deposit,1,{tx_id},1.0\nbad record").unwrap();
+    }
+
+    let mut output_stream = Vec::new();
+    run_with(
+        std::io::Cursor::new(input),
+        &mut output_stream,
+        |input_stream| input::parse_with_capacity(input_stream, ParseConfig::default(), 1, 1),
+        OutputConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        "client,available,held,total,locked\n1,10000.0000,0.0000,10000.0000,false\n",
+        String::from_utf8(output_stream).unwrap()
+    );
+}
+
+#[test]
+// Once the error budget is exhausted, the following rows should be ignored.
+fn end_to_end_test_max_errors() {
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+bad record
+deposit,2,2,2.0
+another bad record
+deposit,3,3,3.0
+yet another bad record
+deposit,4,4,4.0
+";
+
+    let mut output_stream = Vec::new();
+    let run_result = run_with_strategy(
+        input.as_bytes(),
+        &mut output_stream,
+        |input_stream, budget| {
+            parse_with_budget(
+                input_stream,
+                ParseConfig::builder().max_errors(Some(2)).build(),
+                budget,
+            )
+        },
+        OutputConfig::default(),
+        Box::new(SinkStrategy),
+        LedgerConfig {
+            max_errors: Some(2),
+            ..LedgerConfig::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+2,2.0000,0.0000,2.0000,false
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(
+        RunResult {
+            transactions_ok: 2,
+            transactions_failed: 0,
+            parse_errors: 2,
+            accounts_written: 2,
+            elapsed: run_result.elapsed,
+        },
+        run_result
+    );
+}
+
+#[test]
+// The failed transactions should count towards the error budget as soon as
+// they fail: no transaction should be applied after the budget is exhausted.
+fn end_to_end_test_max_errors_transactions() {
+    use std::fmt::Write;
+
+    let mut input = String::from(
+        "type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,2,5.0
+",
+    );
+    for tx_id in 3..=2000 {
+        writeln!(input, "deposit,2,{tx_id},1.0").unwrap();
+    }
+
+    let ledger_config = LedgerConfig {
+        max_errors: Some(1),
+        ..LedgerConfig::default()
+    };
+    let mut output_stream = Vec::new();
+    let run_result = run_with_strategy(
+        std::io::Cursor::new(input),
+        &mut output_stream,
+        |input_stream, budget| {
+            parse_with_budget(
+                input_stream,
+                ParseConfig::builder().max_errors(Some(1)).build(),
+                budget,
+            )
+        },
+        OutputConfig::default(),
+        Box::new(SinkStrategy),
+        ledger_config,
+    )
+    .unwrap();
+
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(1, run_result.transactions_ok);
+    assert_eq!(1, run_result.transactions_failed);
+}
+
+#[test]
+// The invalid records and the failed transactions should be counted together,
+// in the input order.
+fn end_to_end_test_max_errors_mixed() {
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+bad record
+deposit,2,2,2.0
+withdrawal,1,3,9.0
+deposit,3,4,1
+";
+
+    // Whichever thread finds its error first, client 3 is never applied.
+    for _ in 0..20 {
+        let mut output_stream = Vec::new();
+        let run_result = run(
+            input.as_bytes(),
+            &mut output_stream,
+            RunConfig::builder().max_errors(Some(2)).build(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+2,2.0000,0.0000,2.0000,false
+",
+            String::from_utf8(output_stream).unwrap()
+        );
+        assert_eq!(
+            RunResult {
+                transactions_ok: 2,
+                transactions_failed: 1,
+                parse_errors: 1,
+                accounts_written: 2,
+                elapsed: run_result.elapsed,
+            },
+            run_result
+        );
+    }
+}
+
+#[test]
+// With a dry run, the budget should be checked the same way.
+fn end_to_end_test_dry_run_max_errors() {
+    let input = "type,client,tx,amount
+bad record
+withdrawal,1,1,2.0
+dispute,1,2,
+";
+
+    let mut output_stream = Vec::new();
+    let run_result = run(
+        input.as_bytes(),
+        &mut output_stream,
+        RunConfig::builder()
+            .max_errors(Some(2))
+            .dry_run(true)
+            .build(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        "tx,error
+1,\"insufficient available funds for this withdrawal (client: 1, tx: 1, amount: 2)\"
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(1, run_result.transactions_failed);
+    assert_eq!(1, run_result.parse_errors);
+}
+
+#[test]
+// A dry run should write the transactions that would fail, instead of the
+// accounts.
+fn end_to_end_test_dry_run() {
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,2,2.0
+dispute,1,3,
+";
+
+    let mut output_stream = Vec::new();
+    let run_result = run_with_strategy(
+        input.as_bytes(),
+        &mut output_stream,
+        |input_stream, _| parse(input_stream, ParseConfig::default()),
+        OutputConfig::default(),
+        Box::new(SinkStrategy),
+        LedgerConfig {
+            dry_run: true,
+            ..LedgerConfig::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        "tx,error
+2,\"insufficient available funds for this withdrawal (client: 1, tx: 2, amount: 2)\"
+3,\"the referenced transaction does not exist (client: 1, tx: 3)\"
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(
+        RunResult {
+            transactions_ok: 1,
+            transactions_failed: 2,
+            parse_errors: 0,
+            accounts_written: 0,
+            elapsed: run_result.elapsed,
+        },
+        run_result
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn end_to_end_test_async() {
+    let input = r"type,       client, tx, amount
+deposit,    1,      1,  1.0
+deposit,    2,      2,  2.0
+badly formated record
+deposit,    1,      3,  2.0
+withdrawal, 1,      4,  1.5
+withdrawal, 2,      5,  3.0
+another bad record
+    ";
+
+    let mut output_stream = Vec::new();
+    let run_result = futures::executor::block_on(run_async(
+        input.as_bytes(),
+        &mut output_stream,
+        RunConfig::default(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.5000,0.0000,1.5000,false
+2,2.0000,0.0000,2.0000,false
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(
+        RunResult {
+            transactions_ok: 4,
+            transactions_failed: 1,
+            // The last line only has whitespace.
+            parse_errors: 3,
+            accounts_written: 2,
+            elapsed: run_result.elapsed,
+        },
+        run_result
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+// The errors should be passed to the strategy, and count towards the same
+// budget whether they are invalid records or failed transactions.
+fn end_to_end_test_async_max_errors() {
+    use crate::strategy::LogStrategy;
+    use std::sync::{Arc, Mutex};
+
+    // Shares the logs with the strategy, which is moved to the run.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+bad record
+deposit,2,2,2.0
+withdrawal,1,3,9.0
+deposit,3,4,1.0
+";
+
+    let logs = Logs::default();
+    let mut output_stream = Vec::new();
+    let run_result = futures::executor::block_on(run_async(
+        input.as_bytes(),
+        &mut output_stream,
+        RunConfig::builder()
+            .max_errors(Some(2))
+            .error_strategy(Box::new(LogStrategy::with_writer(logs.clone())))
+            .build(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+2,2.0000,0.0000,2.0000,false
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(
+        RunResult {
+            transactions_ok: 2,
+            transactions_failed: 1,
+            parse_errors: 1,
+            accounts_written: 2,
+            elapsed: run_result.elapsed,
+        },
+        run_result
+    );
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(2, logs.lines().count(), "{logs}");
+    assert!(logs.lines().nth(1).unwrap().contains("tx: 3"), "{logs}");
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn end_to_end_test_async_dry_run() {
+    let err = futures::executor::block_on(run_async(
+        &b"type,client,tx,amount\n"[..],
+        Vec::new(),
+        RunConfig::builder().dry_run(true).build(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(std::io::ErrorKind::Unsupported, err.kind());
+}
+
+#[test]
+fn end_to_end_test_ndjson() {
+    let input = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "0.25"}
 "#;
 
     let mut output_stream = Vec::new();
-    run(input.as_bytes(), &mut output_stream);
+    run_with(
+        input.as_bytes(),
+        &mut output_stream,
+        |input_stream| input::parse_ndjson(input_stream, ParseConfig::default()),
+        OutputConfig {
+            format: output::OutputFormat::Ndjson,
+            ..OutputConfig::default()
+        },
+    )
+    .unwrap();
 
     let actual_output = String::from_utf8(output_stream).unwrap();
+    assert_eq!(
+        r#"{"client":1,"available":"0.7500","held":"0.0000","total":"0.7500","locked":false}
+"#,
+        actual_output
+    );
+}
+
+#[test]
+fn end_to_end_test_tsv() {
+    let input = "type\tclient\ttx\tamount
+deposit\t1\t1\t1.0
+deposit\t1\t3\t2.0
+withdrawal\t1\t4\t1.5
+";
+
+    let mut output_stream = Vec::new();
+    run_with(
+        input.as_bytes(),
+        &mut output_stream,
+        |input_stream| {
+            parse(
+                input_stream,
+                ParseConfig::builder().delimiter(b'\t').build(),
+            )
+        },
+        OutputConfig {
+            format: output::OutputFormat::Tsv,
+            ..OutputConfig::default()
+        },
+    )
+    .unwrap();
+
+    let actual_output = String::from_utf8(output_stream).unwrap();
+    assert_eq!(
+        "client\tavailable\theld\ttotal\tlocked\n1\t1.5000\t0.0000\t1.5000\tfalse\n",
+        actual_output
+    );
+}
+
+#[test]
+// A dispute in the second file should apply to a deposit from the first one.
+fn end_to_end_test_multiple_inputs() {
+    let file_a = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,2.0";
+    let file_b = "type,client,tx,amount
+dispute,1,1,
+withdrawal,1,3,0.5
+";
 
-    assert!(
-        expected_output_variant_1.to_string() == actual_output
-            || expected_output_variant_2.to_string() == actual_output,
-        "actual: {}\nexpected2: {}\nexpected2: {}",
-        actual_output,
-        expected_output_variant_1,
-        expected_output_variant_1
+    let mut output_stream = Vec::new();
+    run_multi(
+        vec![file_a.as_bytes(), file_b.as_bytes()],
+        &mut output_stream,
+        OutputConfig::default(),
+    )
+    .unwrap();
+
+    let actual_output = String::from_utf8(output_stream).unwrap();
+    assert_eq!(
+        "client,available,held,total,locked\n1,1.5000,1.0000,2.5000,false\n",
+        actual_output
+    );
+}
+
+#[test]
+// A stream with more invalid records than the channels can hold shouldn't
+// block the parser before its transactions are read.
+fn end_to_end_test_multiple_inputs_many_errors() {
+    let file_a = format!(
+        "type,client,tx,amount\n{}deposit,1,1,1.0\n",
+        "bad record\n".repeat(input::DEFAULT_CHANNEL_CAPACITY + 1)
+    );
+    let file_b = "type,client,tx,amount\ndeposit,2,2,2.0\n".to_string();
+
+    let mut output_stream = Vec::new();
+    let run_result = run_multi(
+        vec![std::io::Cursor::new(file_a), std::io::Cursor::new(file_b)],
+        &mut output_stream,
+        OutputConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+2,2.0000,0.0000,2.0000,false
+",
+        String::from_utf8(output_stream).unwrap()
+    );
+    assert_eq!(
+        (input::DEFAULT_CHANNEL_CAPACITY + 1) as u64,
+        run_result.parse_errors
     );
 }
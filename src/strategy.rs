@@ -0,0 +1,137 @@
+use crate::{input, ledger::account::account::TransactionError};
+
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+};
+
+/// Decides what to do with the errors found while processing transactions.
+///
+/// The errors are handled on a separate thread, so strategies have to be
+/// `Send` and `Sync`.
+pub trait ErrorStrategy: Send + Sync {
+    /// Called for each record that could not be read.
+    fn on_parse_error(&self, err: &input::Error);
+
+    /// Called for each transaction that could not be applied to the ledger.
+    fn on_transaction_error(&self, err: &TransactionError);
+}
+
+/// Ignore all the errors, and keep processing other transactions.
+///
+/// This is the default behaviour.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SinkStrategy;
+
+impl ErrorStrategy for SinkStrategy {
+    fn on_parse_error(&self, _err: &input::Error) {}
+
+    fn on_transaction_error(&self, _err: &TransactionError) {}
+}
+
+/// Write all the errors, one per line, and keep processing other transactions.
+///
+/// Writes to stderr by default.
+#[derive(Debug)]
+pub struct LogStrategy<W = io::Stderr> {
+    writer: Mutex<W>,
+}
+
+impl Default for LogStrategy {
+    fn default() -> Self {
+        Self::with_writer(io::stderr())
+    }
+}
+
+impl<W: Write + Send> LogStrategy<W> {
+    /// Write the errors to `writer` instead of stderr.
+    pub const fn with_writer(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Get the writer back, e.g. to read what was logged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while logging an error.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().expect("the writer is poisoned")
+    }
+
+    fn log(&self, msg: std::fmt::Arguments) {
+        let mut writer = self.writer.lock().expect("the writer is poisoned");
+        // Failing to log an error shouldn't stop the processing.
+        let _ = writer.write_fmt(msg).and_then(|()| writer.write_all(b"\n"));
+    }
+}
+
+impl<W: Write + Send> ErrorStrategy for LogStrategy<W> {
+    fn on_parse_error(&self, err: &input::Error) {
+        self.log(format_args!("failed to read record: {err}"));
+    }
+
+    fn on_transaction_error(&self, err: &TransactionError) {
+        self.log(format_args!("failed to apply transaction: {err}"));
+    }
+}
+
+/// Panic on the first error.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AbortStrategy;
+
+impl ErrorStrategy for AbortStrategy {
+    fn on_parse_error(&self, err: &input::Error) {
+        panic!("failed to read record: {err}");
+    }
+
+    fn on_transaction_error(&self, err: &TransactionError) {
+        panic!("failed to apply transaction: {err}");
+    }
+}
+
+#[cfg(test)]
+const fn unknown_transaction() -> TransactionError {
+    use crate::ledger::{ClientId, TransactionId};
+
+    TransactionError::UnknownTransaction {
+        tx_id: TransactionId(2),
+        client_id: ClientId(1),
+        amount: None,
+    }
+}
+
+#[test]
+fn test_sink_strategy() {
+    // Nothing to observe: it should simply not panic.
+    SinkStrategy.on_parse_error(&input::Error::MissingHeader);
+    SinkStrategy.on_transaction_error(&unknown_transaction());
+}
+
+#[test]
+fn test_log_strategy() {
+    let strategy = LogStrategy::with_writer(Vec::new());
+    strategy.on_parse_error(&input::Error::MissingHeader);
+    strategy.on_transaction_error(&unknown_transaction());
+
+    assert_eq!(
+        format!(
+            "failed to read record: missing or malformed header row\nfailed to apply transaction: {}\n",
+            unknown_transaction()
+        ),
+        String::from_utf8(strategy.into_inner()).unwrap()
+    );
+}
+
+#[test]
+#[should_panic(expected = "failed to read record")]
+fn test_abort_strategy_parse_error() {
+    AbortStrategy.on_parse_error(&input::Error::MissingHeader);
+}
+
+#[test]
+#[should_panic(expected = "failed to apply transaction")]
+fn test_abort_strategy_transaction_error() {
+    AbortStrategy.on_transaction_error(&unknown_transaction());
+}
@@ -0,0 +1,107 @@
+use proptest::{prelude::*, sample::Index};
+use rust_decimal::Decimal;
+use transaction_engine::ledger::{
+    account::account::{Account, TransactionState},
+    transaction::{Transaction, Type},
+    Amount, ClientId, LedgerConfig, TransactionId,
+};
+
+// Disputes, resolves and chargebacks reference one of the transactions
+// generated so far, so most of them actually apply to something.
+#[derive(Debug, Clone)]
+enum Op {
+    Deposit(Amount),
+    Withdrawal(Amount),
+    Dispute(Index),
+    Resolve(Index),
+    Chargeback(Index),
+}
+
+// Between 0.0001 and 1000, with the ledger's precision.
+fn amount() -> impl Strategy<Value = Amount> {
+    (1_i64..=10_000_000).prop_map(|n| Decimal::new(n, 4))
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => amount().prop_map(Op::Deposit),
+        2 => amount().prop_map(Op::Withdrawal),
+        2 => any::<Index>().prop_map(Op::Dispute),
+        1 => any::<Index>().prop_map(Op::Resolve),
+        1 => any::<Index>().prop_map(Op::Chargeback),
+    ]
+}
+
+fn transactions(ops: Vec<Op>) -> Vec<Transaction> {
+    let mut tx_count: usize = 0;
+    ops.into_iter()
+        .map(|op| {
+            // Before the first deposit or withdrawal, the reference is unknown.
+            let referenced = |index: Index| TransactionId(index.index(tx_count.max(1)) as u64);
+            let (tx_type, tx_id) = match op {
+                Op::Deposit(amount) => (Type::Deposit(amount), TransactionId(tx_count as u64)),
+                Op::Withdrawal(amount) => {
+                    (Type::Withdrawal(amount), TransactionId(tx_count as u64))
+                }
+                Op::Dispute(index) => (Type::Dispute, referenced(index)),
+                Op::Resolve(index) => (Type::Resolve, referenced(index)),
+                Op::Chargeback(index) => (Type::Chargeback, referenced(index)),
+            };
+            if matches!(tx_type, Type::Deposit(_) | Type::Withdrawal(_)) {
+                tx_count += 1;
+            }
+            Transaction::new(tx_type, ClientId(1), tx_id).unwrap()
+        })
+        .collect()
+}
+
+// The balances must always match the transactions the account knows about,
+// whether the last transaction succeeded or not.
+fn check_invariants(account: &Account) {
+    let mut deposited = Amount::ZERO;
+    let mut disputed = Amount::ZERO;
+    let mut withdrawn = Amount::ZERO;
+    for (_, tx_state, amount) in account.history() {
+        match tx_state {
+            TransactionState::Deposited => deposited += amount,
+            TransactionState::Disputed => disputed += amount,
+            TransactionState::Withdrawn => withdrawn += amount,
+            TransactionState::ChargedBack => {}
+        }
+    }
+
+    let (available, held) = (account.available.amount(), account.held.amount());
+    assert_eq!(available + held, account.total_amount());
+    assert_eq!(deposited + disputed - withdrawn, account.total_amount());
+    assert_eq!(disputed, held);
+    assert!(held >= Amount::ZERO, "negative held funds: {held}");
+    assert_eq!(account.frozen, account.chargeback_count() > 0);
+}
+
+proptest! {
+    #[test]
+    fn test_account_invariants(ops in proptest::collection::vec(op(), 0..=100)) {
+        let mut account = Account::new();
+        for transaction in transactions(ops) {
+            let _ = account.apply(&transaction);
+            check_invariants(&account);
+        }
+    }
+
+    #[test]
+    // Without overdrafts, the available funds can never be negative, so the
+    // held funds are part of the total.
+    fn test_account_invariants_no_overdraft(ops in proptest::collection::vec(op(), 0..=100)) {
+        let config = LedgerConfig {
+            allow_negative_available: false,
+            ..LedgerConfig::default()
+        };
+        let mut account = Account::new();
+        for transaction in transactions(ops) {
+            let _ = account.apply_with_config(&transaction, &config);
+            check_invariants(&account);
+            prop_assert!(account.available.amount() >= Amount::ZERO);
+            prop_assert!(account.held.amount() <= account.total_amount());
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+const INPUT: &str = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,2.0
+withdrawal,1,3,1.5
+";
+
+const EXPECTED_OUTPUT: &str = "client,available,held,total,locked
+1,1.5000,0.0000,1.5000,false
+";
+
+#[test]
+// Without a file name, the transactions should be read from stdin.
+fn test_read_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start the binary");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(INPUT.as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(EXPECTED_OUTPUT, String::from_utf8(output.stdout).unwrap());
+
+    // A summary of the run is written to stderr.
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with(
+            "3 transaction(s) applied, 0 failed, 0 record(s) could not be read, 1 account(s) written in "
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+// With --output, the accounts should be written to the given file, replacing
+// its previous content.
+fn test_write_to_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_path = dir.path().join("transactions.csv");
+    let output_path = dir.path().join("accounts.csv");
+    std::fs::write(&input_path, INPUT).unwrap();
+    std::fs::write(
+        &output_path,
+        "previous content that is much longer than the new one ".repeat(10),
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(&input_path)
+        .arg("--output")
+        .arg(&output_path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        EXPECTED_OUTPUT,
+        std::fs::read_to_string(&output_path).unwrap()
+    );
+}
+
+#[test]
+// When the output file can't be created, we should exit with an error
+// message rather than panicking.
+fn test_write_to_invalid_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_path = dir.path().join("transactions.csv");
+    std::fs::write(&input_path, INPUT).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(&input_path)
+        .arg("--output")
+        .arg(dir.path().join("missing_directory").join("accounts.csv"))
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("could not create the output file"),
+        "{stderr}"
+    );
+}
+
+#[test]
+// With --format ndjson, both the transactions and the accounts should be
+// newline-delimited JSON.
+fn test_ndjson_format() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg("--format")
+        .arg("ndjson")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start the binary");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            br#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "0.25"}
+"#,
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        r#"{"client":1,"available":"0.7500","held":"0.0000","total":"0.7500","locked":false}
+"#,
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+// The transactions can be read in one format and the accounts written in
+// another one.
+fn test_input_output_formats() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg("--input-format")
+        .arg("csv")
+        .arg("--output-format")
+        .arg("ndjson")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start the binary");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            b"type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,2,0.25
+",
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        r#"{"client":1,"available":"0.7500","held":"0.0000","total":"0.7500","locked":false}
+"#,
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+// --output-format should override --format for the output only.
+fn test_output_format_override() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg("--format")
+        .arg("ndjson")
+        .arg("--output-format")
+        .arg("csv")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start the binary");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            br#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+"#,
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_missing_input_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(dir.path().join("missing.csv"))
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("could not open the input file"),
+        "{stderr}"
+    );
+}
+
+#[test]
+// --strict should stop at the first invalid line of NDJSON input as well.
+fn test_strict_ndjson() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg("--strict")
+        .arg("--format")
+        .arg("ndjson")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start the binary");
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            br#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+not json at all
+{"type": "deposit", "client": 2, "tx": 2, "amount": "2.0"}
+"#,
+        )
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("failed to read record"), "{stderr}");
+    assert_eq!(
+        r#"{"client":1,"available":"1.0000","held":"0.0000","total":"1.0000","locked":false}
+"#,
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_unknown_format() {
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg("--format")
+        .arg("xml")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("unknown format xml"), "{stderr}");
+}
+
+#[test]
+// With --skip-empty, accounts without any funds should not be written, unless
+// they are frozen.
+fn test_skip_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_path = dir.path().join("transactions.csv");
+    std::fs::write(
+        &input_path,
+        "type,client,tx,amount
+deposit,1,1,1.0
+withdrawal,1,2,1.0
+deposit,2,3,2.0
+dispute,2,3,
+chargeback,2,3,
+deposit,3,4,1.5
+",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(&input_path)
+        .arg("--skip-empty")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        "client,available,held,total,locked
+2,0.0000,0.0000,0.0000,true
+3,1.5000,0.0000,1.5000,false
+",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+// With --strict, the processing should stop at the first invalid row: only the
+// accounts from the rows before it should be written.
+fn test_strict() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_path = dir.path().join("transactions.csv");
+    std::fs::write(
+        &input_path,
+        "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2
+deposit,2,3,2.0
+",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(&input_path)
+        .arg("--strict")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("failed to read record"), "{stderr}");
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+// With --strict, the processing should also stop at the first transaction that
+// can't be applied: the transactions after it should be ignored.
+fn test_strict_transaction_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_path = dir.path().join("transactions.csv");
+    let mut input = String::from("type,client,tx,amount\ndeposit,1,1,1.0\nwithdrawal,1,2,5.0\n");
+    for tx_id in 3..=2000 {
+        input.push_str(&format!("deposit,2,{tx_id},1.0\n"));
+    }
+    std::fs::write(&input_path, input).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(&input_path)
+        .arg("--strict")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("failed to apply transaction"),
+        "{stderr}"
+    );
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+// With --max-errors, the processing should stop once the error budget is
+// exhausted: only the accounts from the rows before that should be written.
+fn test_max_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_path = dir.path().join("transactions.csv");
+    std::fs::write(
+        &input_path,
+        "type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2
+deposit,2,3,2.0
+deposit,2,4
+deposit,3,5,3.0
+deposit,3,6
+",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg(&input_path)
+        .arg("--max-errors")
+        .arg("2")
+        .output()
+        .unwrap();
+
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+2,2.0000,0.0000,2.0000,false
+",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+// --version should print the version from Cargo.toml, without reading stdin.
+fn test_version() {
+    let output = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+        .arg("--version")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        format!("transaction_engine {}\n", env!("CARGO_PKG_VERSION")),
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+#[cfg(unix)]
+// On SIGINT or SIGTERM, the transactions received so far should still be
+// applied, and their accounts written, even though stdin is still open.
+fn test_graceful_shutdown() {
+    for signal in ["INT", "TERM"] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_transaction_engine"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to start the binary");
+
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(INPUT.as_bytes()).unwrap();
+        stdin.flush().unwrap();
+        // Gives the binary time to install its signal handler and to process
+        // the transactions.
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let status = Command::new("kill")
+            .arg(format!("-{signal}"))
+            .arg(child.id().to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        // stdin is only closed once the binary has exited.
+        let output = child.wait_with_output().unwrap();
+        drop(stdin);
+        assert_eq!(Some(1), output.status.code(), "SIG{signal}");
+        assert_eq!(EXPECTED_OUTPUT, String::from_utf8(output.stdout).unwrap());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("interrupted"), "{stderr}");
+    }
+}
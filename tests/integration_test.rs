@@ -0,0 +1,187 @@
+use rust_decimal_macros::dec;
+use std::{fs::File, io::Write};
+use transaction_engine::{
+    input::{self, ParseConfig},
+    ledger::{
+        ledger::{self, Ledger},
+        transaction::{Transaction, Type},
+        Amount, ClientId, LedgerConfig, TransactionId,
+    },
+    output,
+    run::{run, RunConfig},
+};
+
+// Write the input to a temporary file, and run the whole pipeline on it.
+fn run_on_file(input: &str) -> String {
+    let mut input_file = tempfile::NamedTempFile::new().unwrap();
+    input_file.write_all(input.as_bytes()).unwrap();
+
+    let mut output = Vec::new();
+    run(
+        File::open(input_file.path()).unwrap(),
+        &mut output,
+        RunConfig::default(),
+    )
+    .unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+// The output is compared byte for byte, so any change to the header, the field
+// order or the decimal format is caught.
+fn test_full_pipeline_csv() {
+    let input = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,2,2.0
+deposit,1,3,2.0
+withdrawal,1,4,1.5
+withdrawal,2,5,3.0
+";
+
+    assert_eq!(
+        include_str!("fixtures/expected_output.csv"),
+        run_on_file(input)
+    );
+}
+
+#[test]
+// Covers the whole state machine: client 1 has a pending dispute, client 2's
+// dispute was resolved, and client 3 was charged back after withdrawing part
+// of the deposit, so its account is frozen and overdrawn.
+fn test_full_pipeline_csv_disputes() {
+    let input = "type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.5
+deposit,3,3,100.1234
+deposit,1,4,3.0
+withdrawal,3,5,0.1234
+dispute,1,1,
+dispute,2,2,
+resolve,2,2,
+dispute,3,3,
+chargeback,3,3,
+deposit,3,6,1.0
+withdrawal,2,7,1.25
+";
+
+    assert_eq!(
+        include_str!("fixtures/expected_output_disputes.csv"),
+        run_on_file(input)
+    );
+}
+
+// Apply the transactions one by one, checking the balances of client 1 after
+// each of them: `(available, held)`.
+fn apply_and_check(transactions: Vec<(Type, u64)>, want_balances: &[(Amount, Amount)]) {
+    let mut ledger = Ledger::new();
+    for ((tx_type, tx_id), (want_available, want_held)) in
+        transactions.into_iter().zip(want_balances)
+    {
+        let transaction = Transaction::new(tx_type, ClientId(1), TransactionId(tx_id)).unwrap();
+        ledger.apply_transaction(&transaction).unwrap();
+
+        let account = ledger.get_account(ClientId(1)).unwrap();
+        let (available, held) = (account.available.amount(), account.held.amount());
+        assert_eq!(available + held, account.total_amount(), "tx {tx_id}");
+        assert_eq!(
+            (*want_available, *want_held),
+            (available, held),
+            "tx {tx_id}"
+        );
+    }
+}
+
+#[test]
+// A dispute moves the funds to held, and resolving it moves them back: the
+// total never changes.
+fn test_dispute_resolve_preserves_total() {
+    apply_and_check(
+        vec![
+            (Type::Deposit(dec!(1000.0000)), 1),
+            (Type::Dispute, 1),
+            (Type::Resolve, 1),
+        ],
+        &[
+            (dec!(1000.0000), dec!(0)),
+            (dec!(0), dec!(1000.0000)),
+            (dec!(1000.0000), dec!(0)),
+        ],
+    );
+}
+
+#[test]
+fn test_overlapping_disputes_resolve_preserves_total() {
+    apply_and_check(
+        vec![
+            (Type::Deposit(dec!(1000.0000)), 1),
+            (Type::Deposit(dec!(200)), 2),
+            (Type::Deposit(dec!(30)), 3),
+            (Type::Dispute, 1),
+            (Type::Dispute, 2),
+            (Type::Dispute, 3),
+            (Type::Resolve, 2),
+            (Type::Resolve, 1),
+            (Type::Resolve, 3),
+        ],
+        &[
+            (dec!(1000), dec!(0)),
+            (dec!(1200), dec!(0)),
+            (dec!(1230), dec!(0)),
+            (dec!(230), dec!(1000)),
+            (dec!(30), dec!(1200)),
+            (dec!(0), dec!(1230)),
+            (dec!(200), dec!(1030)),
+            (dec!(1200), dec!(30)),
+            (dec!(1230), dec!(0)),
+        ],
+    );
+}
+
+#[test]
+// Each client only gets their own deposits, whether the clients are processed
+// one transaction after the other or in parallel.
+fn test_many_clients_no_cross_client_contamination() {
+    use std::fmt::Write;
+
+    const CLIENTS: u32 = 1000;
+    const DEPOSITS: u32 = 10;
+
+    let mut input = String::from("type,client,tx,amount\n");
+    let mut tx_id = 0;
+    // The deposits of the different clients are interleaved.
+    for deposit in 0..DEPOSITS {
+        for client_id in 1..=CLIENTS {
+            tx_id += 1;
+            let amount = Amount::new(i64::from(client_id * DEPOSITS + deposit), 2);
+            writeln!(input, "deposit,{client_id},{tx_id},{amount}").unwrap();
+        }
+    }
+
+    let sequential_output = run_on_file(&input);
+
+    let mut want_output = String::from("client,available,held,total,locked\n");
+    for client_id in 1..=CLIENTS {
+        let total: Amount = (0..DEPOSITS)
+            .map(|deposit| Amount::new(i64::from(client_id * DEPOSITS + deposit), 2))
+            .sum();
+        let total = output::format_amount(total);
+        writeln!(want_output, "{client_id},{total},0.0000,{total},false").unwrap();
+    }
+    assert_eq!(want_output, sequential_output);
+
+    let (transactions, parse_errors) =
+        input::parse(std::io::Cursor::new(input), ParseConfig::default());
+    let (errors_tx, errors) = std::sync::mpsc::sync_channel(ledger::DEFAULT_CHANNEL_CAPACITY);
+    let (ledger, _) = ledger::build_parallel(transactions, &errors_tx, LedgerConfig::default());
+    drop(errors_tx);
+    assert_eq!(0, parse_errors.iter().count());
+    assert_eq!(0, errors.iter().count());
+
+    let mut parallel_output = Vec::new();
+    output::write(&mut parallel_output, ledger.into_sorted_accounts()).unwrap();
+    assert_eq!(
+        sequential_output,
+        String::from_utf8(parallel_output).unwrap()
+    );
+}
@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+
+use rust_decimal_macros::dec;
+use transaction_engine::{
+    error_handler,
+    ledger::{
+        ledger,
+        transaction::{Transaction, Type},
+        ClientId, TransactionId,
+    },
+};
+
+// Collects every record, instead of printing them.
+struct Logger(Mutex<Vec<(log::Level, String)>>);
+
+impl log::Log for Logger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger(Mutex::new(Vec::new()));
+
+#[test]
+// Without a `tracing` subscriber, the errors should still be reported to the
+// `log` backend when they are handled.
+fn test_build_logs_failed_transactions() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let transactions = vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(4)), ClientId(1), TransactionId(2)).unwrap(),
+    ];
+    let (errors_tx, errors) = std::sync::mpsc::sync_channel(3);
+    ledger::build(transactions, &errors_tx);
+    drop(errors_tx);
+    let (_, input_errors) = std::sync::mpsc::channel();
+    let _ = error_handler::count(input_errors, errors);
+
+    let records = LOGGER.0.lock().unwrap();
+    assert!(
+        records
+            .iter()
+            .any(|(level, message)| *level == log::Level::Warn
+                && message.contains("failed to apply transaction")
+                && message.contains("tx: 2")),
+        "{records:?}"
+    );
+}
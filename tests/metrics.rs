@@ -0,0 +1,71 @@
+#![cfg(feature = "metrics")]
+
+use rust_decimal_macros::dec;
+use transaction_engine::{
+    ledger::{
+        ledger::Ledger,
+        transaction::{Transaction, Type},
+        ClientId, TransactionId,
+    },
+    metrics,
+};
+
+// The value of the metric with the given name and labels, from the default
+// registry.
+fn metric_value(name: &str, label: Option<(&str, &str)>) -> f64 {
+    let family = prometheus::gather()
+        .into_iter()
+        .find(|family| family.name() == name)
+        .unwrap_or_else(|| panic!("missing metric {name}"));
+    let metric = family
+        .get_metric()
+        .iter()
+        .find(|metric| {
+            label.is_none_or(|(label_name, label_value)| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|pair| pair.name() == label_name && pair.value() == label_value)
+            })
+        })
+        .unwrap_or_else(|| panic!("missing labels {label:?} for {name}"));
+    metric.get_counter().get_value()
+}
+
+#[test]
+// The metrics are global, so they are all checked in a single test.
+fn test_metrics() {
+    let mut ledger = Ledger::new();
+    for (tx_type, client_id, tx_id) in [
+        (Type::Deposit(dec!(10)), 1, 1),
+        (Type::Deposit(dec!(2.5)), 1, 2),
+        (Type::Dispute, 1, 2),
+        (Type::Chargeback, 1, 2),
+        (Type::Deposit(dec!(1)), 1, 3), // The account is frozen.
+        (Type::Deposit(dec!(4)), 2, 4),
+        (Type::Withdrawal(dec!(5)), 2, 5), // Not enough funds.
+        (Type::Dispute, 2, 4),
+    ] {
+        let transaction =
+            Transaction::new(tx_type, ClientId(client_id), TransactionId(tx_id)).unwrap();
+        let _ = ledger.apply_transaction(&transaction);
+    }
+
+    assert_eq!(
+        6.0,
+        metric_value("transactions_total", Some(("status", "ok")))
+    );
+    assert_eq!(
+        2.0,
+        metric_value("transactions_total", Some(("status", "error")))
+    );
+    assert_eq!(1.0, metric_value("accounts_frozen_total", None));
+    assert_eq!(6.5, metric_value("disputed_amount_total", None));
+
+    let text = metrics::get_metrics();
+    assert!(
+        text.contains("transactions_total{status=\"ok\"} 6"),
+        "{text}"
+    );
+    assert!(text.contains("accounts_frozen_total 1"), "{text}");
+}
@@ -0,0 +1,162 @@
+use std::{
+    io::Write,
+    sync::{mpsc, Arc, Mutex},
+};
+
+use rust_decimal_macros::dec;
+use tracing_subscriber::fmt::format::FmtSpan;
+use transaction_engine::ledger::{
+    account::account::{Account, TransactionError},
+    ledger,
+    transaction::{Transaction, Type},
+    ClientId, LedgerConfig, TransactionId,
+};
+
+// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+// Each transaction should get its own span, and only the summary of the build
+// should be logged: the errors are reported by whoever reads them.
+fn test_build_emits_spans() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::NEW)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+
+    let transactions = vec![
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(4)), ClientId(1), TransactionId(2)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(100)), ClientId(1), TransactionId(3)).unwrap(),
+    ];
+    let (errors_tx, _errors) = mpsc::sync_channel(3);
+    tracing::subscriber::with_default(subscriber, || {
+        ledger::build(transactions, &errors_tx);
+    });
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let spans = logs
+        .lines()
+        .filter(|line| line.ends_with("transaction_engine::ledger::ledger: new"))
+        .count();
+    assert_eq!(3, spans, "{logs}");
+    assert!(
+        !logs
+            .lines()
+            .any(|line| line.contains("transaction failed") || line.contains("transaction applied")),
+        "{logs}"
+    );
+    assert_eq!(
+        1,
+        logs.lines()
+            .filter(|line| line.contains("INFO") && line.contains("ledger built"))
+            .count(),
+        "{logs}"
+    );
+}
+
+#[test]
+// Each transaction applied to an account should get its own span, with the
+// transaction and client ids.
+fn test_account_apply_emits_spans() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::NEW)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+
+    let transactions = [
+        Transaction::new(Type::Deposit(dec!(10)), ClientId(7), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Dispute, ClientId(7), TransactionId(1)).unwrap(),
+        Transaction::new(Type::Withdrawal(dec!(4)), ClientId(7), TransactionId(2)).unwrap(),
+    ];
+    let mut account = Account::new();
+    tracing::subscriber::with_default(subscriber, || {
+        for transaction in &transactions {
+            let _ = account.apply(transaction);
+        }
+    });
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    for tx_id in [1, 2] {
+        assert!(
+            logs.lines().any(|line| line
+                .contains(&format!("apply_with_config{{tx_id={tx_id} client_id=7}}"))
+                && line.ends_with("transaction_engine::ledger::account::account: new")),
+            "{logs}"
+        );
+    }
+    assert_eq!(
+        3,
+        logs.lines()
+            .filter(|line| line.ends_with("transaction_engine::ledger::account::account: new"))
+            .count(),
+        "{logs}"
+    );
+    // The withdrawal fails: the error is recorded in its span, but only at the
+    // debug level, since it is reported by whoever handles it.
+    assert!(
+        logs.lines().any(|line| line.contains("DEBUG")
+            && line.contains("apply_with_config{tx_id=2 client_id=7}")
+            && line.contains("insufficient available funds")),
+        "{logs}"
+    );
+    assert!(!logs.lines().any(|line| line.contains("WARN")), "{logs}");
+}
+
+#[test]
+// At the info level, nothing should be logged for each transaction: only the
+// summary of the build, once, even when the clients are built separately.
+fn test_info_level_only_logs_the_summary() {
+    let transactions = || {
+        vec![
+            Transaction::new(Type::Deposit(dec!(10)), ClientId(1), TransactionId(1)).unwrap(),
+            Transaction::new(Type::Deposit(dec!(5)), ClientId(2), TransactionId(2)).unwrap(),
+            Transaction::new(Type::Withdrawal(dec!(100)), ClientId(1), TransactionId(3)).unwrap(),
+        ]
+    };
+
+    for build in [
+        ledger::build,
+        |transactions: Vec<Transaction>, errors: &mpsc::SyncSender<TransactionError>| {
+            ledger::build_parallel(transactions, errors, LedgerConfig::default())
+        },
+    ] {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_span_events(FmtSpan::NEW)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let (errors_tx, _errors) = mpsc::sync_channel(3);
+        tracing::subscriber::with_default(subscriber, || {
+            build(transactions(), &errors_tx);
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(1, logs.lines().count(), "{logs}");
+        assert!(logs.contains("ledger built"), "{logs}");
+    }
+}